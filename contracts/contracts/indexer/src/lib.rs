@@ -0,0 +1,588 @@
+//! Rebuilds farmers market state from the contract's events into SQLite.
+//!
+//! Events are consumed in the shape `soroban_sdk::testutils::Events::all`
+//! produces them: `(contract, topics, payload)`. Market and stake rows are
+//! derived only from event payloads, so they can be checked against
+//! `get_market` / `get_stake` to prove the event stream is complete.
+use std::fmt;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use soroban_sdk::{symbol_short, Address, Env, Symbol, TryFromVal, Val};
+use stellar_farmers_market::{events, Market, Outcome, Stake};
+
+mod schema;
+
+// -------------------------------
+// Errors
+// -------------------------------
+#[derive(Debug)]
+pub enum IndexerError {
+    Sql(rusqlite::Error),
+    /// Event topics or payload didn't decode into the expected type
+    Decode(&'static str),
+    /// Payload was published with a schema version this indexer doesn't know
+    UnsupportedVersion(u32),
+    /// Amount doesn't fit in a SQLite integer
+    AmountOverflow(i128),
+    /// Event references a market the indexer hasn't seen created
+    UnknownMarket(u32),
+    /// Reconstructed state differs from the contract's
+    Mismatch {
+        market_id: u32,
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for IndexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexerError::Sql(err) => write!(f, "sqlite: {err}"),
+            IndexerError::Decode(what) => write!(f, "could not decode {what}"),
+            IndexerError::UnsupportedVersion(v) => write!(f, "unsupported event version {v}"),
+            IndexerError::AmountOverflow(v) => write!(f, "amount {v} does not fit in i64"),
+            IndexerError::UnknownMarket(id) => write!(f, "event for unknown market {id}"),
+            IndexerError::Mismatch { market_id, field } => {
+                write!(f, "market {market_id}: {field} does not match the contract")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IndexerError {}
+
+impl From<rusqlite::Error> for IndexerError {
+    fn from(err: rusqlite::Error) -> Self {
+        IndexerError::Sql(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, IndexerError>;
+
+// -------------------------------
+// Query types
+// -------------------------------
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MarketStatus {
+    Open,      // accepting bets
+    Closed,    // past close_ts, waiting for resolution
+    Resolved,  // resolved by the resolver
+    Cancelled, // voided by the admin
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketRow {
+    pub id: u32,
+    pub question: String,
+    pub creator: String,
+    pub category: String,
+    pub close_ts: u64,
+    pub resolution_ts: u64,
+    pub status: MarketStatus, // Open until resolved or cancelled
+    pub outcome: Option<Outcome>,
+    pub yes_pool: i128,
+    pub no_pool: i128,
+    pub bond: i128,
+    pub cancel_reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakeRow {
+    pub market_id: u32,
+    pub user: String,
+    pub yes: i128,
+    pub no: i128,
+    pub claimed: bool,
+    pub payout: i128,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ActivityKind {
+    Bet { side_yes: bool },
+    Claim,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Activity {
+    pub market_id: u32,
+    pub kind: ActivityKind,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LeaderboardMetric {
+    Volume, // total KALE bet, whatever happened to the shares since
+    Payout, // total KALE claimed
+    NetPnl, // claimed minus staked, over settled markets
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeaderboardEntry {
+    pub user: String,
+    pub value: i128,
+}
+
+// -------------------------------
+// Indexer
+// -------------------------------
+pub struct Indexer {
+    conn: Connection,
+}
+
+impl Indexer {
+    /// Open (or create) an index database on disk
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open a throwaway index, mostly for tests
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(schema::SCHEMA)?;
+        Ok(Indexer { conn })
+    }
+
+    /// Apply the events `contract_id` published, in order. Events from other
+    /// contracts (e.g. the KALE SAC) and topics the indexer doesn't track are
+    /// skipped. Returns the number of events applied.
+    pub fn ingest(
+        &mut self,
+        env: &Env,
+        contract_id: &Address,
+        events: &soroban_sdk::Vec<(Address, soroban_sdk::Vec<Val>, Val)>,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut applied = 0;
+        for (contract, topics, data) in events.iter() {
+            if &contract != contract_id {
+                continue;
+            }
+            if apply_event(&tx, env, &topics, &data)? {
+                applied += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(applied)
+    }
+
+    pub fn market(&self, market_id: u32) -> Result<Option<MarketRow>> {
+        let row = self
+            .conn
+            .query_row(
+                &format!("SELECT {MARKET_COLUMNS} FROM markets WHERE id = ?1"),
+                params![market_id],
+                market_from_row,
+            )
+            .optional()?;
+        Ok(row)
+    }
+
+    pub fn stake(&self, market_id: u32, user: &Address) -> Result<Option<StakeRow>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT market_id, user, yes, no, claimed, payout FROM stakes
+                 WHERE market_id = ?1 AND user = ?2",
+                params![market_id, address_key(user)],
+                stake_from_row,
+            )
+            .optional()?;
+        Ok(row)
+    }
+
+    /// Markets in a status as of `now`, oldest first
+    pub fn markets_by_status(&self, status: MarketStatus, now: u64) -> Result<Vec<MarketRow>> {
+        let filter = match status {
+            MarketStatus::Open => "status = 'open' AND close_ts > ?1",
+            MarketStatus::Closed => "status = 'open' AND close_ts <= ?1",
+            MarketStatus::Resolved => "status = 'resolved'",
+            MarketStatus::Cancelled => "status = 'cancelled'",
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {MARKET_COLUMNS} FROM markets WHERE {filter} ORDER BY id"
+        ))?;
+        let rows = match status {
+            MarketStatus::Open | MarketStatus::Closed => {
+                stmt.query_map(params![to_sql_u64(now)], market_from_row)?
+            }
+            MarketStatus::Resolved | MarketStatus::Cancelled => {
+                stmt.query_map([], market_from_row)?
+            }
+        };
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// A user's bets and claims, in the order they happened
+    pub fn user_history(&self, user: &Address) -> Result<Vec<Activity>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT market_id, kind, side_yes, amount, timestamp FROM activity
+             WHERE user = ?1 ORDER BY seq",
+        )?;
+        let rows = stmt.query_map(params![address_key(user)], |row| {
+            let kind: String = row.get(1)?;
+            let side_yes: Option<bool> = row.get(2)?;
+            Ok(Activity {
+                market_id: row.get(0)?,
+                kind: match (kind.as_str(), side_yes) {
+                    ("bet", Some(side_yes)) => ActivityKind::Bet { side_yes },
+                    _ => ActivityKind::Claim,
+                },
+                amount: row.get::<_, i64>(3)? as i128,
+                timestamp: row.get::<_, i64>(4)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Top `limit` users by `metric`, ties broken by address
+    pub fn leaderboard(
+        &self,
+        metric: LeaderboardMetric,
+        limit: u32,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        let sql = match metric {
+            LeaderboardMetric::Volume => {
+                "SELECT user, SUM(amount) AS value FROM activity WHERE kind = 'bet'
+                 GROUP BY user ORDER BY value DESC, user LIMIT ?1"
+            }
+            LeaderboardMetric::Payout => {
+                "SELECT user, SUM(payout) AS value FROM stakes
+                 GROUP BY user ORDER BY value DESC, user LIMIT ?1"
+            }
+            LeaderboardMetric::NetPnl => {
+                "SELECT s.user, SUM(s.payout) - SUM(s.yes + s.no) AS value
+                 FROM stakes s JOIN markets m ON m.id = s.market_id
+                 WHERE m.status != 'open'
+                 GROUP BY s.user ORDER BY value DESC, s.user LIMIT ?1"
+            }
+        };
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(LeaderboardEntry {
+                user: row.get(0)?,
+                value: row.get::<_, i64>(1)? as i128,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Check the reconstructed market against the contract's `get_market`
+    pub fn verify_market(&self, market: &Market) -> Result<()> {
+        let row = self
+            .market(market.id)?
+            .ok_or(IndexerError::UnknownMarket(market.id))?;
+        let mismatch = |field| IndexerError::Mismatch {
+            market_id: market.id,
+            field,
+        };
+
+        if row.yes_pool != market.yes_pool {
+            return Err(mismatch("yes_pool"));
+        }
+        if row.no_pool != market.no_pool {
+            return Err(mismatch("no_pool"));
+        }
+        if row.bond != market.bond {
+            return Err(mismatch("bond"));
+        }
+        if (row.status != MarketStatus::Open) != market.resolved {
+            return Err(mismatch("resolved"));
+        }
+        if market.resolved && row.outcome.as_ref() != Some(&market.outcome) {
+            return Err(mismatch("outcome"));
+        }
+        Ok(())
+    }
+
+    /// Check a reconstructed stake against the contract's `get_stake`
+    pub fn verify_stake(&self, market_id: u32, user: &Address, stake: &Stake) -> Result<()> {
+        let row = self.stake(market_id, user)?.unwrap_or(StakeRow {
+            market_id,
+            user: address_key(user),
+            yes: 0,
+            no: 0,
+            claimed: false,
+            payout: 0,
+        });
+        let mismatch = |field| IndexerError::Mismatch { market_id, field };
+
+        if row.yes != stake.yes {
+            return Err(mismatch("stake.yes"));
+        }
+        if row.no != stake.no {
+            return Err(mismatch("stake.no"));
+        }
+        if row.claimed != stake.claimed {
+            return Err(mismatch("stake.claimed"));
+        }
+        Ok(())
+    }
+}
+
+// -------------------------------
+// Event application
+// -------------------------------
+
+fn decode<T: TryFromVal<Env, Val>>(env: &Env, v: &Val, what: &'static str) -> Result<T> {
+    T::try_from_val(env, v).map_err(|_| IndexerError::Decode(what))
+}
+
+// Oldest payload version the tracked events still decode from. Version 2
+// only added `to_lp` to FeeCollected, which the index doesn't read.
+const MIN_EVENT_VERSION: u32 = 1;
+
+fn check_version(version: u32) -> Result<()> {
+    if !(MIN_EVENT_VERSION..=events::EVENT_VERSION).contains(&version) {
+        return Err(IndexerError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// Apply one contract event; false if the topic isn't one the index tracks
+fn apply_event(
+    conn: &Connection,
+    env: &Env,
+    topics: &soroban_sdk::Vec<Val>,
+    data: &Val,
+) -> Result<bool> {
+    let topic = topics.get(0).ok_or(IndexerError::Decode("event topic"))?;
+    let topic: Symbol = decode(env, &topic, "event topic")?;
+
+    if topic == symbol_short!("Created") {
+        let ev: events::MarketCreated = decode(env, data, "MarketCreated")?;
+        check_version(ev.version)?;
+        conn.execute(
+            "INSERT INTO markets (id, question, creator, category, close_ts, resolution_ts,
+                                  created_at, status, bond)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'open', ?8)",
+            params![
+                ev.market_id,
+                to_std_string(&ev.question),
+                address_key(&ev.creator),
+                format!("{:?}", ev.category),
+                to_sql_u64(ev.close_ts),
+                to_sql_u64(ev.resolution_ts),
+                to_sql_u64(ev.timestamp),
+                to_sql_amount(ev.bond)?,
+            ],
+        )?;
+    } else if topic == symbol_short!("Metadata") {
+        let ev: events::MetadataUpdated = decode(env, data, "MetadataUpdated")?;
+        check_version(ev.version)?;
+        update_market(
+            conn,
+            ev.market_id,
+            "UPDATE markets SET category = ?2 WHERE id = ?1",
+            params![ev.market_id, format!("{:?}", ev.category)],
+        )?;
+    } else if topic == symbol_short!("Bet") {
+        let ev: events::BetPlaced = decode(env, data, "BetPlaced")?;
+        check_version(ev.version)?;
+        set_pools(conn, ev.market_id, ev.yes_pool, ev.no_pool)?;
+        set_stake(conn, ev.market_id, &ev.user, ev.user_yes, ev.user_no)?;
+        conn.execute(
+            "INSERT INTO activity (market_id, user, kind, side_yes, amount, timestamp)
+             VALUES (?1, ?2, 'bet', ?3, ?4, ?5)",
+            params![
+                ev.market_id,
+                address_key(&ev.user),
+                ev.side_yes,
+                to_sql_amount(ev.amount)?,
+                to_sql_u64(ev.timestamp),
+            ],
+        )?;
+    } else if topic == symbol_short!("ShareXfer") {
+        let ev: events::SharesTransferred = decode(env, data, "SharesTransferred")?;
+        check_version(ev.version)?;
+        set_stake(conn, ev.market_id, &ev.from, ev.from_yes, ev.from_no)?;
+        set_stake(conn, ev.market_id, &ev.to, ev.to_yes, ev.to_no)?;
+    } else if topic == symbol_short!("LpAdd") {
+        // LP deposits go straight into both pools without a stake row
+        let ev: events::LiquidityAdded = decode(env, data, "LiquidityAdded")?;
+        check_version(ev.version)?;
+        set_pools(conn, ev.market_id, ev.yes_pool, ev.no_pool)?;
+    } else if topic == symbol_short!("Order") {
+        // Asks move shares out of the trader's stake into escrow
+        let ev: events::OrderPlaced = decode(env, data, "OrderPlaced")?;
+        check_version(ev.version)?;
+        set_stake(conn, ev.market_id, &ev.trader, ev.trader_yes, ev.trader_no)?;
+    } else if topic == symbol_short!("Fill") {
+        let ev: events::OrderFilled = decode(env, data, "OrderFilled")?;
+        check_version(ev.version)?;
+        set_stake(conn, ev.market_id, &ev.buyer, ev.buyer_yes, ev.buyer_no)?;
+    } else if topic == symbol_short!("OrdCancel") {
+        let ev: events::OrderCancelled = decode(env, data, "OrderCancelled")?;
+        check_version(ev.version)?;
+        set_stake(conn, ev.market_id, &ev.trader, ev.trader_yes, ev.trader_no)?;
+    } else if topic == symbol_short!("Resolved") {
+        let ev: events::MarketResolved = decode(env, data, "MarketResolved")?;
+        check_version(ev.version)?;
+        update_market(
+            conn,
+            ev.market_id,
+            "UPDATE markets SET status = 'resolved', outcome = ?2 WHERE id = ?1",
+            params![ev.market_id, outcome_key(&ev.outcome)],
+        )?;
+        set_pools(conn, ev.market_id, ev.yes_pool, ev.no_pool)?;
+    } else if topic == symbol_short!("Cancel") {
+        let ev: events::MarketCancelled = decode(env, data, "MarketCancelled")?;
+        check_version(ev.version)?;
+        update_market(
+            conn,
+            ev.market_id,
+            "UPDATE markets SET status = 'cancelled', outcome = 'invalid', bond = 0,
+                                cancel_reason = ?2 WHERE id = ?1",
+            params![ev.market_id, to_std_string(&ev.reason)],
+        )?;
+        set_pools(conn, ev.market_id, ev.yes_pool, ev.no_pool)?;
+    } else if topic == symbol_short!("BondBack") {
+        let ev: events::BondReturned = decode(env, data, "BondReturned")?;
+        check_version(ev.version)?;
+        update_market(
+            conn,
+            ev.market_id,
+            "UPDATE markets SET bond = 0 WHERE id = ?1",
+            params![ev.market_id],
+        )?;
+    } else if topic == symbol_short!("Claimed") {
+        let ev: events::Claimed = decode(env, data, "Claimed")?;
+        check_version(ev.version)?;
+        let user = address_key(&ev.user);
+        conn.execute(
+            "UPDATE stakes SET claimed = 1, payout = payout + ?3
+             WHERE market_id = ?1 AND user = ?2",
+            params![ev.market_id, user, to_sql_amount(ev.payout)?],
+        )?;
+        conn.execute(
+            "INSERT INTO activity (market_id, user, kind, amount, timestamp)
+             VALUES (?1, ?2, 'claim', ?3, ?4)",
+            params![
+                ev.market_id,
+                user,
+                to_sql_amount(ev.payout)?,
+                to_sql_u64(ev.timestamp)
+            ],
+        )?;
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+fn update_market(
+    conn: &Connection,
+    market_id: u32,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<()> {
+    if conn.execute(sql, params)? == 0 {
+        return Err(IndexerError::UnknownMarket(market_id));
+    }
+    Ok(())
+}
+
+fn set_stake(conn: &Connection, market_id: u32, user: &Address, yes: i128, no: i128) -> Result<()> {
+    conn.execute(
+        "INSERT INTO stakes (market_id, user, yes, no) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (market_id, user) DO UPDATE SET yes = ?3, no = ?4",
+        params![
+            market_id,
+            address_key(user),
+            to_sql_amount(yes)?,
+            to_sql_amount(no)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn set_pools(conn: &Connection, market_id: u32, yes_pool: i128, no_pool: i128) -> Result<()> {
+    update_market(
+        conn,
+        market_id,
+        "UPDATE markets SET yes_pool = ?2, no_pool = ?3 WHERE id = ?1",
+        params![market_id, to_sql_amount(yes_pool)?, to_sql_amount(no_pool)?],
+    )
+}
+
+// -------------------------------
+// Conversions
+// -------------------------------
+
+const MARKET_COLUMNS: &str = "id, question, creator, category, close_ts, resolution_ts, status, \
+                              outcome, yes_pool, no_pool, bond, cancel_reason";
+
+fn market_from_row(row: &Row) -> rusqlite::Result<MarketRow> {
+    let status: String = row.get(6)?;
+    let outcome: Option<String> = row.get(7)?;
+    Ok(MarketRow {
+        id: row.get(0)?,
+        question: row.get(1)?,
+        creator: row.get(2)?,
+        category: row.get(3)?,
+        close_ts: row.get::<_, i64>(4)? as u64,
+        resolution_ts: row.get::<_, i64>(5)? as u64,
+        status: match status.as_str() {
+            "resolved" => MarketStatus::Resolved,
+            "cancelled" => MarketStatus::Cancelled,
+            _ => MarketStatus::Open,
+        },
+        outcome: outcome.as_deref().map(outcome_from_key),
+        yes_pool: row.get::<_, i64>(8)? as i128,
+        no_pool: row.get::<_, i64>(9)? as i128,
+        bond: row.get::<_, i64>(10)? as i128,
+        cancel_reason: row.get(11)?,
+    })
+}
+
+fn stake_from_row(row: &Row) -> rusqlite::Result<StakeRow> {
+    Ok(StakeRow {
+        market_id: row.get(0)?,
+        user: row.get(1)?,
+        yes: row.get::<_, i64>(2)? as i128,
+        no: row.get::<_, i64>(3)? as i128,
+        claimed: row.get(4)?,
+        payout: row.get::<_, i64>(5)? as i128,
+    })
+}
+
+fn outcome_key(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Outcome::Yes => "yes",
+        Outcome::No => "no",
+        Outcome::Invalid => "invalid",
+    }
+}
+
+fn outcome_from_key(key: &str) -> Outcome {
+    match key {
+        "yes" => Outcome::Yes,
+        "no" => Outcome::No,
+        _ => Outcome::Invalid,
+    }
+}
+
+fn to_sql_amount(v: i128) -> Result<i64> {
+    i64::try_from(v).map_err(|_| IndexerError::AmountOverflow(v))
+}
+
+fn to_sql_u64(v: u64) -> i64 {
+    v as i64
+}
+
+fn to_std_string(s: &soroban_sdk::String) -> String {
+    let mut buf = vec![0u8; s.len() as usize];
+    s.copy_into_slice(&mut buf);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Strkey form of an address, used as the user key in every table
+pub fn address_key(a: &Address) -> String {
+    to_std_string(&a.to_string())
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,40 @@
+// Amounts are KALE in stroops (7 decimals) stored as SQLite integers.
+pub(crate) const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS markets (
+    id            INTEGER PRIMARY KEY,
+    question      TEXT    NOT NULL,
+    creator       TEXT    NOT NULL,
+    category      TEXT    NOT NULL,
+    close_ts      INTEGER NOT NULL,
+    resolution_ts INTEGER NOT NULL,
+    created_at    INTEGER NOT NULL,
+    status        TEXT    NOT NULL,          -- open | resolved | cancelled
+    outcome       TEXT,                      -- yes | no | invalid once settled
+    yes_pool      INTEGER NOT NULL DEFAULT 0,
+    no_pool       INTEGER NOT NULL DEFAULT 0,
+    bond          INTEGER NOT NULL DEFAULT 0,
+    cancel_reason TEXT
+);
+
+CREATE TABLE IF NOT EXISTS stakes (
+    market_id INTEGER NOT NULL,
+    user      TEXT    NOT NULL,
+    yes       INTEGER NOT NULL DEFAULT 0,
+    no        INTEGER NOT NULL DEFAULT 0,
+    claimed   INTEGER NOT NULL DEFAULT 0,
+    payout    INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (market_id, user)
+);
+
+CREATE TABLE IF NOT EXISTS activity (
+    seq       INTEGER PRIMARY KEY AUTOINCREMENT,
+    market_id INTEGER NOT NULL,
+    user      TEXT    NOT NULL,
+    kind      TEXT    NOT NULL,              -- bet | claim
+    side_yes  INTEGER,                       -- bets only
+    amount    INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS activity_by_user ON activity (user, seq);
+";
@@ -0,0 +1,383 @@
+use super::*;
+use soroban_sdk::{
+    testutils::{Address as _, Events, Ledger},
+    token, vec, Env, IntoVal, String,
+};
+use stellar_farmers_market::orderbook::OrderSide;
+use stellar_farmers_market::{
+    BetLimitsChoice, Category, CommitRevealMode, FarmersMarket, FarmersMarketClient, MarketConfig,
+    MarketMetadata, NoWinnerPolicy, ResolverChoice,
+};
+
+/// A scripted contract session whose events are fed to an in-memory index
+/// after every call, since `Events::all` only holds the last invocation.
+struct Session<'a> {
+    env: Env,
+    contract_id: Address,
+    client: FarmersMarketClient<'a>,
+    kale: token::StellarAssetClient<'a>,
+    indexer: Indexer,
+    admin: Address,
+    resolver: Address,
+}
+
+impl Session<'_> {
+    fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let kale_sac = env.register_stellar_asset_contract_v2(Address::generate(&env));
+        let kale = token::StellarAssetClient::new(&env, &kale_sac.address());
+        let contract_id = env.register(FarmersMarket, ());
+        let client = FarmersMarketClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let resolver = Address::generate(&env);
+        client.init(&admin, &resolver, &kale_sac.address());
+
+        let mut session = Session {
+            env,
+            contract_id,
+            client,
+            kale,
+            indexer: Indexer::open_in_memory().unwrap(),
+            admin,
+            resolver,
+        };
+        session.sync();
+        session
+    }
+
+    fn sync(&mut self) -> usize {
+        let events = self.env.events().all();
+        self.indexer
+            .ingest(&self.env, &self.contract_id, &events)
+            .unwrap()
+    }
+
+    fn create_market(&mut self, creator: &Address, category: Category) -> u32 {
+        let now = self.env.ledger().timestamp();
+        let id = self.client.create_market(
+            creator,
+            &String::from_str(&self.env, "Will it rain tomorrow?"),
+            &(now + 3600),
+            &(now + 7200),
+            &MarketConfig {
+                no_winner_policy: NoWinnerPolicy::Refund,
+                bet_limits: BetLimitsChoice::Default,
+                subsidy: 0,
+                early_bonus_bps: 0,
+                commit_reveal: CommitRevealMode::Off,
+                resolver: ResolverChoice::Default,
+            },
+            &MarketMetadata {
+                description: String::from_str(&self.env, ""),
+                category,
+                tags: vec![&self.env],
+                resolution_source: String::from_str(&self.env, "https://weather.gov"),
+                content_hash: None,
+            },
+        );
+        self.sync();
+        id
+    }
+
+    fn bet(&mut self, user: &Address, market_id: u32, side_yes: bool, amount: i128) {
+        self.kale.mint(user, &amount);
+        self.client.bet(user, &market_id, &side_yes, &amount);
+        self.sync();
+    }
+
+    fn advance(&self, secs: u64) {
+        self.env.ledger().with_mut(|ledger| {
+            ledger.timestamp += secs;
+        });
+    }
+
+    fn verify(&self, market_id: u32, users: &[&Address]) {
+        let market = self.client.get_market(&market_id);
+        self.indexer.verify_market(&market).unwrap();
+        for user in users {
+            let stake = self.client.get_stake(&market_id, user);
+            self.indexer.verify_stake(market_id, user, &stake).unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_replay_matches_contract_state() {
+    let mut s = Session::new();
+    let creator = Address::generate(&s.env);
+    let alice = Address::generate(&s.env);
+    let bob = Address::generate(&s.env);
+    let carol = Address::generate(&s.env);
+    let users = [&alice, &bob, &carol];
+
+    let rain = s.create_market(&creator, Category::Weather);
+    let harvest = s.create_market(&creator, Category::Farming);
+
+    s.bet(&alice, rain, true, 300);
+    s.bet(&bob, rain, false, 100);
+    s.bet(&alice, rain, false, 50);
+    s.bet(&carol, harvest, true, 400);
+    s.verify(rain, &users);
+    s.verify(harvest, &users);
+
+    let now = s.env.ledger().timestamp();
+    assert_eq!(
+        s.indexer
+            .markets_by_status(MarketStatus::Open, now)
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // Betting closes, then one market resolves and the other is cancelled
+    s.advance(3700);
+    let now = s.env.ledger().timestamp();
+    let closed = s
+        .indexer
+        .markets_by_status(MarketStatus::Closed, now)
+        .unwrap();
+    assert_eq!(closed.len(), 2);
+
+    s.client.cancel_market(
+        &s.admin,
+        &harvest,
+        &String::from_str(&s.env, "duplicate"),
+        &true,
+    );
+    s.sync();
+    s.advance(3600);
+    s.client.resolve(&s.resolver, &rain, &Outcome::Yes);
+    s.sync();
+
+    s.client.claim(&alice, &rain);
+    s.sync();
+    s.client.claim(&carol, &harvest);
+    s.sync();
+    s.verify(rain, &users);
+    s.verify(harvest, &users);
+
+    let now = s.env.ledger().timestamp();
+    let resolved = s
+        .indexer
+        .markets_by_status(MarketStatus::Resolved, now)
+        .unwrap();
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0].id, rain);
+    assert_eq!(resolved[0].outcome, Some(Outcome::Yes));
+    assert_eq!((resolved[0].yes_pool, resolved[0].no_pool), (300, 150));
+
+    let cancelled = s
+        .indexer
+        .markets_by_status(MarketStatus::Cancelled, now)
+        .unwrap();
+    assert_eq!(cancelled.len(), 1);
+    assert_eq!(cancelled[0].category, "Farming");
+    assert_eq!(cancelled[0].cancel_reason.as_deref(), Some("duplicate"));
+
+    let alice_stake = s.indexer.stake(rain, &alice).unwrap().unwrap();
+    assert!(alice_stake.claimed);
+    assert_eq!(alice_stake.payout, 450);
+}
+
+#[test]
+fn test_user_history_and_leaderboards() {
+    let mut s = Session::new();
+    let creator = Address::generate(&s.env);
+    let alice = Address::generate(&s.env);
+    let bob = Address::generate(&s.env);
+
+    let market_id = s.create_market(&creator, Category::Crypto);
+    s.bet(&alice, market_id, true, 100);
+    s.bet(&bob, market_id, false, 300);
+    s.bet(&alice, market_id, true, 100);
+
+    s.advance(7300);
+    s.client.resolve(&s.resolver, &market_id, &Outcome::Yes);
+    s.sync();
+    s.client.claim(&alice, &market_id);
+    s.sync();
+
+    let history = s.indexer.user_history(&alice).unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].kind, ActivityKind::Bet { side_yes: true });
+    assert_eq!(history[0].amount, 100);
+    assert_eq!(history[2].kind, ActivityKind::Claim);
+    assert_eq!(history[2].amount, 500);
+
+    // Volume counts what was bet, not who holds the shares now
+    let carol = Address::generate(&s.env);
+    let dave = Address::generate(&s.env);
+    let other = s.create_market(&creator, Category::Crypto);
+    s.bet(&carol, other, true, 50);
+    s.client.share_transfer(&carol, &dave, &other, &true, &50);
+    s.sync();
+
+    let volume = s
+        .indexer
+        .leaderboard(LeaderboardMetric::Volume, 10)
+        .unwrap();
+    assert_eq!(volume.len(), 3);
+    assert_eq!(volume[0].user, address_key(&bob));
+    assert_eq!(volume[0].value, 300);
+    assert_eq!(volume[1].value, 200);
+    assert_eq!(volume[2].user, address_key(&carol));
+    assert_eq!(volume[2].value, 50);
+
+    let pnl = s
+        .indexer
+        .leaderboard(LeaderboardMetric::NetPnl, 10)
+        .unwrap();
+    assert_eq!(
+        pnl,
+        std::vec![
+            LeaderboardEntry {
+                user: address_key(&alice),
+                value: 300,
+            },
+            LeaderboardEntry {
+                user: address_key(&bob),
+                value: -300,
+            },
+        ]
+    );
+
+    let top = s.indexer.leaderboard(LeaderboardMetric::Payout, 1).unwrap();
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].user, address_key(&alice));
+}
+
+#[test]
+fn test_ingest_filters_and_rejects_bad_events() {
+    let mut s = Session::new();
+    let creator = Address::generate(&s.env);
+    let alice = Address::generate(&s.env);
+
+    // Bets also publish SAC transfer events, which are skipped
+    let market_id = s.create_market(&creator, Category::Weather);
+    s.kale.mint(&alice, &100);
+    s.client.bet(&alice, &market_id, &true, &100);
+    let events = s.env.events().all();
+    assert!(events.len() > 1);
+    assert_eq!(
+        s.indexer.ingest(&s.env, &s.contract_id, &events).unwrap(),
+        1
+    );
+
+    // Version 1 payloads of the tracked events still decode
+    let mut ev = events::BetPlaced {
+        version: 1,
+        market_id,
+        user: alice.clone(),
+        side_yes: true,
+        amount: 100,
+        user_yes: 100,
+        user_no: 0,
+        yes_pool: 100,
+        no_pool: 0,
+        timestamp: 0,
+    };
+    let topics = (symbol_short!("Bet"), market_id, alice.clone()).into_val(&s.env);
+    let replayed = vec![
+        &s.env,
+        (s.contract_id.clone(), topics, ev.clone().into_val(&s.env)),
+    ];
+    assert_eq!(
+        s.indexer.ingest(&s.env, &s.contract_id, &replayed).unwrap(),
+        1
+    );
+
+    // Payloads from an unknown schema version are rejected
+    ev.version = 99;
+    let topics = (symbol_short!("Bet"), market_id, alice.clone()).into_val(&s.env);
+    let forged = vec![
+        &s.env,
+        (s.contract_id.clone(), topics, ev.clone().into_val(&s.env)),
+    ];
+    assert!(matches!(
+        s.indexer.ingest(&s.env, &s.contract_id, &forged),
+        Err(IndexerError::UnsupportedVersion(99))
+    ));
+
+    // A stream that skipped events no longer matches the contract
+    ev.version = events::EVENT_VERSION;
+    ev.market_id = market_id + 1;
+    let topics = (symbol_short!("Bet"), market_id + 1, alice.clone()).into_val(&s.env);
+    let orphan = vec![&s.env, (s.contract_id.clone(), topics, ev.into_val(&s.env))];
+    assert!(matches!(
+        s.indexer.ingest(&s.env, &s.contract_id, &orphan),
+        Err(IndexerError::UnknownMarket(_))
+    ));
+    s.verify(market_id, &[&alice]);
+}
+
+#[test]
+fn test_share_transfers_move_indexed_stakes() {
+    let mut s = Session::new();
+    let creator = Address::generate(&s.env);
+    let alice = Address::generate(&s.env);
+    let bob = Address::generate(&s.env);
+
+    let market_id = s.create_market(&creator, Category::Crypto);
+    s.bet(&alice, market_id, true, 300);
+    s.client
+        .share_transfer(&alice, &bob, &market_id, &true, &120);
+    assert_eq!(s.sync(), 1);
+    s.verify(market_id, &[&alice, &bob]);
+
+    let bob_stake = s.indexer.stake(market_id, &bob).unwrap().unwrap();
+    assert_eq!((bob_stake.yes, bob_stake.no), (120, 0));
+}
+
+#[test]
+fn test_order_book_fills_move_indexed_stakes() {
+    let mut s = Session::new();
+    let creator = Address::generate(&s.env);
+    let alice = Address::generate(&s.env);
+    let bob = Address::generate(&s.env);
+
+    let market_id = s.create_market(&creator, Category::Crypto);
+    s.bet(&alice, market_id, true, 300);
+
+    // Alice's ask takes 200 shares out of her stake into escrow
+    s.client
+        .place_order(&alice, &market_id, &OrderSide::Ask, &5_000, &200);
+    s.sync();
+    s.verify(market_id, &[&alice]);
+
+    // Bob's bid crosses it and the bought shares land in his stake
+    s.kale.mint(&bob, &90);
+    s.client
+        .place_order(&bob, &market_id, &OrderSide::Bid, &6_000, &150);
+    s.sync();
+    s.verify(market_id, &[&alice, &bob]);
+
+    // Cancelling returns the unfilled 50 shares to Alice
+    s.client.cancel_order(&0);
+    s.sync();
+    s.verify(market_id, &[&alice, &bob]);
+
+    let alice_stake = s.indexer.stake(market_id, &alice).unwrap().unwrap();
+    let bob_stake = s.indexer.stake(market_id, &bob).unwrap().unwrap();
+    assert_eq!((alice_stake.yes, bob_stake.yes), (150, 150));
+}
+
+#[test]
+fn test_liquidity_moves_indexed_pools() {
+    let mut s = Session::new();
+    let creator = Address::generate(&s.env);
+    let lp = Address::generate(&s.env);
+    let alice = Address::generate(&s.env);
+
+    let market_id = s.create_market(&creator, Category::Farming);
+    s.kale.mint(&lp, &1_000);
+    s.client.add_liquidity(&lp, &market_id, &1_000);
+    s.sync();
+    s.bet(&alice, market_id, true, 200);
+    s.verify(market_id, &[&alice]);
+
+    let market = s.indexer.market(market_id).unwrap().unwrap();
+    assert_eq!((market.yes_pool, market.no_pool), (700, 500));
+}
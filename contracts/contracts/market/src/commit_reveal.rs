@@ -0,0 +1,205 @@
+//! Commit-reveal betting for sealed markets.
+//!
+//! In a market whose `commit_reveal` mode is on, a bettor locks KALE with a
+//! `sha256(market_id || user || side || salt)` commitment before `close_ts`,
+//! where `market_id` is 4 big-endian bytes, `user` is the bettor's address
+//! as XDR, `side` is one byte (1 for YES, 0 for NO) and `salt` is 32 random
+//! bytes. Binding the market and bettor stops anyone replaying a commitment
+//! they saw elsewhere. Nobody can see which side the money is on until
+//! betting has closed. Sealed KALE counts towards the market's limits from
+//! the moment it is committed, and locks the market's details. Between
+//! `close_ts` and the reveal deadline, halfway to `resolution_ts`, the
+//! bettor reveals the side and salt, and only then does the stake join the
+//! pools. The window ends well before resolution so a bettor can't wait to
+//! see which way the outcome is heading and reveal only a winner.
+//! Commitments still unrevealed at the deadline are refunded or forfeited
+//! to the treasury, depending on the market's mode.
+use soroban_sdk::{contractimpl, contracttype, xdr::ToXdr, Address, Bytes, BytesN, Env};
+
+use crate::dispute::require_final;
+use crate::weighting::{weight_multiplier, WEIGHT_SCALE};
+use crate::{
+    check_bet_limits, credit_bet, events, now, read_address, read_market, read_stake,
+    require_initialized, require_not_paused, token_client, validate_amount, write_market,
+    CommitRevealMode, DataKey, Error, FarmersMarket, FarmersMarketArgs, FarmersMarketClient,
+    Market, Outcome,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Commitment {
+    pub hash: BytesN<32>, // sha256(market_id || user || side || salt)
+    pub amount: i128,
+    pub committed_at: u64, // time weighting uses the commit time, not the reveal
+}
+
+fn commitment_key(market_id: u32, user: &Address) -> DataKey {
+    DataKey::Commitment(market_id, user.clone())
+}
+
+fn read_commitment(e: &Env, market_id: u32, user: &Address) -> Result<Commitment, Error> {
+    e.storage()
+        .persistent()
+        .get(&commitment_key(market_id, user))
+        .ok_or(Error::NoCommitment)
+}
+
+/// KALE a bettor has sealed in a market and not yet revealed
+pub(crate) fn pending_commitment(e: &Env, market_id: u32, user: &Address) -> i128 {
+    read_commitment(e, market_id, user).map_or(0, |c| c.amount)
+}
+
+/// When the reveal window closes: halfway between `close_ts` and
+/// `resolution_ts`
+pub(crate) fn reveal_deadline(market: &Market) -> u64 {
+    market.close_ts + (market.resolution_ts - market.close_ts) / 2
+}
+
+/// The commitment a bettor publishes for a side and salt
+pub fn commitment_hash(
+    e: &Env,
+    market_id: u32,
+    user: &Address,
+    side_yes: bool,
+    salt: &BytesN<32>,
+) -> BytesN<32> {
+    let mut preimage = Bytes::from_array(e, &market_id.to_be_bytes());
+    preimage.append(&user.clone().to_xdr(e));
+    preimage.push_back(side_yes as u8);
+    preimage.append(&Bytes::from(salt.clone()));
+    e.crypto().sha256(&preimage).to_bytes()
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Lock KALE behind a sealed bet. One commitment per bettor per market.
+    pub fn commit_bet(
+        e: Env,
+        user: Address,
+        market_id: u32,
+        hash: BytesN<32>,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        user.require_auth();
+        require_not_paused(&e)?;
+        validate_amount(amount)?;
+
+        let mut market = read_market(&e, market_id)?;
+        if market.config.commit_reveal == CommitRevealMode::Off {
+            return Err(Error::WrongBettingMode);
+        }
+        if market.resolved {
+            return Err(Error::MarketClosed);
+        }
+        if now(&e) >= market.close_ts {
+            return Err(Error::BetsClosed);
+        }
+        let key = commitment_key(market_id, &user);
+        if e.storage().persistent().has(&key) {
+            return Err(Error::CommitmentExists);
+        }
+        check_bet_limits(&market, &read_stake(&e, market_id, &user), amount)?;
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&user, &e.current_contract_address(), &amount);
+
+        let commitment = Commitment {
+            hash,
+            amount,
+            committed_at: now(&e),
+        };
+        e.storage().persistent().set(&key, &commitment);
+        market.committed += amount;
+        write_market(&e, &market);
+
+        events::bet_committed(&e, market_id, user, &commitment);
+        Ok(())
+    }
+
+    /// Open a sealed bet between `close_ts` and the reveal deadline, adding
+    /// it to the pools
+    pub fn reveal_bet(
+        e: Env,
+        user: Address,
+        market_id: u32,
+        side_yes: bool,
+        salt: BytesN<32>,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        user.require_auth();
+
+        let mut market = read_market(&e, market_id)?;
+        if market.resolved {
+            return Err(Error::MarketClosed);
+        }
+        let t = now(&e);
+        if t < market.close_ts || t >= reveal_deadline(&market) {
+            return Err(Error::InvalidTime);
+        }
+        let commitment = read_commitment(&e, market_id, &user)?;
+        if commitment_hash(&e, market_id, &user, side_yes, &salt) != commitment.hash {
+            return Err(Error::RevealMismatch);
+        }
+        // Recheck the limits as the KALE moves from sealed to staked
+        market.committed -= commitment.amount;
+        check_bet_limits(
+            &market,
+            &read_stake(&e, market_id, &user),
+            commitment.amount,
+        )?;
+
+        e.storage()
+            .persistent()
+            .remove(&commitment_key(market_id, &user));
+        let weight =
+            commitment.amount * weight_multiplier(&market, commitment.committed_at) / WEIGHT_SCALE;
+        credit_bet(&e, &mut market, &user, side_yes, commitment.amount, weight);
+        Ok(())
+    }
+
+    /// Settle a commitment nobody revealed in time. Anyone can call this
+    /// once the reveal window has passed; the KALE goes back to the bettor
+    /// or to the treasury as the market's mode says. A cancelled market
+    /// always refunds, since its bettors may not have had a window.
+    pub fn settle_unrevealed(e: Env, market_id: u32, user: Address) -> Result<i128, Error> {
+        require_initialized(&e)?;
+
+        let mut market = read_market(&e, market_id)?;
+        if !market.resolved && now(&e) < reveal_deadline(&market) {
+            return Err(Error::InvalidTime);
+        }
+        if market.resolved {
+            require_final(&e, market_id)?;
+        }
+        let commitment = read_commitment(&e, market_id, &user)?;
+        e.storage()
+            .persistent()
+            .remove(&commitment_key(market_id, &user));
+        market.committed -= commitment.amount;
+        write_market(&e, &market);
+
+        let refund = market.config.commit_reveal != CommitRevealMode::ForfeitUnrevealed
+            || (market.resolved && market.outcome == Outcome::Invalid);
+        let to = if refund {
+            user.clone()
+        } else {
+            read_address(&e, &DataKey::Treasury)?
+        };
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(
+            &e.current_contract_address(),
+            &to,
+            &commitment.amount,
+        );
+
+        events::unrevealed_settled(&e, market_id, user, commitment.amount, refund);
+        Ok(commitment.amount)
+    }
+
+    pub fn get_commitment(e: Env, market_id: u32, user: Address) -> Option<Commitment> {
+        e.storage()
+            .persistent()
+            .get(&commitment_key(market_id, &user))
+    }
+}
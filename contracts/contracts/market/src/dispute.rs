@@ -0,0 +1,373 @@
+//! KALE-holder dispute ballots.
+//!
+//! With a dispute window configured, every resolved market waits
+//! `window_secs` before anything pays out. During the window anyone can
+//! contest the outcome by posting the dispute bond. That opens a ballot
+//! where KALE holders lock tokens behind the outcome they believe, one
+//! stroop per vote, for `voting_secs`. Once voting ends the outcome with
+//! the most KALE behind it is applied to the market; a tie keeps the
+//! original. An overturned outcome refunds the disputer. An upheld one
+//! forfeits the bond to the voters who backed it, pro-rata to their locked
+//! KALE. Voters take their lock back with `withdraw_vote`. A market's
+//! creation bond stays locked until its outcome is final.
+use soroban_sdk::{contractimpl, contracttype, Address, Env};
+
+use crate::roles::{require_role, Role};
+use crate::staking;
+use crate::{
+    events, now, read_address, read_market, require_initialized, return_bond,
+    sweep_unwinnable_pool, token_client, validate_amount, winning_pool, write_market, DataKey,
+    Error, FarmersMarket, FarmersMarketArgs, FarmersMarketClient, NoWinnerPolicy, Outcome,
+};
+
+/// Dispute settings; a zero window turns disputes off
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct DisputeConfig {
+    pub bond: i128,       // KALE a disputer puts up
+    pub window_secs: u64, // how long after resolution a dispute may open
+    pub voting_secs: u64, // how long a ballot stays open
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Ballot {
+    pub market_id: u32,
+    pub disputer: Address,
+    pub original: Outcome,
+    pub proposed: Outcome,
+    pub bond: i128,
+    pub ends_at: u64,
+    pub yes_votes: i128, // KALE locked behind each outcome
+    pub no_votes: i128,
+    pub invalid_votes: i128,
+    pub settled: bool,
+    pub result: Outcome, // the outcome applied once settled
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Vote {
+    pub outcome: Outcome,
+    pub amount: i128,
+}
+
+fn read_dispute_config(e: &Env) -> DisputeConfig {
+    e.storage()
+        .instance()
+        .get(&DataKey::DisputeConfig)
+        .unwrap_or_default()
+}
+
+fn read_ballot(e: &Env, market_id: u32) -> Option<Ballot> {
+    e.storage().persistent().get(&DataKey::Ballot(market_id))
+}
+
+fn write_ballot(e: &Env, ballot: &Ballot) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::Ballot(ballot.market_id), ballot);
+}
+
+fn votes_for(b: &Ballot, outcome: &Outcome) -> i128 {
+    match outcome {
+        Outcome::Yes => b.yes_votes,
+        Outcome::No => b.no_votes,
+        Outcome::Invalid => b.invalid_votes,
+    }
+}
+
+/// The outcome with strictly the most KALE behind it, else the original
+fn ballot_result(b: &Ballot) -> Outcome {
+    let best = b.yes_votes.max(b.no_votes).max(b.invalid_votes);
+    let leaders = [Outcome::Yes, Outcome::No, Outcome::Invalid]
+        .into_iter()
+        .filter(|o| votes_for(b, o) == best);
+    let mut result = None;
+    for outcome in leaders {
+        if result.is_some() || outcome == b.original {
+            return b.original.clone();
+        }
+        result = Some(outcome);
+    }
+    result.unwrap_or_else(|| b.original.clone())
+}
+
+/// Start a resolved market's dispute window, if disputes are on
+pub(crate) fn open_dispute_window(e: &Env, market_id: u32) {
+    let config = read_dispute_config(e);
+    if config.window_secs > 0 {
+        e.storage().persistent().set(
+            &DataKey::DisputeDeadline(market_id),
+            &(now(e) + config.window_secs),
+        );
+    }
+}
+
+/// Refuse payouts while a resolved market's outcome can still change
+pub(crate) fn require_final(e: &Env, market_id: u32) -> Result<(), Error> {
+    if let Some(ballot) = read_ballot(e, market_id) {
+        return if ballot.settled {
+            Ok(())
+        } else {
+            Err(Error::DisputePending)
+        };
+    }
+    let deadline: u64 = e
+        .storage()
+        .persistent()
+        .get(&DataKey::DisputeDeadline(market_id))
+        .unwrap_or(0);
+    if now(e) < deadline {
+        return Err(Error::DisputePending);
+    }
+    Ok(())
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Configure the dispute bond, window and voting period (Admin only).
+    /// Applies to markets resolved from now on.
+    pub fn set_dispute_config(e: Env, admin: Address, config: DisputeConfig) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        if config.bond < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if config.window_secs > 0 && config.voting_secs == 0 {
+            return Err(Error::InvalidTime);
+        }
+        e.storage().instance().set(&DataKey::DisputeConfig, &config);
+
+        events::dispute_config_updated(&e, config);
+        Ok(())
+    }
+
+    /// Contest a market's outcome within its dispute window by posting the
+    /// dispute bond. Opens a ballot on the market.
+    pub fn dispute_outcome(
+        e: Env,
+        disputer: Address,
+        market_id: u32,
+        proposed: Outcome,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        disputer.require_auth();
+
+        let market = read_market(&e, market_id)?;
+        if !market.resolved {
+            return Err(Error::NotResolved);
+        }
+        let deadline: u64 = e
+            .storage()
+            .persistent()
+            .get(&DataKey::DisputeDeadline(market_id))
+            .ok_or(Error::NotDisputable)?;
+        if now(&e) >= deadline || read_ballot(&e, market_id).is_some() {
+            return Err(Error::NotDisputable);
+        }
+        if proposed == market.outcome {
+            return Err(Error::NotDisputable);
+        }
+        // A pool already swept to the treasury can't be paid out again
+        if winning_pool(&market) == Some(0)
+            && market.config.no_winner_policy == NoWinnerPolicy::Treasury
+            && market.yes_pool + market.no_pool > 0
+        {
+            return Err(Error::NotDisputable);
+        }
+
+        let config = read_dispute_config(&e);
+        if config.bond > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            token_client(&e, &token_addr).transfer(
+                &disputer,
+                &e.current_contract_address(),
+                &config.bond,
+            );
+        }
+
+        let ballot = Ballot {
+            market_id,
+            disputer,
+            original: market.outcome.clone(),
+            proposed,
+            bond: config.bond,
+            ends_at: now(&e) + config.voting_secs,
+            yes_votes: 0,
+            no_votes: 0,
+            invalid_votes: 0,
+            settled: false,
+            result: market.outcome,
+        };
+        write_ballot(&e, &ballot);
+
+        events::dispute_opened(&e, &ballot);
+        Ok(())
+    }
+
+    /// Lock KALE behind an outcome on an open ballot. A voter can add to
+    /// their vote but not switch sides.
+    pub fn vote(
+        e: Env,
+        voter: Address,
+        market_id: u32,
+        outcome: Outcome,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        voter.require_auth();
+        validate_amount(amount)?;
+
+        let mut ballot = read_ballot(&e, market_id).ok_or(Error::NotDisputable)?;
+        if ballot.settled || now(&e) >= ballot.ends_at {
+            return Err(Error::InvalidTime);
+        }
+        let key = DataKey::Vote(market_id, voter.clone());
+        let mut vote: Vote = e.storage().persistent().get(&key).unwrap_or(Vote {
+            outcome: outcome.clone(),
+            amount: 0,
+        });
+        if vote.outcome != outcome {
+            return Err(Error::AlreadyVoted);
+        }
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&voter, &e.current_contract_address(), &amount);
+
+        vote.amount += amount;
+        e.storage().persistent().set(&key, &vote);
+        match outcome {
+            Outcome::Yes => ballot.yes_votes += amount,
+            Outcome::No => ballot.no_votes += amount,
+            Outcome::Invalid => ballot.invalid_votes += amount,
+        }
+        write_ballot(&e, &ballot);
+
+        events::vote_cast(&e, market_id, voter, &vote, &ballot);
+        Ok(())
+    }
+
+    /// Close a ballot once voting has ended and apply its result. Anyone
+    /// can call this.
+    pub fn settle_dispute(e: Env, market_id: u32) -> Result<Outcome, Error> {
+        require_initialized(&e)?;
+
+        let mut ballot = read_ballot(&e, market_id).ok_or(Error::NotDisputable)?;
+        if ballot.settled {
+            return Err(Error::AlreadyResolved);
+        }
+        if now(&e) < ballot.ends_at {
+            return Err(Error::InvalidTime);
+        }
+
+        let result = ballot_result(&ballot);
+        ballot.settled = true;
+        ballot.result = result.clone();
+        write_ballot(&e, &ballot);
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        let overturned = result != ballot.original;
+        if overturned {
+            let mut market = read_market(&e, market_id)?;
+            market.outcome = result.clone();
+            write_market(&e, &market);
+            events::market_resolved(&e, &market);
+            if ballot.bond > 0 {
+                token_client(&e, &token_addr).transfer(
+                    &e.current_contract_address(),
+                    &ballot.disputer,
+                    &ballot.bond,
+                );
+            }
+            // The corrected outcome may leave nobody to pay
+            sweep_unwinnable_pool(&e, &market)?;
+            staking::slash_resolver(&e, &market)?;
+        } else if votes_for(&ballot, &result) == 0 && ballot.bond > 0 {
+            // Nobody voted, so nobody shares the bond
+            let treasury = read_address(&e, &DataKey::Treasury)?;
+            token_client(&e, &token_addr).transfer(
+                &e.current_contract_address(),
+                &treasury,
+                &ballot.bond,
+            );
+        }
+
+        // The outcome is final now, so the creation bond can go back
+        let mut market = read_market(&e, market_id)?;
+        return_bond(&e, &mut market)?;
+
+        events::dispute_settled(&e, &ballot, overturned);
+        Ok(result)
+    }
+
+    /// Hand a resolved market's creation bond back once its dispute window
+    /// has closed. Anyone can call this.
+    pub fn release_bond(e: Env, market_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+
+        let mut market = read_market(&e, market_id)?;
+        if !market.resolved {
+            return Err(Error::NotResolved);
+        }
+        require_final(&e, market_id)?;
+        let bond = market.bond;
+        if bond == 0 {
+            return Err(Error::NothingToClaim);
+        }
+        return_bond(&e, &mut market)?;
+        Ok(bond)
+    }
+
+    /// Take back KALE locked on a settled ballot, plus a share of a
+    /// forfeited bond for voters who backed the result
+    pub fn withdraw_vote(e: Env, voter: Address, market_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        voter.require_auth();
+
+        let ballot = read_ballot(&e, market_id).ok_or(Error::NotDisputable)?;
+        if !ballot.settled {
+            return Err(Error::DisputePending);
+        }
+        let key = DataKey::Vote(market_id, voter.clone());
+        let vote: Vote = e
+            .storage()
+            .persistent()
+            .get(&key)
+            .ok_or(Error::NothingToClaim)?;
+        e.storage().persistent().remove(&key);
+
+        let reward = if ballot.result == ballot.original && vote.outcome == ballot.result {
+            ballot.bond * vote.amount / votes_for(&ballot, &ballot.result)
+        } else {
+            0
+        };
+        let payout = vote.amount + reward;
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&e.current_contract_address(), &voter, &payout);
+
+        events::vote_withdrawn(&e, market_id, voter, vote.amount, reward);
+        Ok(payout)
+    }
+
+    pub fn get_dispute_config(e: Env) -> DisputeConfig {
+        read_dispute_config(&e)
+    }
+
+    pub fn get_ballot(e: Env, market_id: u32) -> Option<Ballot> {
+        read_ballot(&e, market_id)
+    }
+
+    pub fn get_vote(e: Env, market_id: u32, voter: Address) -> Option<Vote> {
+        e.storage()
+            .persistent()
+            .get(&DataKey::Vote(market_id, voter))
+    }
+
+    /// When a resolved market's dispute window closes, if it has one
+    pub fn get_dispute_deadline(e: Env, market_id: u32) -> Option<u64> {
+        e.storage()
+            .persistent()
+            .get(&DataKey::DisputeDeadline(market_id))
+    }
+}
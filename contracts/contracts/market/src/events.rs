@@ -0,0 +1,1445 @@
+use soroban_sdk::{contracttype, symbol_short, Address, BytesN, Env, String, Vec};
+
+use crate::commit_reveal::Commitment;
+use crate::dispute::{Ballot, DisputeConfig, Vote};
+use crate::keeper::{BountyConfig, KeeperAction, PriceCondition};
+use crate::orderbook::{Order, OrderSide};
+use crate::parlay::{ParlayHouse, ParlayTicket};
+use crate::roles::Role;
+use crate::seasons::{RankMetric, Season};
+use crate::staking::{ResolverRecord, ResolverStakeConfig};
+use crate::templates::TemplateState;
+use crate::timelock::{PendingAction, TimelockAction};
+use crate::{BetLimits, Category, CreationPolicy, FeeConfig, Market, Outcome, Stake};
+
+// Bump whenever a payload changes shape so indexers can tell schemas apart.
+// 2: FeeCollected gained `to_lp`.
+pub const EVENT_VERSION: u32 = 2;
+
+// -------------------------------
+// Event payloads
+// -------------------------------
+// Every event is published as (topic symbol, ids...) => payload struct.
+// Market events carry the pools as they stand after the change.
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Initialized {
+    pub version: u32,
+    pub admin: Address,
+    pub resolver: Address,
+    pub kale_token: Address,
+    pub oracle: Address,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketCreated {
+    pub version: u32,
+    pub market_id: u32,
+    pub creator: Address,
+    pub question: String,
+    pub category: Category,
+    pub close_ts: u64,
+    pub resolution_ts: u64,
+    pub bond: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MetadataUpdated {
+    pub version: u32,
+    pub market_id: u32,
+    pub category: Category,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BetPlaced {
+    pub version: u32,
+    pub market_id: u32,
+    pub user: Address,
+    pub side_yes: bool,
+    pub amount: i128,
+    pub user_yes: i128, // user's stake after the bet
+    pub user_no: i128,
+    pub yes_pool: i128,
+    pub no_pool: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketResolved {
+    pub version: u32,
+    pub market_id: u32,
+    pub outcome: Outcome,
+    pub yes_pool: i128,
+    pub no_pool: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketCancelled {
+    pub version: u32,
+    pub market_id: u32,
+    pub reason: String,
+    pub bond_slashed: i128,
+    pub yes_pool: i128,
+    pub no_pool: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BondReturned {
+    pub version: u32,
+    pub market_id: u32,
+    pub creator: Address,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PoolSwept {
+    pub version: u32,
+    pub market_id: u32,
+    pub treasury: Address,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Claimed {
+    pub version: u32,
+    pub market_id: u32,
+    pub user: Address,
+    pub payout: i128,
+    pub yes_pool: i128,
+    pub no_pool: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TreasuryUpdated {
+    pub version: u32,
+    pub treasury: Address,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct OracleUpdated {
+    pub version: u32,
+    pub oracle: Address,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CreationPolicyUpdated {
+    pub version: u32,
+    pub policy: CreationPolicy,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DefaultBetLimitsUpdated {
+    pub version: u32,
+    pub limits: BetLimits,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CreatorAllowed {
+    pub version: u32,
+    pub creator: Address,
+    pub allowed: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlayPlaced {
+    pub version: u32,
+    pub ticket_id: u32,
+    pub owner: Address,
+    pub markets: Vec<u32>,
+    pub stake: i128,
+    pub potential_payout: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlaySettled {
+    pub version: u32,
+    pub ticket_id: u32,
+    pub owner: Address,
+    pub payout: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlayHouseUpdated {
+    pub version: u32,
+    pub amount: i128, // positive when funded, negative when withdrawn
+    pub balance: i128,
+    pub reserved: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TemplateUpdated {
+    pub version: u32,
+    pub template_id: u32,
+    pub active: bool,
+    pub tip_balance: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketSpawned {
+    pub version: u32,
+    pub template_id: u32,
+    pub market_id: u32,
+    pub keeper: Address,
+    pub tip: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeCollected {
+    pub version: u32,
+    pub market_id: u32,
+    pub user: Address,
+    pub fee: i128,
+    pub to_bounty: i128, // part of the fee kept for keeper bounties
+    pub to_lp: i128,     // part of the fee paid to the market's LPs
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeConfigUpdated {
+    pub version: u32,
+    pub fees: FeeConfig,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct KeeperPaid {
+    pub version: u32,
+    pub market_id: u32,
+    pub keeper: Address,
+    pub action: KeeperAction,
+    pub tip: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BountyPoolFunded {
+    pub version: u32,
+    pub from: Address,
+    pub amount: i128,
+    pub balance: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BountyConfigUpdated {
+    pub version: u32,
+    pub config: BountyConfig,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PriceConditionSet {
+    pub version: u32,
+    pub market_id: u32,
+    pub condition: PriceCondition,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SharesTransferred {
+    pub version: u32,
+    pub market_id: u32,
+    pub side_yes: bool,
+    pub from: Address,
+    pub to: Address,
+    pub amount: i128,
+    pub from_yes: i128, // both holders' stakes after the transfer
+    pub from_no: i128,
+    pub to_yes: i128,
+    pub to_no: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SharesApproved {
+    pub version: u32,
+    pub market_id: u32,
+    pub side_yes: bool,
+    pub from: Address,
+    pub spender: Address,
+    pub amount: i128,
+    pub expiration_ledger: u32,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct OrderPlaced {
+    pub version: u32,
+    pub order_id: u32,
+    pub market_id: u32,
+    pub trader: Address,
+    pub side: OrderSide,
+    pub price: i128,
+    pub amount: i128,
+    pub escrow: i128,     // KALE held for a bid
+    pub trader_yes: i128, // trader's stake after any shares went into escrow
+    pub trader_no: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct OrderFilled {
+    pub version: u32,
+    pub market_id: u32,
+    pub bid_id: u32,
+    pub ask_id: u32,
+    pub buyer: Address,
+    pub seller: Address,
+    pub price: i128,
+    pub amount: i128,
+    pub buyer_yes: i128, // buyer's stake after the fill
+    pub buyer_no: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct OrderCancelled {
+    pub version: u32,
+    pub order_id: u32,
+    pub market_id: u32,
+    pub trader: Address,
+    pub side: OrderSide,
+    pub unfilled: i128, // shares left on the order
+    pub refund: i128,   // KALE returned for a bid
+    pub trader_yes: i128,
+    pub trader_no: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct LiquidityAdded {
+    pub version: u32,
+    pub market_id: u32,
+    pub provider: Address,
+    pub amount: i128,
+    pub lp_shares: i128, // provider's LP shares after the deposit
+    pub yes_pool: i128,
+    pub no_pool: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct LiquidityRemoved {
+    pub version: u32,
+    pub market_id: u32,
+    pub provider: Address,
+    pub lp_shares: i128, // shares burned
+    pub payout: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SubsidyAdded {
+    pub version: u32,
+    pub market_id: u32,
+    pub funder: Address,
+    pub amount: i128,
+    pub total: i128, // market's subsidy after the top-up
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SubsidyRefunded {
+    pub version: u32,
+    pub market_id: u32,
+    pub funder: Address,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BetCommitted {
+    pub version: u32,
+    pub market_id: u32,
+    pub user: Address,
+    pub hash: BytesN<32>, // the side stays hidden until the reveal's Bet event
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct UnrevealedSettled {
+    pub version: u32,
+    pub market_id: u32,
+    pub user: Address,
+    pub amount: i128,
+    pub refunded: bool, // false when forfeited to the treasury
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RoleGranted {
+    pub version: u32,
+    pub role: Role,
+    pub account: Address,
+    pub sender: Address, // the admin who granted it, or the deployer at init
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RoleRevoked {
+    pub version: u32,
+    pub role: Role,
+    pub account: Address,
+    pub sender: Address,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PauseUpdated {
+    pub version: u32,
+    pub paused: bool,
+    pub pauser: Address,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ActionScheduled {
+    pub version: u32,
+    pub action_id: u32,
+    pub action: TimelockAction,
+    pub proposer: Address,
+    pub eta: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ActionCancelled {
+    pub version: u32,
+    pub action_id: u32,
+    pub cancelled_by: Address,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ActionExecuted {
+    pub version: u32,
+    pub action_id: u32,
+    pub action: TimelockAction,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DisputeConfigUpdated {
+    pub version: u32,
+    pub config: DisputeConfig,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DisputeOpened {
+    pub version: u32,
+    pub market_id: u32,
+    pub disputer: Address,
+    pub original: Outcome,
+    pub proposed: Outcome,
+    pub bond: i128,
+    pub ends_at: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct VoteCast {
+    pub version: u32,
+    pub market_id: u32,
+    pub voter: Address,
+    pub outcome: Outcome,
+    pub amount: i128, // the voter's total behind `outcome`
+    pub yes_votes: i128,
+    pub no_votes: i128,
+    pub invalid_votes: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DisputeSettled {
+    pub version: u32,
+    pub market_id: u32,
+    pub result: Outcome,
+    pub overturned: bool,
+    pub yes_votes: i128,
+    pub no_votes: i128,
+    pub invalid_votes: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct VoteWithdrawn {
+    pub version: u32,
+    pub market_id: u32,
+    pub voter: Address,
+    pub amount: i128,
+    pub reward: i128, // share of a forfeited dispute bond
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ResolverStakeConfigUpdated {
+    pub version: u32,
+    pub config: ResolverStakeConfig,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ResolverStaked {
+    pub version: u32,
+    pub resolver: Address,
+    pub amount: i128,
+    pub stake: i128, // the resolver's stake after the change
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ResolverUnstaked {
+    pub version: u32,
+    pub resolver: Address,
+    pub amount: i128,
+    pub stake: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ResolverSlashed {
+    pub version: u32,
+    pub market_id: u32,
+    pub resolver: Address,
+    pub amount: i128,
+    pub stake: i128,
+    pub overturned: u32, // the resolver's overturned resolutions so far
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SeasonCreated {
+    pub version: u32,
+    pub season_id: u32,
+    pub name: String,
+    pub start_ts: u64,
+    pub end_ts: u64,
+    pub metric: RankMetric,
+    pub prize_pool: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SeasonFunded {
+    pub version: u32,
+    pub season_id: u32,
+    pub funder: Address,
+    pub amount: i128,
+    pub prize_pool: i128, // pool after the top-up
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SeasonPrizePaid {
+    pub version: u32,
+    pub season_id: u32,
+    pub player: Address,
+    pub rank: u32, // 1 = best
+    pub prize: i128,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SeasonFinalized {
+    pub version: u32,
+    pub season_id: u32,
+    pub prize_pool: i128,
+    pub paid: i128, // the rest went to the treasury
+    pub timestamp: u64,
+}
+
+// -------------------------------
+// Publishers
+// -------------------------------
+
+pub(crate) fn initialized(
+    e: &Env,
+    admin: Address,
+    resolver: Address,
+    kale_token: Address,
+    oracle: Address,
+) {
+    let ev = Initialized {
+        version: EVENT_VERSION,
+        admin,
+        resolver,
+        kale_token,
+        oracle,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Init"),), ev);
+}
+
+pub(crate) fn market_created(e: &Env, m: &Market, category: Category) {
+    let ev = MarketCreated {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        creator: m.creator.clone(),
+        question: m.question.clone(),
+        category,
+        close_ts: m.close_ts,
+        resolution_ts: m.resolution_ts,
+        bond: m.bond,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Created"), m.id, m.creator.clone()), ev);
+}
+
+pub(crate) fn metadata_updated(e: &Env, market_id: u32, category: Category) {
+    let ev = MetadataUpdated {
+        version: EVENT_VERSION,
+        market_id,
+        category,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Metadata"), market_id), ev);
+}
+
+pub(crate) fn bet_placed(
+    e: &Env,
+    m: &Market,
+    user: Address,
+    side_yes: bool,
+    amount: i128,
+    stake: &Stake,
+) {
+    let ev = BetPlaced {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        user: user.clone(),
+        side_yes,
+        amount,
+        user_yes: stake.yes,
+        user_no: stake.no,
+        yes_pool: m.yes_pool,
+        no_pool: m.no_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Bet"), m.id, user), ev);
+}
+
+pub(crate) fn market_resolved(e: &Env, m: &Market) {
+    let ev = MarketResolved {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        outcome: m.outcome.clone(),
+        yes_pool: m.yes_pool,
+        no_pool: m.no_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Resolved"), m.id), ev);
+}
+
+pub(crate) fn market_cancelled(e: &Env, m: &Market, reason: String, bond_slashed: i128) {
+    let ev = MarketCancelled {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        reason,
+        bond_slashed,
+        yes_pool: m.yes_pool,
+        no_pool: m.no_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Cancel"), m.id), ev);
+}
+
+pub(crate) fn bond_returned(e: &Env, m: &Market, amount: i128) {
+    let ev = BondReturned {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        creator: m.creator.clone(),
+        amount,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("BondBack"), m.id, m.creator.clone()), ev);
+}
+
+pub(crate) fn pool_swept(e: &Env, market_id: u32, treasury: Address, amount: i128) {
+    let ev = PoolSwept {
+        version: EVENT_VERSION,
+        market_id,
+        treasury: treasury.clone(),
+        amount,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Swept"), market_id, treasury), ev);
+}
+
+pub(crate) fn claimed(e: &Env, m: &Market, user: Address, payout: i128) {
+    let ev = Claimed {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        user: user.clone(),
+        payout,
+        yes_pool: m.yes_pool,
+        no_pool: m.no_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Claimed"), m.id, user), ev);
+}
+
+pub(crate) fn treasury_updated(e: &Env, treasury: Address) {
+    let ev = TreasuryUpdated {
+        version: EVENT_VERSION,
+        treasury,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Treasury"),), ev);
+}
+
+pub(crate) fn oracle_updated(e: &Env, oracle: Address) {
+    let ev = OracleUpdated {
+        version: EVENT_VERSION,
+        oracle,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Oracle"),), ev);
+}
+
+pub(crate) fn creation_policy_updated(e: &Env, policy: CreationPolicy) {
+    let ev = CreationPolicyUpdated {
+        version: EVENT_VERSION,
+        policy,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Policy"),), ev);
+}
+
+pub(crate) fn default_bet_limits_updated(e: &Env, limits: BetLimits) {
+    let ev = DefaultBetLimitsUpdated {
+        version: EVENT_VERSION,
+        limits,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Limits"),), ev);
+}
+
+pub(crate) fn creator_allowed(e: &Env, creator: Address, allowed: bool) {
+    let ev = CreatorAllowed {
+        version: EVENT_VERSION,
+        creator: creator.clone(),
+        allowed,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Allowed"), creator), ev);
+}
+
+pub(crate) fn parlay_placed(e: &Env, ticket: &ParlayTicket) {
+    let mut markets = Vec::new(e);
+    for leg in ticket.legs.iter() {
+        markets.push_back(leg.market_id);
+    }
+    let ev = ParlayPlaced {
+        version: EVENT_VERSION,
+        ticket_id: ticket.id,
+        owner: ticket.owner.clone(),
+        markets,
+        stake: ticket.stake,
+        potential_payout: ticket.potential_payout,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (symbol_short!("Parlay"), ticket.id, ticket.owner.clone()),
+        ev,
+    );
+}
+
+pub(crate) fn parlay_settled(e: &Env, ticket: &ParlayTicket) {
+    let ev = ParlaySettled {
+        version: EVENT_VERSION,
+        ticket_id: ticket.id,
+        owner: ticket.owner.clone(),
+        payout: ticket.payout,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (symbol_short!("ParlayEnd"), ticket.id, ticket.owner.clone()),
+        ev,
+    );
+}
+
+pub(crate) fn parlay_house_updated(e: &Env, house: &ParlayHouse, amount: i128) {
+    let ev = ParlayHouseUpdated {
+        version: EVENT_VERSION,
+        amount,
+        balance: house.balance,
+        reserved: house.reserved,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("House"),), ev);
+}
+
+pub(crate) fn template_updated(e: &Env, template_id: u32, state: &TemplateState) {
+    let ev = TemplateUpdated {
+        version: EVENT_VERSION,
+        template_id,
+        active: state.active,
+        tip_balance: state.tip_balance,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Template"), template_id), ev);
+}
+
+pub(crate) fn market_spawned(
+    e: &Env,
+    template_id: u32,
+    market_id: u32,
+    keeper: Address,
+    tip: i128,
+) {
+    let ev = MarketSpawned {
+        version: EVENT_VERSION,
+        template_id,
+        market_id,
+        keeper,
+        tip,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Spawned"), template_id, market_id), ev);
+}
+
+pub(crate) fn fee_collected(
+    e: &Env,
+    market_id: u32,
+    user: Address,
+    fee: i128,
+    to_bounty: i128,
+    to_lp: i128,
+) {
+    let ev = FeeCollected {
+        version: EVENT_VERSION,
+        market_id,
+        user: user.clone(),
+        fee,
+        to_bounty,
+        to_lp,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Fee"), market_id, user), ev);
+}
+
+pub(crate) fn fee_config_updated(e: &Env, fees: FeeConfig) {
+    let ev = FeeConfigUpdated {
+        version: EVENT_VERSION,
+        fees,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Fees"),), ev);
+}
+
+pub(crate) fn keeper_paid(
+    e: &Env,
+    market_id: u32,
+    keeper: Address,
+    action: KeeperAction,
+    tip: i128,
+) {
+    let ev = KeeperPaid {
+        version: EVENT_VERSION,
+        market_id,
+        keeper: keeper.clone(),
+        action,
+        tip,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Bounty"), market_id, keeper), ev);
+}
+
+pub(crate) fn bounty_pool_funded(e: &Env, from: Address, amount: i128, balance: i128) {
+    let ev = BountyPoolFunded {
+        version: EVENT_VERSION,
+        from,
+        amount,
+        balance,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Bounties"),), ev);
+}
+
+pub(crate) fn bounty_config_updated(e: &Env, config: BountyConfig) {
+    let ev = BountyConfigUpdated {
+        version: EVENT_VERSION,
+        config,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Keeper"),), ev);
+}
+
+pub(crate) fn price_condition_set(e: &Env, market_id: u32, condition: PriceCondition) {
+    let ev = PriceConditionSet {
+        version: EVENT_VERSION,
+        market_id,
+        condition,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("PriceCond"), market_id), ev);
+}
+
+pub(crate) fn shares_transferred(
+    e: &Env,
+    market_id: u32,
+    side_yes: bool,
+    amount: i128,
+    from: (&Address, &Stake),
+    to: (&Address, &Stake),
+) {
+    let ev = SharesTransferred {
+        version: EVENT_VERSION,
+        market_id,
+        side_yes,
+        from: from.0.clone(),
+        to: to.0.clone(),
+        amount,
+        from_yes: from.1.yes,
+        from_no: from.1.no,
+        to_yes: to.1.yes,
+        to_no: to.1.no,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (
+            symbol_short!("ShareXfer"),
+            market_id,
+            from.0.clone(),
+            to.0.clone(),
+        ),
+        ev,
+    );
+}
+
+pub(crate) fn shares_approved(
+    e: &Env,
+    market_id: u32,
+    side_yes: bool,
+    from: Address,
+    spender: Address,
+    amount: i128,
+    expiration_ledger: u32,
+) {
+    let ev = SharesApproved {
+        version: EVENT_VERSION,
+        market_id,
+        side_yes,
+        from: from.clone(),
+        spender: spender.clone(),
+        amount,
+        expiration_ledger,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("ShareAppr"), market_id, from, spender), ev);
+}
+
+pub(crate) fn order_placed(e: &Env, order: &Order, stake: &Stake) {
+    let ev = OrderPlaced {
+        version: EVENT_VERSION,
+        order_id: order.id,
+        market_id: order.market_id,
+        trader: order.trader.clone(),
+        side: order.side,
+        price: order.price,
+        amount: order.amount,
+        escrow: order.escrow,
+        trader_yes: stake.yes,
+        trader_no: stake.no,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (
+            symbol_short!("Order"),
+            order.market_id,
+            order.trader.clone(),
+        ),
+        ev,
+    );
+}
+
+pub(crate) fn order_filled(
+    e: &Env,
+    bid: &Order,
+    ask: &Order,
+    price: i128,
+    amount: i128,
+    buyer: &Stake,
+) {
+    let ev = OrderFilled {
+        version: EVENT_VERSION,
+        market_id: bid.market_id,
+        bid_id: bid.id,
+        ask_id: ask.id,
+        buyer: bid.trader.clone(),
+        seller: ask.trader.clone(),
+        price,
+        amount,
+        buyer_yes: buyer.yes,
+        buyer_no: buyer.no,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (
+            symbol_short!("Fill"),
+            bid.market_id,
+            bid.trader.clone(),
+            ask.trader.clone(),
+        ),
+        ev,
+    );
+}
+
+pub(crate) fn order_cancelled(e: &Env, order: &Order, refund: i128, stake: &Stake) {
+    let ev = OrderCancelled {
+        version: EVENT_VERSION,
+        order_id: order.id,
+        market_id: order.market_id,
+        trader: order.trader.clone(),
+        side: order.side,
+        unfilled: order.amount,
+        refund,
+        trader_yes: stake.yes,
+        trader_no: stake.no,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (
+            symbol_short!("OrdCancel"),
+            order.market_id,
+            order.trader.clone(),
+        ),
+        ev,
+    );
+}
+
+pub(crate) fn liquidity_added(
+    e: &Env,
+    m: &Market,
+    provider: Address,
+    amount: i128,
+    lp_shares: i128,
+) {
+    let ev = LiquidityAdded {
+        version: EVENT_VERSION,
+        market_id: m.id,
+        provider: provider.clone(),
+        amount,
+        lp_shares,
+        yes_pool: m.yes_pool,
+        no_pool: m.no_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("LpAdd"), m.id, provider), ev);
+}
+
+pub(crate) fn liquidity_removed(
+    e: &Env,
+    market_id: u32,
+    provider: Address,
+    lp_shares: i128,
+    payout: i128,
+) {
+    let ev = LiquidityRemoved {
+        version: EVENT_VERSION,
+        market_id,
+        provider: provider.clone(),
+        lp_shares,
+        payout,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("LpRemove"), market_id, provider), ev);
+}
+
+pub(crate) fn subsidy_added(e: &Env, market_id: u32, funder: Address, amount: i128, total: i128) {
+    let ev = SubsidyAdded {
+        version: EVENT_VERSION,
+        market_id,
+        funder: funder.clone(),
+        amount,
+        total,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Subsidy"), market_id, funder), ev);
+}
+
+pub(crate) fn subsidy_refunded(e: &Env, market_id: u32, funder: Address, amount: i128) {
+    let ev = SubsidyRefunded {
+        version: EVENT_VERSION,
+        market_id,
+        funder: funder.clone(),
+        amount,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("SubRefund"), market_id, funder), ev);
+}
+
+pub(crate) fn bet_committed(e: &Env, market_id: u32, user: Address, commitment: &Commitment) {
+    let ev = BetCommitted {
+        version: EVENT_VERSION,
+        market_id,
+        user: user.clone(),
+        hash: commitment.hash.clone(),
+        amount: commitment.amount,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Committed"), market_id, user), ev);
+}
+
+pub(crate) fn unrevealed_settled(
+    e: &Env,
+    market_id: u32,
+    user: Address,
+    amount: i128,
+    refunded: bool,
+) {
+    let ev = UnrevealedSettled {
+        version: EVENT_VERSION,
+        market_id,
+        user: user.clone(),
+        amount,
+        refunded,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("NoReveal"), market_id, user), ev);
+}
+
+pub(crate) fn role_granted(e: &Env, role: Role, account: Address, sender: Address) {
+    let ev = RoleGranted {
+        version: EVENT_VERSION,
+        role,
+        account: account.clone(),
+        sender,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("RoleGrant"), role, account), ev);
+}
+
+pub(crate) fn role_revoked(e: &Env, role: Role, account: Address, sender: Address) {
+    let ev = RoleRevoked {
+        version: EVENT_VERSION,
+        role,
+        account: account.clone(),
+        sender,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("RoleRevok"), role, account), ev);
+}
+
+pub(crate) fn pause_updated(e: &Env, paused: bool, pauser: Address) {
+    let ev = PauseUpdated {
+        version: EVENT_VERSION,
+        paused,
+        pauser,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Paused"),), ev);
+}
+
+pub(crate) fn action_scheduled(e: &Env, pending: &PendingAction) {
+    let ev = ActionScheduled {
+        version: EVENT_VERSION,
+        action_id: pending.id,
+        action: pending.action.clone(),
+        proposer: pending.proposer.clone(),
+        eta: pending.eta,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Scheduled"), pending.id), ev);
+}
+
+pub(crate) fn action_cancelled(e: &Env, action_id: u32, cancelled_by: Address) {
+    let ev = ActionCancelled {
+        version: EVENT_VERSION,
+        action_id,
+        cancelled_by,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Unsched"), action_id), ev);
+}
+
+pub(crate) fn action_executed(e: &Env, pending: &PendingAction) {
+    let ev = ActionExecuted {
+        version: EVENT_VERSION,
+        action_id: pending.id,
+        action: pending.action.clone(),
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Executed"), pending.id), ev);
+}
+
+pub(crate) fn dispute_config_updated(e: &Env, config: DisputeConfig) {
+    let ev = DisputeConfigUpdated {
+        version: EVENT_VERSION,
+        config,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("DispCfg"),), ev);
+}
+
+pub(crate) fn dispute_opened(e: &Env, ballot: &Ballot) {
+    let ev = DisputeOpened {
+        version: EVENT_VERSION,
+        market_id: ballot.market_id,
+        disputer: ballot.disputer.clone(),
+        original: ballot.original.clone(),
+        proposed: ballot.proposed.clone(),
+        bond: ballot.bond,
+        ends_at: ballot.ends_at,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish(
+        (
+            symbol_short!("Dispute"),
+            ballot.market_id,
+            ballot.disputer.clone(),
+        ),
+        ev,
+    );
+}
+
+pub(crate) fn vote_cast(e: &Env, market_id: u32, voter: Address, vote: &Vote, ballot: &Ballot) {
+    let ev = VoteCast {
+        version: EVENT_VERSION,
+        market_id,
+        voter: voter.clone(),
+        outcome: vote.outcome.clone(),
+        amount: vote.amount,
+        yes_votes: ballot.yes_votes,
+        no_votes: ballot.no_votes,
+        invalid_votes: ballot.invalid_votes,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Vote"), market_id, voter), ev);
+}
+
+pub(crate) fn dispute_settled(e: &Env, ballot: &Ballot, overturned: bool) {
+    let ev = DisputeSettled {
+        version: EVENT_VERSION,
+        market_id: ballot.market_id,
+        result: ballot.result.clone(),
+        overturned,
+        yes_votes: ballot.yes_votes,
+        no_votes: ballot.no_votes,
+        invalid_votes: ballot.invalid_votes,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("DispEnd"), ballot.market_id), ev);
+}
+
+pub(crate) fn vote_withdrawn(e: &Env, market_id: u32, voter: Address, amount: i128, reward: i128) {
+    let ev = VoteWithdrawn {
+        version: EVENT_VERSION,
+        market_id,
+        voter: voter.clone(),
+        amount,
+        reward,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("VoteBack"), market_id, voter), ev);
+}
+
+pub(crate) fn resolver_stake_config_updated(e: &Env, config: ResolverStakeConfig) {
+    let ev = ResolverStakeConfigUpdated {
+        version: EVENT_VERSION,
+        config,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("RStakeCfg"),), ev);
+}
+
+pub(crate) fn resolver_staked(e: &Env, resolver: Address, amount: i128, stake: i128) {
+    let ev = ResolverStaked {
+        version: EVENT_VERSION,
+        resolver: resolver.clone(),
+        amount,
+        stake,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("RStake"), resolver), ev);
+}
+
+pub(crate) fn resolver_unstaked(e: &Env, resolver: Address, amount: i128, stake: i128) {
+    let ev = ResolverUnstaked {
+        version: EVENT_VERSION,
+        resolver: resolver.clone(),
+        amount,
+        stake,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("RUnstake"), resolver), ev);
+}
+
+pub(crate) fn resolver_slashed(
+    e: &Env,
+    market_id: u32,
+    resolver: Address,
+    amount: i128,
+    record: &ResolverRecord,
+) {
+    let ev = ResolverSlashed {
+        version: EVENT_VERSION,
+        market_id,
+        resolver: resolver.clone(),
+        amount,
+        stake: record.stake,
+        overturned: record.overturned,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Slashed"), market_id, resolver), ev);
+}
+
+pub(crate) fn season_created(e: &Env, season: &Season) {
+    let ev = SeasonCreated {
+        version: EVENT_VERSION,
+        season_id: season.id,
+        name: season.config.name.clone(),
+        start_ts: season.config.start_ts,
+        end_ts: season.config.end_ts,
+        metric: season.config.metric,
+        prize_pool: season.prize_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events().publish((symbol_short!("Season"), season.id), ev);
+}
+
+pub(crate) fn season_funded(
+    e: &Env,
+    season_id: u32,
+    funder: Address,
+    amount: i128,
+    prize_pool: i128,
+) {
+    let ev = SeasonFunded {
+        version: EVENT_VERSION,
+        season_id,
+        funder: funder.clone(),
+        amount,
+        prize_pool,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("SeasonFnd"), season_id, funder), ev);
+}
+
+pub(crate) fn season_prize_paid(e: &Env, season_id: u32, player: Address, rank: u32, prize: i128) {
+    let ev = SeasonPrizePaid {
+        version: EVENT_VERSION,
+        season_id,
+        player: player.clone(),
+        rank,
+        prize,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("Prize"), season_id, player), ev);
+}
+
+pub(crate) fn season_finalized(e: &Env, season: &Season, paid: i128) {
+    let ev = SeasonFinalized {
+        version: EVENT_VERSION,
+        season_id: season.id,
+        prize_pool: season.prize_pool,
+        paid,
+        timestamp: e.ledger().timestamp(),
+    };
+    e.events()
+        .publish((symbol_short!("SeasonEnd"), season.id), ev);
+}
@@ -0,0 +1,344 @@
+//! Keeper bounties for permissionless maintenance.
+//!
+//! Settling an oracle market, voiding a market its resolver abandoned and
+//! extending storage TTL all need someone to send a transaction. Callers are
+//! tipped from a bounty pool funded by the protocol fee (and by anyone who
+//! tops it up). One-shot actions pay once per market; TTL bumps pay at most
+//! once per extension window, and only the first caller in it.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Symbol, Vec};
+
+use crate::reflector::{Asset as ReflectorAsset, ReflectorClient};
+use crate::{
+    events, finalize_market, now, read_address, read_market, require_initialized, require_role,
+    token_client, validate_amount, DataKey, Error, FarmersMarket, FarmersMarketArgs,
+    FarmersMarketClient, MarketConfig, Outcome, ResolverChoice, Role, RESOLVER_FALLBACK_SECS,
+};
+
+// Ledgers close roughly every 5 seconds
+const LEDGERS_PER_DAY: u32 = 17_280;
+pub const MARKET_TTL_THRESHOLD: u32 = 7 * LEDGERS_PER_DAY;
+pub const MARKET_TTL_EXTEND_TO: u32 = 30 * LEDGERS_PER_DAY;
+// Stake entries one bump can extend alongside the market
+pub const MAX_TTL_BUMP_STAKERS: u32 = 20;
+
+const DEFAULT_INVALIDATE_GRACE: u64 = 7 * 24 * 60 * 60;
+// Voiding can't start before global Resolvers have had their fallback window
+pub const MIN_INVALIDATE_GRACE: u64 = RESOLVER_FALLBACK_SECS;
+
+/// Oracle rule that settles a market: YES when the asset's price for the
+/// first oracle period at or after `resolution_ts` is at least `strike` (in
+/// oracle decimals)
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PriceCondition {
+    pub asset: Symbol, // Reflector ticker, e.g. BTC
+    pub strike: i128,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BountyConfig {
+    pub settle_tip: i128,      // settling an oracle market
+    pub invalidate_tip: i128,  // voiding a market the resolver never resolved
+    pub ttl_tip: i128,         // extending a market's storage TTL
+    pub invalidate_grace: u64, // how long after resolution_ts before anyone may void
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum KeeperAction {
+    Settle,
+    Invalidate,
+    BumpTtl,
+}
+
+fn read_bounty_config(e: &Env) -> BountyConfig {
+    e.storage()
+        .instance()
+        .get(&DataKey::BountyConfig)
+        .unwrap_or(BountyConfig {
+            settle_tip: 0,
+            invalidate_tip: 0,
+            ttl_tip: 0,
+            invalidate_grace: DEFAULT_INVALIDATE_GRACE,
+        })
+}
+
+fn read_bounty_pool(e: &Env) -> i128 {
+    e.storage()
+        .instance()
+        .get(&DataKey::BountyPool)
+        .unwrap_or(0)
+}
+
+fn write_bounty_pool(e: &Env, balance: i128) {
+    e.storage().instance().set(&DataKey::BountyPool, &balance);
+}
+
+pub(crate) fn add_to_bounty_pool(e: &Env, amount: i128) {
+    if amount > 0 {
+        write_bounty_pool(e, read_bounty_pool(e) + amount);
+    }
+}
+
+pub(crate) fn write_price_condition(e: &Env, market_id: u32, condition: &PriceCondition) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::PriceCondition(market_id), condition);
+    events::price_condition_set(e, market_id, condition.clone());
+}
+
+/// Keepers may settle a market from the oracle unless the creator named an
+/// account to resolve it
+pub(crate) fn keeper_settleable(config: &MarketConfig) -> bool {
+    !matches!(config.resolver, ResolverChoice::Account(_))
+}
+
+/// Give a new market's entries a full TTL and open its first extension
+/// window, so no tip is paid until they could have drifted back under the
+/// threshold
+pub(crate) fn start_ttl_window(e: &Env, market_id: u32) {
+    let storage = e.storage().persistent();
+    for key in [DataKey::Market(market_id), DataKey::Metadata(market_id)] {
+        storage.extend_ttl(&key, MARKET_TTL_EXTEND_TO, MARKET_TTL_EXTEND_TO);
+    }
+    let key = DataKey::TtlBountyLedger(market_id);
+    storage.set(&key, &e.ledger().sequence());
+    storage.extend_ttl(&key, MARKET_TTL_THRESHOLD, MARKET_TTL_EXTEND_TO);
+}
+
+/// The Reflector oracle a market settles against: its own, else the global one
+pub(crate) fn market_oracle(e: &Env, config: &MarketConfig) -> Result<Address, Error> {
+    match &config.resolver {
+        ResolverChoice::Oracle(oracle) => Ok(oracle.clone()),
+        _ => read_address(e, &DataKey::ReflectorOracle),
+    }
+}
+
+/// Pay `tip` (or whatever is left in the pool) to the keeper
+fn pay_bounty(
+    e: &Env,
+    keeper: &Address,
+    market_id: u32,
+    action: KeeperAction,
+    tip: i128,
+) -> Result<i128, Error> {
+    let pool = read_bounty_pool(e);
+    let paid = tip.min(pool);
+    if paid > 0 {
+        write_bounty_pool(e, pool - paid);
+        let token_addr = read_address(e, &DataKey::KaleToken)?;
+        token_client(e, &token_addr).transfer(&e.current_contract_address(), keeper, &paid);
+    }
+    events::keeper_paid(e, market_id, keeper.clone(), action, paid);
+    Ok(paid)
+}
+
+/// Pay a one-shot action's tip unless this market already paid for it
+fn pay_once(
+    e: &Env,
+    keeper: &Address,
+    market_id: u32,
+    action: KeeperAction,
+    tip: i128,
+) -> Result<i128, Error> {
+    let key = DataKey::BountyPaid(market_id, action);
+    if e.storage().persistent().has(&key) {
+        return Ok(0);
+    }
+    e.storage().persistent().set(&key, &true);
+    pay_bounty(e, keeper, market_id, action, tip)
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Attach an oracle rule so anyone can settle the market once it is due.
+    /// Only the creator can set it, only before the first bet, and not on a
+    /// market with an assigned resolver account.
+    pub fn set_price_condition(
+        e: Env,
+        market_id: u32,
+        condition: PriceCondition,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        let market = read_market(&e, market_id)?;
+        market.creator.require_auth();
+
+        if market.resolved {
+            return Err(Error::MarketClosed);
+        }
+        if market.yes_pool > 0 || market.no_pool > 0 || market.committed > 0 {
+            return Err(Error::MetadataLocked);
+        }
+        if condition.strike <= 0 || !keeper_settleable(&market.config) {
+            return Err(Error::InvalidAmount);
+        }
+        write_price_condition(&e, market_id, &condition);
+        Ok(())
+    }
+
+    /// Resolve a market from its price condition. Anyone can call this once
+    /// the market is due and the oracle has published the price for the
+    /// period `resolution_ts` falls in. Later prices are ignored, so waiting
+    /// for the market to move can't change the outcome.
+    pub fn settle_market(e: Env, keeper: Address, market_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        keeper.require_auth();
+
+        let market = read_market(&e, market_id)?;
+        if market.resolved {
+            return Err(Error::AlreadyResolved);
+        }
+        if now(&e) < market.resolution_ts {
+            return Err(Error::InvalidTime);
+        }
+        let condition: PriceCondition = e
+            .storage()
+            .persistent()
+            .get(&DataKey::PriceCondition(market_id))
+            .ok_or(Error::NoPriceCondition)?;
+
+        // Reflector records prices on period boundaries. The first one at or
+        // after resolution_ts decides; one before it says nothing about the
+        // outcome.
+        let oracle = ReflectorClient::new(&e, &market_oracle(&e, &market.config)?);
+        let period = oracle.resolution().max(1) as u64;
+        let price = oracle
+            .price(
+                &ReflectorAsset::Other(condition.asset),
+                &market.resolution_ts.div_ceil(period).saturating_mul(period),
+            )
+            .ok_or(Error::OraclePriceUnavailable)?;
+
+        let outcome = if price.price >= condition.strike {
+            Outcome::Yes
+        } else {
+            Outcome::No
+        };
+        finalize_market(&e, market, outcome)?;
+
+        let tip = read_bounty_config(&e).settle_tip;
+        pay_once(&e, &keeper, market_id, KeeperAction::Settle, tip)
+    }
+
+    /// Void a market the resolver has left unresolved past the grace period.
+    /// Stakers are refunded through `claim()` and the creator keeps the bond.
+    pub fn force_invalidate(e: Env, keeper: Address, market_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        keeper.require_auth();
+
+        let market = read_market(&e, market_id)?;
+        if market.resolved {
+            return Err(Error::AlreadyResolved);
+        }
+        let config = read_bounty_config(&e);
+        if now(&e) < market.resolution_ts.saturating_add(config.invalidate_grace) {
+            return Err(Error::InvalidTime);
+        }
+
+        finalize_market(&e, market, Outcome::Invalid)?;
+        pay_once(
+            &e,
+            &keeper,
+            market_id,
+            KeeperAction::Invalidate,
+            config.invalidate_tip,
+        )
+    }
+
+    /// Extend the storage TTL of a market, its metadata and the listed
+    /// stakers' positions. Anyone can bump at any time. The tip is paid once
+    /// per extension window, and only while the market is unresolved with
+    /// KALE at stake; claimers of a resolved market bump for free.
+    pub fn bump_market_ttl(
+        e: Env,
+        keeper: Address,
+        market_id: u32,
+        stakers: Vec<Address>,
+    ) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        keeper.require_auth();
+        let market = read_market(&e, market_id)?;
+        if stakers.len() > MAX_TTL_BUMP_STAKERS {
+            return Err(Error::InvalidAmount);
+        }
+
+        let storage = e.storage().persistent();
+        let mut keys = Vec::from_array(
+            &e,
+            [DataKey::Market(market_id), DataKey::Metadata(market_id)],
+        );
+        for staker in stakers.iter() {
+            keys.push_back(DataKey::Stake(market_id, staker));
+        }
+        for key in keys.iter() {
+            if storage.has(&key) {
+                storage.extend_ttl(&key, MARKET_TTL_THRESHOLD, MARKET_TTL_EXTEND_TO);
+            }
+        }
+
+        if market.resolved || market.yes_pool + market.no_pool + market.committed == 0 {
+            return Ok(0);
+        }
+        // The entries were last extended to the full TTL at `last`, so they
+        // can only be back under the threshold once the difference has passed
+        let seq = e.ledger().sequence();
+        let key = DataKey::TtlBountyLedger(market_id);
+        if let Some(last) = storage.get::<_, u32>(&key) {
+            if seq < last + (MARKET_TTL_EXTEND_TO - MARKET_TTL_THRESHOLD) {
+                return Ok(0);
+            }
+        }
+        storage.set(&key, &seq);
+        storage.extend_ttl(&key, MARKET_TTL_THRESHOLD, MARKET_TTL_EXTEND_TO);
+
+        let tip = read_bounty_config(&e).ttl_tip;
+        pay_bounty(&e, &keeper, market_id, KeeperAction::BumpTtl, tip)
+    }
+
+    /// Top up the keeper bounty pool
+    pub fn fund_bounty_pool(e: Env, from: Address, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        from.require_auth();
+        validate_amount(amount)?;
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&from, &e.current_contract_address(), &amount);
+        add_to_bounty_pool(&e, amount);
+
+        events::bounty_pool_funded(&e, from, amount, read_bounty_pool(&e));
+        Ok(())
+    }
+
+    /// Set the keeper tips paid from the bounty pool and the invalidation
+    /// grace, at least `MIN_INVALIDATE_GRACE` (FeeManager only)
+    pub fn set_bounty_config(e: Env, manager: Address, config: BountyConfig) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &manager, Role::FeeManager)?;
+        if config.settle_tip < 0
+            || config.invalidate_tip < 0
+            || config.ttl_tip < 0
+            || config.invalidate_grace < MIN_INVALIDATE_GRACE
+        {
+            return Err(Error::InvalidAmount);
+        }
+        e.storage().instance().set(&DataKey::BountyConfig, &config);
+
+        events::bounty_config_updated(&e, config);
+        Ok(())
+    }
+
+    pub fn get_price_condition(e: Env, market_id: u32) -> Option<PriceCondition> {
+        e.storage()
+            .persistent()
+            .get(&DataKey::PriceCondition(market_id))
+    }
+
+    pub fn get_bounty_config(e: Env) -> BountyConfig {
+        read_bounty_config(&e)
+    }
+
+    pub fn get_bounty_pool(e: Env) -> i128 {
+        read_bounty_pool(&e)
+    }
+}
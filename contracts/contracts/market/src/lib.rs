@@ -1,11 +1,33 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractclient, contracterror, contractimpl, contracttype, symbol_short, token,
-    Address, Env, String, Symbol,
+    contract, contractclient, contracterror, contractimpl, contracttype, token, Address, Env,
+    String, Symbol, Vec,
 };
 
-mod reflector;
+pub mod commit_reveal;
+pub mod dispute;
+pub mod events;
+pub mod keeper;
+pub mod liquidity;
+#[cfg(any(test, feature = "testutils"))]
+pub mod mock_reflector;
+pub mod orderbook;
+pub mod parlay;
+pub mod reflector;
+pub mod roles;
+pub mod seasons;
+pub mod shares;
+pub mod staking;
+pub mod subsidy;
+pub mod templates;
+pub mod timelock;
+pub mod weighting;
+use keeper::KeeperAction;
+use orderbook::OrderSide;
 use reflector::{Asset as ReflectorAsset, PriceData, ReflectorClient};
+use roles::{require_not_paused, require_role, Role, RoleKey};
+use seasons::SeasonKey;
+use staking::StakingKey;
 
 // -------------------------------
 // Errors
@@ -16,8 +38,7 @@ use reflector::{Asset as ReflectorAsset, PriceData, ReflectorClient};
 pub enum Error {
     NotInitialized = 1,
     AlreadyInitialized = 2,
-    NotResolver = 3,
-    NotAdmin = 4,
+    // 3 and 4 were NotResolver and NotAdmin; role checks now fail with MissingRole
     MarketClosed = 5,
     BetsClosed = 6,
     AlreadyResolved = 7,
@@ -27,6 +48,46 @@ pub enum Error {
     InvalidTime = 11,
     MarketNotFound = 12,
     OraclePriceUnavailable = 13,
+    CreatorNotAllowed = 14,
+    CreationRateLimited = 15,
+    MetadataTooLong = 16,
+    TooManyTags = 17,
+    MetadataLocked = 18,
+    BetTooSmall = 19,
+    BetTooLarge = 20,
+    UserStakeCapExceeded = 21,
+    PoolCapExceeded = 22,
+    InvalidParlay = 23,
+    ParlayLegUnpriced = 24,
+    ParlaySlippage = 25,
+    InsufficientHouseLiquidity = 26,
+    ParlayNotFound = 27,
+    // 28 was ParlaySettled; settling a ticket twice fails with AlreadyResolved
+    TemplateNotFound = 29,
+    TemplateInactive = 30,
+    TemplateNotDue = 31,
+    NoPriceCondition = 32,
+    OraclePriceStale = 33,
+    InsufficientAllowance = 34,
+    InsufficientShares = 35,
+    OrderNotFound = 36,
+    OrderBookFull = 37,
+    OpenOrders = 38,
+    WrongBettingMode = 39,
+    CommitmentExists = 40,
+    NoCommitment = 41,
+    RevealMismatch = 42,
+    MissingRole = 43,
+    LastAdmin = 44,
+    ContractPaused = 45,
+    ActionNotFound = 46,
+    ActionNotReady = 47,
+    ActionExpired = 48,
+    TooManyPendingActions = 49,
+    DisputePending = 50,
+    NotDisputable = 51,
+    AlreadyVoted = 52,
+    InsufficientResolverStake = 53,
 }
 
 // -------------------------------
@@ -40,6 +101,64 @@ pub enum Outcome {
     Invalid, // for refunds
 }
 
+/// What happens to the pool when the resolved side has no stakers
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum NoWinnerPolicy {
+    Refund,   // settle as Invalid so everyone gets their stake back
+    Treasury, // sweep the whole pool to the treasury on resolve
+}
+
+/// Whether bets are placed in the open or committed as a hash and revealed
+/// after close, and what happens to commitments nobody reveals
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum CommitRevealMode {
+    Off,               // bets are public as soon as they are placed
+    RefundUnrevealed,  // sealed bets; unrevealed KALE goes back to the bettor
+    ForfeitUnrevealed, // sealed bets; unrevealed KALE goes to the treasury
+}
+
+/// Who settles a market. Global Resolvers can always step in once
+/// `RESOLVER_FALLBACK_SECS` have passed since `resolution_ts`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ResolverChoice {
+    Default,          // any Resolver role holder
+    Account(Address), // only this address calls `resolve`
+    Oracle(Address),  // `settle_market` against this Reflector oracle
+}
+
+/// Bet size limits in KALE; 0 disables a limit
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BetLimits {
+    pub min_bet: i128,        // smallest single bet
+    pub max_bet: i128,        // largest single bet
+    pub max_user_stake: i128, // total a user may stake across both sides
+    pub max_pool: i128,       // total KALE the market accepts
+}
+
+/// Bet limits requested at creation
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum BetLimitsChoice {
+    Default, // snapshot the admin defaults
+    Custom(BetLimits),
+}
+
+/// Per-market settings chosen at creation
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketConfig {
+    pub no_winner_policy: NoWinnerPolicy,
+    pub bet_limits: BetLimitsChoice,
+    pub subsidy: i128,        // KALE the creator adds for winners, 0 for none
+    pub early_bonus_bps: u32, // extra weight for staking at open, decaying to 0 at close
+    pub commit_reveal: CommitRevealMode,
+    pub resolver: ResolverChoice,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[contracttype]
 pub struct Market {
@@ -50,8 +169,69 @@ pub struct Market {
     pub resolution_ts: u64, // when market can be resolved
     pub resolved: bool,
     pub outcome: Outcome,
-    pub yes_pool: i128, // total KALE staked on YES
-    pub no_pool: i128,  // total KALE staked on NO
+    pub yes_pool: i128,   // total KALE staked on YES
+    pub no_pool: i128,    // total KALE staked on NO
+    pub yes_weight: i128, // time-weighted YES stake, equal to yes_pool when unweighted
+    pub no_weight: i128,
+    pub created_ts: u64,
+    pub config: MarketConfig,
+    pub bond: i128, // creation bond held until the market resolves
+    pub limits: BetLimits,
+    pub committed: i128, // sealed KALE not yet revealed or settled
+}
+
+/// Market category, one per stall in the farmers market
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Category {
+    Crypto,
+    Weather,
+    Farming,
+    Sports,
+    Gaming,
+    DeFi,
+    Governance,
+    Other,
+}
+
+/// Descriptive details the frontend shows alongside the question
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketMetadata {
+    pub description: String,
+    pub category: Category,
+    pub tags: Vec<String>,
+    pub resolution_source: String,    // URL the resolver will check
+    pub content_hash: Option<String>, // IPFS CID of off-chain details
+}
+
+/// Admin rules for who may create markets and at what cost
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CreationPolicy {
+    pub bond: i128,              // KALE locked per market, 0 disables the bond
+    pub allowlist_enabled: bool, // only allowlisted creators may create markets
+    pub max_per_window: u32,     // markets per creator per window, 0 = unlimited
+    pub window_secs: u64,        // length of the rate limit window
+}
+
+/// Protocol fee on claimed winnings. Markets with liquidity providers pay
+/// them a cut first; part of the rest funds keeper bounties and the
+/// remainder goes to the treasury.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeConfig {
+    pub fee_bps: u32,          // share of a winner's profit, 0 disables the fee
+    pub bounty_share_bps: u32, // share of the fee (after the LP cut) kept for keeper bounties
+    pub lp_share_bps: u32,     // share of the fee paid to the market's liquidity providers
+}
+
+/// Rate limit bookkeeping for a single creator
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct CreatorWindow {
+    pub start: u64,
+    pub count: u32,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
@@ -60,6 +240,8 @@ pub struct Stake {
     pub yes: i128,
     pub no: i128,
     pub claimed: bool,
+    pub yes_weight: i128, // time-weighted amounts that claim() pays out on
+    pub no_weight: i128,
 }
 
 // -------------------------------
@@ -68,13 +250,66 @@ pub struct Stake {
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[contracttype]
 pub enum DataKey {
-    Admin,
-    Resolver,
     KaleToken,
     NextMarketId,
     Market(u32),
     Stake(u32, Address), // (market_id, user)
     ReflectorOracle,     // Reflector oracle contract address
+    Treasury,            // receives pools swept under NoWinnerPolicy::Treasury
+    CreationPolicy,
+    CreatorAllowed(Address),
+    CreatorWindow(Address),
+    CancelReason(u32), // why the admin cancelled a market
+    Metadata(u32),
+    Categories(CategoryKey), // per-category market listings
+    DefaultBetLimits,
+    ParlayHouse,
+    NextParlayId,
+    Parlay(u32),
+    NextTemplateId,
+    Template(u32),
+    TemplateState(u32),
+    TemplateMarkets(u32, u32), // (template_id, n) -> its nth spawned market
+    MarketTemplate(u32),       // market_id -> TemplateInstance
+    FeeConfig,
+    BountyConfig,
+    BountyPool,                                  // KALE held for keeper tips
+    PriceCondition(u32),                         // oracle rule that settles a market
+    BountyPaid(u32, KeeperAction),               // one-shot keeper actions already rewarded
+    TtlBountyLedger(u32),                        // ledger the market entries last got a full TTL
+    ShareAllowance(u32, bool, Address, Address), // (market_id, side_yes, from, spender)
+    NextOrderId,
+    Order(u32),
+    OrderBook(u32, OrderSide), // resting order ids, best price first
+    OpenAsks(u32, Address),    // asks a trader has resting on a market
+    LiquidityPool(u32),
+    LpShares(u32, Address),
+    Subsidy(u32),                // KALE paid to winners on top of the pool
+    SubsidyFunder(u32, Address), // what each funder put in, for refunds
+    Commitment(u32, Address),    // sealed bet awaiting reveal
+    Role(Role, Address),         // a holder's place in its role's list
+    RoleMembers(RoleKey),        // holders of a role, so the last Admin can't be revoked
+    Paused,
+    TimelockDelay,
+    NextActionId,
+    TimelockAction(u32),
+    PendingActions, // queued action ids, oldest first
+    DisputeConfig,
+    DisputeDeadline(u32), // end of a resolved market's dispute window
+    Ballot(u32),
+    Vote(u32, Address),
+    Staking(StakingKey), // grouped to stay under the contracttype case limit
+    Seasons(SeasonKey),
+}
+
+/// Category listings, one entry per market so creating a market never
+/// rewrites the whole list
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum CategoryKey {
+    Count(Category),      // entries ever added to the category
+    Entry(Category, u32), // (category, n) -> market id, oldest first
+    Slot(u32),            // market_id -> its n in its current category
 }
 
 // -------------------------------
@@ -96,9 +331,17 @@ fn token_client<'a>(e: &'a Env, token: &'a Address) -> token::Client<'a> {
     token::Client::new(e, token)
 }
 
-// KALE SAC (Stellar Asset Contract) addresses
-const KALE_SAC_MAINNET: &str = "CB23WRDQWGSP6YPMY4UV5C4OW5CBTXKYN3XEATG7KJEZCXMJBYEHOUOV";
-const KALE_SAC_TESTNET: &str = "CAAVU2UQJLMZ3GUZFM56KVNHLPA3ZSSNR4VP2U53YBXFD2GI3QLIVHZZ";
+// Metadata length limits (bytes)
+const MAX_QUESTION_LEN: u32 = 256;
+const MAX_DESCRIPTION_LEN: u32 = 1024;
+const MAX_TAGS: u32 = 5;
+const MAX_TAG_LEN: u32 = 32;
+const MAX_SOURCE_LEN: u32 = 256;
+const MAX_CONTENT_HASH_LEN: u32 = 128;
+
+// How long a market's own resolver or oracle has before global Resolvers may
+// settle it instead
+pub const RESOLVER_FALLBACK_SECS: u64 = 3 * 86_400;
 
 // Reflector Oracle address (Testnet CEX & DEX)
 const REFLECTOR_CEX_DEX_TESTNET: &str = "CCYOZJCOPG34LLQQ7N24YXBM7LL62R7ONMZ3G6WZAAYPB5OYKOMJRN63";
@@ -140,6 +383,52 @@ fn write_market(e: &Env, m: &Market) {
     e.storage().persistent().set(&DataKey::Market(m.id), m);
 }
 
+fn read_metadata(e: &Env, id: u32) -> Result<MarketMetadata, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Metadata(id))
+        .ok_or(Error::MarketNotFound)
+}
+
+fn write_metadata(e: &Env, id: u32, m: &MarketMetadata) {
+    e.storage().persistent().set(&DataKey::Metadata(id), m);
+}
+
+fn category_count(e: &Env, category: &Category) -> u32 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Categories(CategoryKey::Count(category.clone())))
+        .unwrap_or(0)
+}
+
+/// Append a market to a category's listing
+fn add_to_category(e: &Env, category: &Category, market_id: u32) {
+    let n = category_count(e, category);
+    let storage = e.storage().persistent();
+    storage.set(
+        &DataKey::Categories(CategoryKey::Entry(category.clone(), n)),
+        &market_id,
+    );
+    storage.set(
+        &DataKey::Categories(CategoryKey::Count(category.clone())),
+        &(n + 1),
+    );
+    storage.set(&DataKey::Categories(CategoryKey::Slot(market_id)), &n);
+}
+
+/// Drop a market's entry from a category's listing, leaving a gap so the
+/// other entries keep their place
+fn remove_from_category(e: &Env, category: &Category, market_id: u32) {
+    let storage = e.storage().persistent();
+    let slot: Option<u32> = storage.get(&DataKey::Categories(CategoryKey::Slot(market_id)));
+    if let Some(n) = slot {
+        storage.remove(&DataKey::Categories(CategoryKey::Entry(
+            category.clone(),
+            n,
+        )));
+    }
+}
+
 fn read_stake(e: &Env, id: u32, user: &Address) -> Stake {
     e.storage()
         .persistent()
@@ -153,10 +442,129 @@ fn write_stake(e: &Env, id: u32, user: &Address, s: &Stake) {
         .set(&DataKey::Stake(id, user.clone()), s);
 }
 
+fn read_creation_policy(e: &Env) -> CreationPolicy {
+    e.storage()
+        .instance()
+        .get(&DataKey::CreationPolicy)
+        .unwrap_or(CreationPolicy {
+            bond: 0,
+            allowlist_enabled: false,
+            max_per_window: 0,
+            window_secs: 0,
+        })
+}
+
+fn read_fee_config(e: &Env) -> FeeConfig {
+    e.storage()
+        .instance()
+        .get(&DataKey::FeeConfig)
+        .unwrap_or(FeeConfig {
+            fee_bps: 0,
+            bounty_share_bps: 0,
+            lp_share_bps: 0,
+        })
+}
+
+fn read_default_bet_limits(e: &Env) -> BetLimits {
+    e.storage()
+        .instance()
+        .get(&DataKey::DefaultBetLimits)
+        .unwrap_or(BetLimits {
+            min_bet: 0,
+            max_bet: 0,
+            max_user_stake: 0,
+            max_pool: 0,
+        })
+}
+
+fn is_allowed_creator(e: &Env, creator: &Address) -> bool {
+    e.storage()
+        .persistent()
+        .get(&DataKey::CreatorAllowed(creator.clone()))
+        .unwrap_or(false)
+}
+
 fn now(e: &Env) -> u64 {
     e.ledger().timestamp()
 }
 
+// -------------------------------
+// Settlement helpers
+// -------------------------------
+
+/// Pool staked on the side that won, or None for Invalid
+fn winning_pool(m: &Market) -> Option<i128> {
+    match m.outcome {
+        Outcome::Yes => Some(m.yes_pool),
+        Outcome::No => Some(m.no_pool),
+        Outcome::Invalid => None,
+    }
+}
+
+/// Outcome used for payouts. A Yes/No result with nobody on the winning
+/// side settles as Invalid when the market refunds in that case.
+fn payout_outcome(m: &Market) -> Outcome {
+    if winning_pool(m) == Some(0) && m.config.no_winner_policy == NoWinnerPolicy::Refund {
+        return Outcome::Invalid;
+    }
+    m.outcome.clone()
+}
+
+/// Record a market's outcome, hand the creation bond back and apply the
+/// no-winner policy. Callers check the market is due and unresolved.
+fn finalize_market(e: &Env, mut market: Market, outcome: Outcome) -> Result<(), Error> {
+    market.resolved = true;
+    market.outcome = outcome;
+    staking::release_market(e, &market);
+    write_market(e, &market);
+    dispute::open_dispute_window(e, market.id);
+
+    events::market_resolved(e, &market);
+
+    // A cleanly resolved market gets its creation bond back, but not while
+    // its outcome can still be disputed
+    if dispute::require_final(e, market.id).is_ok() {
+        return_bond(e, &mut market)?;
+    }
+
+    sweep_unwinnable_pool(e, &market)
+}
+
+/// Hand a resolved market's creation bond back to its creator
+pub(crate) fn return_bond(e: &Env, market: &mut Market) -> Result<(), Error> {
+    let bond = market.bond;
+    if bond == 0 {
+        return Ok(());
+    }
+    market.bond = 0;
+    write_market(e, market);
+
+    let token_addr = read_address(e, &DataKey::KaleToken)?;
+    token_client(e, &token_addr).transfer(&e.current_contract_address(), &market.creator, &bond);
+    events::bond_returned(e, market, bond);
+    Ok(())
+}
+
+/// Nobody backed the winning side: under `NoWinnerPolicy::Treasury` the
+/// pool goes to the treasury
+pub(crate) fn sweep_unwinnable_pool(e: &Env, market: &Market) -> Result<(), Error> {
+    let total_pool = market.yes_pool + market.no_pool;
+    if winning_pool(market) == Some(0)
+        && market.config.no_winner_policy == NoWinnerPolicy::Treasury
+        && total_pool > 0
+    {
+        let token_addr = read_address(e, &DataKey::KaleToken)?;
+        let treasury = read_address(e, &DataKey::Treasury)?;
+        token_client(e, &token_addr).transfer(
+            &e.current_contract_address(),
+            &treasury,
+            &total_pool,
+        );
+        events::pool_swept(e, market.id, treasury, total_pool);
+    }
+    Ok(())
+}
+
 // -------------------------------
 // Validation helpers
 // -------------------------------
@@ -170,6 +578,123 @@ fn validate_market_timing(e: &Env, close_ts: u64, resolution_ts: u64) -> Result<
     Ok(())
 }
 
+fn validate_metadata(m: &MarketMetadata) -> Result<(), Error> {
+    if m.description.len() > MAX_DESCRIPTION_LEN || m.resolution_source.len() > MAX_SOURCE_LEN {
+        return Err(Error::MetadataTooLong);
+    }
+    if let Some(hash) = &m.content_hash {
+        if hash.len() > MAX_CONTENT_HASH_LEN {
+            return Err(Error::MetadataTooLong);
+        }
+    }
+    if m.tags.len() > MAX_TAGS {
+        return Err(Error::TooManyTags);
+    }
+    for tag in m.tags.iter() {
+        if tag.len() > MAX_TAG_LEN {
+            return Err(Error::MetadataTooLong);
+        }
+    }
+    Ok(())
+}
+
+fn validate_bet_limits(l: &BetLimits) -> Result<(), Error> {
+    if l.min_bet < 0 || l.max_bet < 0 || l.max_user_stake < 0 || l.max_pool < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if l.max_bet > 0 && l.min_bet > l.max_bet {
+        return Err(Error::InvalidAmount);
+    }
+    Ok(())
+}
+
+/// Check a bet against the market's limits given the user's current stake.
+/// Sealed KALE still waiting to be revealed counts towards the pool cap.
+fn check_bet_limits(m: &Market, stake: &Stake, amount: i128) -> Result<(), Error> {
+    let l = &m.limits;
+    if amount < l.min_bet {
+        return Err(Error::BetTooSmall);
+    }
+    if l.max_bet > 0 && amount > l.max_bet {
+        return Err(Error::BetTooLarge);
+    }
+    if l.max_user_stake > 0 && stake.yes + stake.no + amount > l.max_user_stake {
+        return Err(Error::UserStakeCapExceeded);
+    }
+    if l.max_pool > 0 && m.yes_pool + m.no_pool + m.committed + amount > l.max_pool {
+        return Err(Error::PoolCapExceeded);
+    }
+    Ok(())
+}
+
+fn validate_market_config(config: &MarketConfig) -> Result<(), Error> {
+    if config.subsidy < 0 || config.early_bonus_bps > weighting::MAX_EARLY_BONUS_BPS {
+        return Err(Error::InvalidAmount);
+    }
+    Ok(())
+}
+
+/// Authenticate `resolver` and check it may settle `market`: its assigned
+/// account, or a global Resolver where none is assigned or the assignee
+/// is past the fallback delay
+fn require_market_resolver(e: &Env, market: &Market, resolver: &Address) -> Result<(), Error> {
+    let assigned = match &market.config.resolver {
+        ResolverChoice::Default => return require_role(e, resolver, Role::Resolver),
+        ResolverChoice::Account(account) => Some(account),
+        ResolverChoice::Oracle(_) => None,
+    };
+    if assigned == Some(resolver) {
+        resolver.require_auth();
+        return Ok(());
+    }
+    if now(e) < market.resolution_ts.saturating_add(RESOLVER_FALLBACK_SECS) {
+        resolver.require_auth();
+        return Err(Error::MissingRole);
+    }
+    require_role(e, resolver, Role::Resolver)
+}
+
+fn validate_creation_policy(p: &CreationPolicy) -> Result<(), Error> {
+    if p.bond < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if p.max_per_window > 0 && p.window_secs == 0 {
+        return Err(Error::InvalidTime);
+    }
+    Ok(())
+}
+
+/// Enforce the allowlist and per-creator rate limit, counting this market
+fn check_creator(e: &Env, creator: &Address, policy: &CreationPolicy) -> Result<(), Error> {
+    if policy.allowlist_enabled && !is_allowed_creator(e, creator) {
+        return Err(Error::CreatorNotAllowed);
+    }
+    if policy.max_per_window == 0 {
+        return Ok(());
+    }
+
+    let key = DataKey::CreatorWindow(creator.clone());
+    let mut window: CreatorWindow = e
+        .storage()
+        .persistent()
+        .get(&key)
+        .unwrap_or(CreatorWindow { start: 0, count: 0 });
+
+    if now(e) >= window.start + policy.window_secs {
+        window = CreatorWindow {
+            start: now(e),
+            count: 0,
+        };
+    }
+    if window.count >= policy.max_per_window {
+        return Err(Error::CreationRateLimited);
+    }
+
+    window.count += 1;
+    e.storage().persistent().set(&key, &window);
+    Ok(())
+}
+
 fn validate_amount(amount: i128) -> Result<(), Error> {
     if amount <= 0 {
         return Err(Error::InvalidAmount);
@@ -177,6 +702,131 @@ fn validate_amount(amount: i128) -> Result<(), Error> {
     Ok(())
 }
 
+// -------------------------------
+// Market helpers
+// -------------------------------
+
+/// Limits a new market enforces: its own, or the current defaults
+/// A cap may only tighten the Admin's: 0 means unlimited on both sides
+fn within_cap(custom: i128, admin: i128) -> bool {
+    admin == 0 || (custom > 0 && custom <= admin)
+}
+
+/// Limits for a new market. Custom limits can be stricter than the Admin's
+/// defaults but never looser: `min_bet` is a floor and the caps a ceiling.
+fn market_limits(e: &Env, config: &MarketConfig) -> Result<BetLimits, Error> {
+    let defaults = read_default_bet_limits(e);
+    match &config.bet_limits {
+        BetLimitsChoice::Custom(l) => {
+            validate_bet_limits(l)?;
+            if l.min_bet < defaults.min_bet
+                || !within_cap(l.max_bet, defaults.max_bet)
+                || !within_cap(l.max_user_stake, defaults.max_user_stake)
+                || !within_cap(l.max_pool, defaults.max_pool)
+            {
+                return Err(Error::InvalidAmount);
+            }
+            Ok(l.clone())
+        }
+        BetLimitsChoice::Default => Ok(defaults),
+    }
+}
+
+/// Give a new market the next id, store it and index it by category
+fn insert_market(e: &Env, mut market: Market, metadata: MarketMetadata) -> Result<u32, Error> {
+    let id = read_u32(e, &DataKey::NextMarketId)?;
+    market.id = id;
+
+    write_market(e, &market);
+    write_u32(e, DataKey::NextMarketId, id + 1);
+
+    add_to_category(e, &metadata.category, id);
+    write_metadata(e, id, &metadata);
+    keeper::start_ttl_window(e, id);
+    staking::assign_market(e, &market);
+
+    events::market_created(e, &market, metadata.category);
+    Ok(id)
+}
+
+/// Take `amount` from `user` (through `spender`'s allowance when given)
+/// and add it to their stake
+fn place_bet(
+    e: &Env,
+    user: &Address,
+    spender: Option<&Address>,
+    market_id: u32,
+    side_yes: bool,
+    amount: i128,
+) -> Result<(), Error> {
+    require_not_paused(e)?;
+    validate_amount(amount)?;
+
+    let mut market = read_market(e, market_id)?;
+
+    if market.resolved {
+        return Err(Error::MarketClosed);
+    }
+    if now(e) >= market.close_ts {
+        return Err(Error::BetsClosed);
+    }
+
+    // Sealed markets only take bets through commit_bet
+    if market.config.commit_reveal != CommitRevealMode::Off {
+        return Err(Error::WrongBettingMode);
+    }
+
+    let stake = read_stake(e, market_id, user);
+    check_bet_limits(&market, &stake, amount)?;
+
+    // Transfer KALE from user to contract
+    let token_addr = read_address(e, &DataKey::KaleToken)?;
+    let token_client = token_client(e, &token_addr);
+    match spender {
+        Some(spender) => {
+            if token_client.allowance(user, spender) < amount {
+                return Err(Error::InsufficientAllowance);
+            }
+            token_client.transfer_from(spender, user, &e.current_contract_address(), &amount);
+        }
+        None => token_client.transfer(user, &e.current_contract_address(), &amount),
+    }
+
+    let weight = weighting::weigh(e, &market, amount);
+    credit_bet(e, &mut market, user, side_yes, amount, weight);
+    Ok(())
+}
+
+/// Add a paid-for bet to the market's pools and the user's stake
+fn credit_bet(
+    e: &Env,
+    market: &mut Market,
+    user: &Address,
+    side_yes: bool,
+    amount: i128,
+    weight: i128,
+) {
+    let mut stake = read_stake(e, market.id, user);
+    let before = stake.clone();
+    if side_yes {
+        market.yes_pool += amount;
+        market.yes_weight += weight;
+        stake.yes += amount;
+        stake.yes_weight += weight;
+    } else {
+        market.no_pool += amount;
+        market.no_weight += weight;
+        stake.no += amount;
+        stake.no_weight += weight;
+    }
+
+    write_market(e, market);
+    write_stake(e, market.id, user, &stake);
+    seasons::record_bet(e, market.id, user, side_yes, amount, &before, &stake);
+
+    events::bet_placed(e, market, user.clone(), side_yes, amount, &stake);
+}
+
 // -------------------------------
 // Contract
 // -------------------------------
@@ -198,17 +848,26 @@ impl FarmersMarket {
 
         admin.require_auth();
 
-        write_address(&e, DataKey::Admin, admin);
-        write_address(&e, DataKey::Resolver, resolver);
-        write_address(&e, DataKey::KaleToken, kale_sac_address); // This is KALE's SAC address
+        for role in [
+            Role::Admin,
+            Role::Moderator,
+            Role::FeeManager,
+            Role::Pauser,
+            Role::OracleManager,
+        ] {
+            roles::set_role(&e, role, &admin, &admin);
+        }
+        roles::set_role(&e, Role::Resolver, &resolver, &admin);
+        write_address(&e, DataKey::Treasury, admin.clone());
+        write_address(&e, DataKey::KaleToken, kale_sac_address.clone()); // This is KALE's SAC address
 
         // Store Reflector oracle address
         let oracle_addr = Address::from_string(&String::from_str(&e, REFLECTOR_CEX_DEX_TESTNET));
-        write_address(&e, DataKey::ReflectorOracle, oracle_addr);
+        write_address(&e, DataKey::ReflectorOracle, oracle_addr.clone());
 
         write_u32(&e, DataKey::NextMarketId, 1);
 
-        e.events().publish((symbol_short!("Init"),), true);
+        events::initialized(&e, admin, resolver, kale_sac_address, oracle_addr);
         Ok(())
     }
 
@@ -219,32 +878,86 @@ impl FarmersMarket {
         question: String,
         close_ts: u64,
         resolution_ts: u64,
+        config: MarketConfig,
+        metadata: MarketMetadata,
     ) -> Result<u32, Error> {
         require_initialized(&e)?;
         creator.require_auth();
+        require_not_paused(&e)?;
 
         validate_market_timing(&e, close_ts, resolution_ts)?;
-
-        let id = read_u32(&e, &DataKey::NextMarketId)?;
+        if question.len() > MAX_QUESTION_LEN {
+            return Err(Error::MetadataTooLong);
+        }
+        validate_metadata(&metadata)?;
+
+        let limits = market_limits(&e, &config)?;
+        validate_market_config(&config)?;
+
+        let policy = read_creation_policy(&e);
+        check_creator(&e, &creator, &policy)?;
+
+        // Lock the creation bond until the market resolves
+        if policy.bond > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            token_client(&e, &token_addr).transfer(
+                &creator,
+                &e.current_contract_address(),
+                &policy.bond,
+            );
+        }
 
         let market = Market {
-            id,
-            question: question.clone(),
-            creator: creator.clone(),
+            id: 0,
+            question,
+            creator,
             close_ts,
             resolution_ts,
             resolved: false,
             outcome: Outcome::Invalid,
             yes_pool: 0,
             no_pool: 0,
+            yes_weight: 0,
+            no_weight: 0,
+            created_ts: now(&e),
+            config,
+            bond: policy.bond,
+            limits,
+            committed: 0,
         };
+        let subsidy = market.config.subsidy;
+        let creator = market.creator.clone();
+        let market_id = insert_market(&e, market, metadata)?;
+        if subsidy > 0 {
+            subsidy::fund_subsidy(&e, market_id, &creator, subsidy)?;
+        }
+        Ok(market_id)
+    }
 
-        write_market(&e, &market);
-        write_u32(&e, DataKey::NextMarketId, id + 1);
+    /// Replace a market's metadata (creator only, before the first bet)
+    pub fn update_metadata(e: Env, market_id: u32, metadata: MarketMetadata) -> Result<(), Error> {
+        require_initialized(&e)?;
+        let market = read_market(&e, market_id)?;
+        market.creator.require_auth();
 
-        e.events()
-            .publish((symbol_short!("Created"), id, creator), question);
-        Ok(id)
+        if market.resolved {
+            return Err(Error::MarketClosed);
+        }
+        if market.yes_pool + market.no_pool + market.committed > 0 {
+            return Err(Error::MetadataLocked);
+        }
+        validate_metadata(&metadata)?;
+
+        // Move the market to its new category listing
+        let previous = read_metadata(&e, market_id)?;
+        if previous.category != metadata.category {
+            remove_from_category(&e, &previous.category, market_id);
+            add_to_category(&e, &metadata.category, market_id);
+        }
+        write_metadata(&e, market_id, &metadata);
+
+        events::metadata_updated(&e, market_id, metadata.category);
+        Ok(())
     }
 
     /// Place a bet on a market
@@ -257,60 +970,95 @@ impl FarmersMarket {
     ) -> Result<(), Error> {
         require_initialized(&e)?;
         user.require_auth();
-        validate_amount(amount)?;
-
-        let mut market = read_market(&e, market_id)?;
+        place_bet(&e, &user, None, market_id, side_yes, amount)
+    }
 
-        if now(&e) >= market.close_ts {
-            return Err(Error::BetsClosed);
-        }
+    /// Bet on `owner`'s behalf with KALE `owner` approved `spender` to move.
+    /// The stake belongs to `owner`, who also receives any payout.
+    pub fn bet_from(
+        e: Env,
+        spender: Address,
+        owner: Address,
+        market_id: u32,
+        side_yes: bool,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        spender.require_auth();
+        place_bet(&e, &owner, Some(&spender), market_id, side_yes, amount)
+    }
 
-        // Transfer KALE from user to contract
-        let token_addr = read_address(&e, &DataKey::KaleToken)?;
-        let token_client = token_client(&e, &token_addr);
-        token_client.transfer(&user, &e.current_contract_address(), &amount);
+    /// Resolve a market (its assigned resolver, else a Resolver)
+    pub fn resolve(
+        e: Env,
+        resolver: Address,
+        market_id: u32,
+        outcome: Outcome,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
 
-        // Update pools and user stake
-        let mut stake = read_stake(&e, market_id, &user);
+        let market = read_market(&e, market_id)?;
+        require_market_resolver(&e, &market, &resolver)?;
+        staking::require_staked(&e, &resolver)?;
 
-        if side_yes {
-            market.yes_pool += amount;
-            stake.yes += amount;
-        } else {
-            market.no_pool += amount;
-            stake.no += amount;
+        if market.resolved {
+            return Err(Error::AlreadyResolved);
         }
 
-        write_market(&e, &market);
-        write_stake(&e, market_id, &user, &stake);
+        if now(&e) < market.resolution_ts {
+            return Err(Error::InvalidTime);
+        }
 
-        e.events()
-            .publish((symbol_short!("Bet"), market_id, user), (side_yes, amount));
+        finalize_market(&e, market, outcome)?;
+        staking::record_resolution(&e, &resolver, market_id);
         Ok(())
     }
 
-    /// Resolve a market (resolver only)
-    pub fn resolve(e: Env, market_id: u32, outcome: Outcome) -> Result<(), Error> {
+    /// Cancel a malformed, ambiguous or abusive market (Moderator only).
+    /// Works before or after close; the market settles as Invalid so every
+    /// staker is refunded through `claim`. The creation bond goes to the
+    /// treasury when `slash_bond` is set for abuse, and back to the creator
+    /// otherwise.
+    pub fn cancel_market(
+        e: Env,
+        moderator: Address,
+        market_id: u32,
+        reason: String,
+        slash_bond: bool,
+    ) -> Result<(), Error> {
         require_initialized(&e)?;
-        let resolver = read_address(&e, &DataKey::Resolver)?;
-        resolver.require_auth();
+        require_role(&e, &moderator, Role::Moderator)?;
 
         let mut market = read_market(&e, market_id)?;
-
         if market.resolved {
             return Err(Error::AlreadyResolved);
         }
 
-        if now(&e) < market.resolution_ts {
-            return Err(Error::InvalidTime);
-        }
-
         market.resolved = true;
-        market.outcome = outcome.clone();
+        market.outcome = Outcome::Invalid;
+        staking::release_market(&e, &market);
+        let bond = market.bond;
+        market.bond = 0;
         write_market(&e, &market);
-
-        e.events()
-            .publish((symbol_short!("Resolved"), market_id), outcome);
+        e.storage()
+            .persistent()
+            .set(&DataKey::CancelReason(market_id), &reason);
+
+        let slashed = if slash_bond { bond } else { 0 };
+        events::market_cancelled(&e, &market, reason, slashed);
+
+        if bond > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            let to = if slash_bond {
+                read_address(&e, &DataKey::Treasury)?
+            } else {
+                market.creator.clone()
+            };
+            token_client(&e, &token_addr).transfer(&e.current_contract_address(), &to, &bond);
+            if !slash_bond {
+                events::bond_returned(&e, &market, bond);
+            }
+        }
         Ok(())
     }
 
@@ -323,36 +1071,50 @@ impl FarmersMarket {
         if !market.resolved {
             return Err(Error::NotResolved);
         }
+        dispute::require_final(&e, market_id)?;
 
         let mut stake = read_stake(&e, market_id, &user);
         if stake.claimed {
             return Err(Error::NothingToClaim);
         }
+        // Shares sitting in an ask would miss the payout
+        if orderbook::open_asks(&e, market_id, &user) > 0 {
+            return Err(Error::OpenOrders);
+        }
 
         let token_addr = read_address(&e, &DataKey::KaleToken)?;
         let token_client = token_client(&e, &token_addr);
 
-        let payout = match market.outcome {
+        // Gross payout and the part of it that was the user's own stake
+        let (payout, staked) = match payout_outcome(&market) {
             Outcome::Invalid => {
                 // Refund original stake
-                stake.yes + stake.no
+                (stake.yes + stake.no, stake.yes + stake.no)
             }
             Outcome::Yes => {
-                if stake.yes > 0 && market.yes_pool > 0 {
-                    // Winner takes proportional share of total pool
+                if stake.yes > 0 && market.yes_weight > 0 {
+                    // Winner takes their weighted share of the total pool
                     let total_pool = market.yes_pool + market.no_pool;
-                    (total_pool * stake.yes) / market.yes_pool
+                    let share = subsidy::subsidy_share(&e, &market, stake.yes_weight);
+                    (
+                        (total_pool * stake.yes_weight) / market.yes_weight + share,
+                        stake.yes,
+                    )
                 } else {
-                    0
+                    (0, 0)
                 }
             }
             Outcome::No => {
-                if stake.no > 0 && market.no_pool > 0 {
-                    // Winner takes proportional share of total pool
+                if stake.no > 0 && market.no_weight > 0 {
+                    // Winner takes their weighted share of the total pool
                     let total_pool = market.yes_pool + market.no_pool;
-                    (total_pool * stake.no) / market.no_pool
+                    let share = subsidy::subsidy_share(&e, &market, stake.no_weight);
+                    (
+                        (total_pool * stake.no_weight) / market.no_weight + share,
+                        stake.no,
+                    )
                 } else {
-                    0
+                    (0, 0)
                 }
             }
         };
@@ -365,13 +1127,103 @@ impl FarmersMarket {
         stake.claimed = true;
         write_stake(&e, market_id, &user, &stake);
 
+        // The protocol fee only comes out of profit, never a refund. A late
+        // winner's weighted payout can fall short of their stake.
+        let fees = read_fee_config(&e);
+        let fee = (payout - staked).max(0) * fees.fee_bps as i128 / 10_000;
+        // Slashed resolver stake rides along, fee-free
+        let compensation = staking::compensation_share(
+            &e,
+            &market,
+            stake.yes,
+            stake.no,
+            stake.yes_weight,
+            stake.no_weight,
+        );
+        let payout = payout - fee + compensation;
+
         token_client.transfer(&e.current_contract_address(), &user, &payout);
 
-        e.events()
-            .publish((symbol_short!("Claimed"), market_id, user), payout);
+        if fee > 0 {
+            let to_lp = liquidity::accrue_fees(&e, market_id, fee, fees.lp_share_bps);
+            let to_bounty = (fee - to_lp) * fees.bounty_share_bps as i128 / 10_000;
+            keeper::add_to_bounty_pool(&e, to_bounty);
+            let to_treasury = fee - to_lp - to_bounty;
+            if to_treasury > 0 {
+                let treasury = read_address(&e, &DataKey::Treasury)?;
+                token_client.transfer(&e.current_contract_address(), &treasury, &to_treasury);
+            }
+            events::fee_collected(&e, market_id, user.clone(), fee, to_bounty, to_lp);
+        }
+
+        let won = payout_outcome(&market) != Outcome::Invalid;
+        seasons::record_claim(&e, market_id, &user, payout, won);
+
+        events::claimed(&e, &market, user, payout);
         Ok(payout)
     }
 
+    // -------------------------------
+    // Admin Functions
+    // -------------------------------
+
+    /// Set the address that receives pools nobody can win (Admin only)
+    pub fn set_treasury(e: Env, admin: Address, treasury: Address) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        write_address(&e, DataKey::Treasury, treasury.clone());
+
+        events::treasury_updated(&e, treasury);
+        Ok(())
+    }
+
+    /// Configure the creation bond, allowlist and rate limit (Admin only)
+    pub fn set_creation_policy(
+        e: Env,
+        admin: Address,
+        policy: CreationPolicy,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        validate_creation_policy(&policy)?;
+        e.storage()
+            .instance()
+            .set(&DataKey::CreationPolicy, &policy);
+
+        events::creation_policy_updated(&e, policy);
+        Ok(())
+    }
+
+    /// Set the bet limits used by markets created without their own (Admin only)
+    pub fn set_default_bet_limits(e: Env, admin: Address, limits: BetLimits) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        validate_bet_limits(&limits)?;
+        e.storage()
+            .instance()
+            .set(&DataKey::DefaultBetLimits, &limits);
+
+        events::default_bet_limits_updated(&e, limits);
+        Ok(())
+    }
+
+    /// Add or remove a creator from the allowlist (Moderator only)
+    pub fn set_creator_allowed(
+        e: Env,
+        moderator: Address,
+        creator: Address,
+        allowed: bool,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &moderator, Role::Moderator)?;
+        e.storage()
+            .persistent()
+            .set(&DataKey::CreatorAllowed(creator.clone()), &allowed);
+
+        events::creator_allowed(&e, creator, allowed);
+        Ok(())
+    }
+
     // -------------------------------
     // View Functions
     // -------------------------------
@@ -384,18 +1236,77 @@ impl FarmersMarket {
         read_stake(&e, market_id, &user)
     }
 
-    pub fn get_admin(e: Env) -> Result<Address, Error> {
-        read_address(&e, &DataKey::Admin)
+    pub fn get_metadata(e: Env, market_id: u32) -> Result<MarketMetadata, Error> {
+        read_metadata(&e, market_id)
     }
 
-    pub fn get_resolver(e: Env) -> Result<Address, Error> {
-        read_address(&e, &DataKey::Resolver)
+    /// Markets in a category, oldest first, `limit` entries at a time from
+    /// `start`. Entries left by markets moved to another category are
+    /// skipped, so a page can hold fewer than `limit` markets.
+    pub fn list_markets_by_category(
+        e: Env,
+        category: Category,
+        start: u32,
+        limit: u32,
+    ) -> Vec<Market> {
+        let end = category_count(&e, &category).min(start.saturating_add(limit));
+        let mut markets = Vec::new(&e);
+        for n in start..end {
+            let id: Option<u32> =
+                e.storage()
+                    .persistent()
+                    .get(&DataKey::Categories(CategoryKey::Entry(
+                        category.clone(),
+                        n,
+                    )));
+            if let Some(m) = id.and_then(|id| read_market(&e, id).ok()) {
+                markets.push_back(m);
+            }
+        }
+        markets
+    }
+
+    /// Entries in a category's listing, for paging with
+    /// `list_markets_by_category`
+    pub fn get_category_count(e: Env, category: Category) -> u32 {
+        category_count(&e, &category)
+    }
+
+    /// Reason recorded when the admin cancelled the market, if it was
+    pub fn get_cancel_reason(e: Env, market_id: u32) -> Option<String> {
+        e.storage()
+            .persistent()
+            .get(&DataKey::CancelReason(market_id))
     }
 
     pub fn get_kale_token(e: Env) -> Result<Address, Error> {
         read_address(&e, &DataKey::KaleToken)
     }
 
+    pub fn get_treasury(e: Env) -> Result<Address, Error> {
+        read_address(&e, &DataKey::Treasury)
+    }
+
+    pub fn get_reflector_oracle(e: Env) -> Result<Address, Error> {
+        read_address(&e, &DataKey::ReflectorOracle)
+    }
+
+    pub fn get_creation_policy(e: Env) -> CreationPolicy {
+        read_creation_policy(&e)
+    }
+
+    pub fn get_fee_config(e: Env) -> FeeConfig {
+        read_fee_config(&e)
+    }
+
+    pub fn get_default_bet_limits(e: Env) -> BetLimits {
+        read_default_bet_limits(&e)
+    }
+
+    pub fn is_creator_allowed(e: Env, creator: Address) -> bool {
+        is_allowed_creator(&e, &creator)
+    }
+
     /// Get market odds (yes percentage * 100)
     pub fn get_odds(e: Env, market_id: u32) -> Result<u32, Error> {
         let market = read_market(&e, market_id)?;
@@ -470,17 +1381,19 @@ impl FarmersMarket {
 
     /// Check if BTC price is above target (for market resolution)
     pub fn is_btc_above_price(e: Env, target_price_usd: i128) -> Result<bool, Error> {
-        let btc_price = Self::get_btc_price(e)?;
-        // Reflector uses 14 decimals for USD prices
-        let target_with_decimals = target_price_usd * 100_000_000_000_000i128;
+        let btc_price = Self::get_btc_price(e.clone())?;
+        // Reflector quotes USD prices with `decimals()` places (14 on mainnet/testnet)
+        let (decimals, _, _) = Self::get_oracle_info(e)?;
+        let target_with_decimals = target_price_usd * 10i128.pow(decimals);
         Ok(btc_price.price >= target_with_decimals)
     }
 
     /// Check if ETH price is above target (for market resolution)
     pub fn is_eth_above_price(e: Env, target_price_usd: i128) -> Result<bool, Error> {
-        let eth_price = Self::get_eth_price(e)?;
-        // Reflector uses 14 decimals for USD prices
-        let target_with_decimals = target_price_usd * 100_000_000_000_000i128;
+        let eth_price = Self::get_eth_price(e.clone())?;
+        // Reflector quotes USD prices with `decimals()` places (14 on mainnet/testnet)
+        let (decimals, _, _) = Self::get_oracle_info(e)?;
+        let target_with_decimals = target_price_usd * 10i128.pow(decimals);
         Ok(eth_price.price >= target_with_decimals)
     }
 
@@ -546,3 +1459,4 @@ impl FarmersMarket {
 }
 
 mod test;
+mod test_invariants;
@@ -0,0 +1,217 @@
+//! Liquidity provider pools.
+//!
+//! LPs deposit KALE into a market before `close_ts` and get LP shares, one
+//! per stroop deposited. Each deposit is split evenly across the YES and NO
+//! pools, so a fresh market has depth on both sides for its first bettor.
+//! The pool's position settles like any stake, and it also earns
+//! `lp_share_bps` of the protocol fee on every winner's claim.
+//!
+//! Liquidity is locked until the market resolves, since pulling it earlier
+//! would change payouts bettors already priced in. After resolution each LP
+//! withdraws their share of the position's payout plus the fees collected
+//! so far; an `Invalid` market returns deposits in full. Fees from claims
+//! made after an LP withdraws go to the LPs still in the pool.
+use soroban_sdk::{contractimpl, contracttype, Address, Env};
+
+use crate::dispute::require_final;
+use crate::staking::compensation_share;
+use crate::subsidy::subsidy_share;
+use crate::weighting::weigh;
+use crate::{
+    events, now, payout_outcome, read_address, read_market, require_initialized,
+    require_not_paused, token_client, validate_amount, write_market, DataKey, Error, FarmersMarket,
+    FarmersMarketArgs, FarmersMarketClient, Market, Outcome,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct LiquidityPool {
+    pub yes: i128,        // LP liquidity in the YES pool
+    pub no: i128,         // LP liquidity in the NO pool
+    pub yes_weight: i128, // time weight of that liquidity
+    pub no_weight: i128,
+    pub shares: i128,  // LP shares outstanding
+    pub fees: i128,    // fees collected and not yet withdrawn
+    pub settled: bool, // yes/no have been swapped for the position's payout
+    pub value: i128,   // unwithdrawn payout once settled
+}
+
+fn read_pool(e: &Env, market_id: u32) -> LiquidityPool {
+    e.storage()
+        .persistent()
+        .get(&DataKey::LiquidityPool(market_id))
+        .unwrap_or(LiquidityPool {
+            yes: 0,
+            no: 0,
+            yes_weight: 0,
+            no_weight: 0,
+            shares: 0,
+            fees: 0,
+            settled: false,
+            value: 0,
+        })
+}
+
+fn write_pool(e: &Env, market_id: u32, pool: &LiquidityPool) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::LiquidityPool(market_id), pool);
+}
+
+fn read_lp_shares(e: &Env, market_id: u32, provider: &Address) -> i128 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::LpShares(market_id, provider.clone()))
+        .unwrap_or(0)
+}
+
+/// What the pool's YES/NO liquidity pays out, using the same split as `claim`
+fn position_payout(e: &Env, market: &Market, pool: &LiquidityPool) -> i128 {
+    let total = market.yes_pool + market.no_pool;
+    let compensation = compensation_share(
+        e,
+        market,
+        pool.yes,
+        pool.no,
+        pool.yes_weight,
+        pool.no_weight,
+    );
+    compensation
+        + match payout_outcome(market) {
+            Outcome::Invalid => pool.yes + pool.no,
+            Outcome::Yes if market.yes_weight > 0 => {
+                total * pool.yes_weight / market.yes_weight
+                    + subsidy_share(e, market, pool.yes_weight)
+            }
+            Outcome::No if market.no_weight > 0 => {
+                total * pool.no_weight / market.no_weight + subsidy_share(e, market, pool.no_weight)
+            }
+            _ => 0,
+        }
+}
+
+/// Credit a claim's LP fee cut to the market's pool. Returns how much was
+/// taken, which is nothing once every LP has left.
+pub(crate) fn accrue_fees(e: &Env, market_id: u32, fee: i128, lp_share_bps: u32) -> i128 {
+    let mut pool = read_pool(e, market_id);
+    if pool.shares == 0 {
+        return 0;
+    }
+    let cut = fee * lp_share_bps as i128 / 10_000;
+    if cut > 0 {
+        pool.fees += cut;
+        write_pool(e, market_id, &pool);
+    }
+    cut
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Deposit KALE as liquidity split across both sides of a market
+    pub fn add_liquidity(
+        e: Env,
+        provider: Address,
+        market_id: u32,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        provider.require_auth();
+        require_not_paused(&e)?;
+        validate_amount(amount)?;
+
+        let mut market = read_market(&e, market_id)?;
+        if market.resolved {
+            return Err(Error::MarketClosed);
+        }
+        if now(&e) >= market.close_ts {
+            return Err(Error::BetsClosed);
+        }
+        let max_pool = market.limits.max_pool;
+        if max_pool > 0 && market.yes_pool + market.no_pool + market.committed + amount > max_pool {
+            return Err(Error::PoolCapExceeded);
+        }
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&provider, &e.current_contract_address(), &amount);
+
+        let to_yes = amount / 2;
+        let to_no = amount - to_yes;
+        // Liquidity is weighted like a bet placed at the same time
+        let yes_weight = weigh(&e, &market, to_yes);
+        let no_weight = weigh(&e, &market, to_no);
+        market.yes_pool += to_yes;
+        market.no_pool += to_no;
+        market.yes_weight += yes_weight;
+        market.no_weight += no_weight;
+        write_market(&e, &market);
+
+        let mut pool = read_pool(&e, market_id);
+        pool.yes += to_yes;
+        pool.no += to_no;
+        pool.yes_weight += yes_weight;
+        pool.no_weight += no_weight;
+        pool.shares += amount;
+        write_pool(&e, market_id, &pool);
+
+        let shares = read_lp_shares(&e, market_id, &provider) + amount;
+        e.storage()
+            .persistent()
+            .set(&DataKey::LpShares(market_id, provider.clone()), &shares);
+
+        events::liquidity_added(&e, &market, provider, amount, shares);
+        Ok(amount)
+    }
+
+    /// Withdraw all of a provider's liquidity once the market has resolved
+    pub fn remove_liquidity(e: Env, provider: Address, market_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        provider.require_auth();
+
+        let market = read_market(&e, market_id)?;
+        if !market.resolved {
+            return Err(Error::NotResolved);
+        }
+        require_final(&e, market_id)?;
+        let shares = read_lp_shares(&e, market_id, &provider);
+        if shares == 0 {
+            return Err(Error::NothingToClaim);
+        }
+
+        let mut pool = read_pool(&e, market_id);
+        if !pool.settled {
+            pool.value = position_payout(&e, &market, &pool);
+            pool.settled = true;
+        }
+
+        let from_value = pool.value * shares / pool.shares;
+        let from_fees = pool.fees * shares / pool.shares;
+        pool.value -= from_value;
+        pool.fees -= from_fees;
+        pool.shares -= shares;
+        write_pool(&e, market_id, &pool);
+        e.storage()
+            .persistent()
+            .remove(&DataKey::LpShares(market_id, provider.clone()));
+
+        let payout = from_value + from_fees;
+        if payout > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            token_client(&e, &token_addr).transfer(
+                &e.current_contract_address(),
+                &provider,
+                &payout,
+            );
+        }
+
+        events::liquidity_removed(&e, market_id, provider, shares, payout);
+        Ok(payout)
+    }
+
+    pub fn get_liquidity_pool(e: Env, market_id: u32) -> LiquidityPool {
+        read_pool(&e, market_id)
+    }
+
+    pub fn get_lp_shares(e: Env, market_id: u32, provider: Address) -> i128 {
+        read_lp_shares(&e, market_id, &provider)
+    }
+}
@@ -0,0 +1,207 @@
+//! Scriptable stand-in for the Reflector oracle, for tests only.
+//!
+//! Prices are set per asset as a series of `PriceData` records (oldest first)
+//! and served back through the full `reflector::Contract` interface, so every
+//! oracle path in the market contract can be exercised deterministically.
+//! Assets without a series behave like assets Reflector doesn't quote.
+use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Symbol, Vec};
+
+use crate::reflector::{Asset, Contract, PriceData};
+
+const DEFAULT_DECIMALS: u32 = 14;
+const DEFAULT_RESOLUTION: u32 = 300;
+const DEFAULT_PERIOD: u64 = 86_400;
+
+#[derive(Clone)]
+#[contracttype]
+enum MockKey {
+    Decimals,
+    Resolution,
+    LastTimestamp,
+    Assets,
+    Prices(Asset),
+}
+
+#[contract]
+pub struct MockReflector;
+
+fn read_prices(e: &Env, asset: &Asset) -> Vec<PriceData> {
+    e.storage()
+        .instance()
+        .get(&MockKey::Prices(asset.clone()))
+        .unwrap_or(Vec::new(e))
+}
+
+/// Most recent `records` entries, newest first (Reflector's ordering)
+fn last_records(e: &Env, asset: &Asset, records: u32) -> Option<Vec<PriceData>> {
+    let series = read_prices(e, asset);
+    if series.is_empty() || records == 0 {
+        return None;
+    }
+    let mut out = Vec::new(e);
+    let mut i = series.len();
+    while i > 0 && out.len() < records {
+        i -= 1;
+        out.push_back(series.get_unchecked(i));
+    }
+    Some(out)
+}
+
+fn cross(e: &Env, base: &PriceData, quote: &PriceData) -> Option<PriceData> {
+    if quote.price == 0 {
+        return None;
+    }
+    let scale = 10i128.pow(MockReflector::decimals(e.clone()));
+    Some(PriceData {
+        price: base.price * scale / quote.price,
+        timestamp: base.timestamp.min(quote.timestamp),
+    })
+}
+
+fn average(prices: &Vec<PriceData>) -> Option<i128> {
+    if prices.is_empty() {
+        return None;
+    }
+    let sum: i128 = prices.iter().map(|p| p.price).sum();
+    Some(sum / prices.len() as i128)
+}
+
+#[contractimpl]
+impl MockReflector {
+    /// Override the reported decimals and tick resolution
+    pub fn configure(e: Env, decimals: u32, resolution: u32) {
+        e.storage().instance().set(&MockKey::Decimals, &decimals);
+        e.storage()
+            .instance()
+            .set(&MockKey::Resolution, &resolution);
+    }
+
+    /// Script the price series for an asset, oldest record first
+    pub fn set_prices(e: Env, asset: Asset, prices: Vec<PriceData>) {
+        let mut assets = Self::assets(e.clone());
+        if !assets.contains(&asset) {
+            assets.push_back(asset.clone());
+            e.storage().instance().set(&MockKey::Assets, &assets);
+        }
+        e.storage().instance().set(&MockKey::Prices(asset), &prices);
+    }
+
+    /// Stop quoting an asset, as if Reflector had delisted it
+    pub fn remove_asset(e: Env, asset: Asset) {
+        let mut assets = Self::assets(e.clone());
+        if let Some(i) = assets.first_index_of(&asset) {
+            assets.remove(i);
+            e.storage().instance().set(&MockKey::Assets, &assets);
+        }
+        e.storage().instance().remove(&MockKey::Prices(asset));
+    }
+
+    /// Pin the feed's last update time, e.g. far behind the ledger for a stale feed
+    pub fn set_last_timestamp(e: Env, timestamp: u64) {
+        e.storage()
+            .instance()
+            .set(&MockKey::LastTimestamp, &timestamp);
+    }
+}
+
+#[contractimpl]
+impl Contract for MockReflector {
+    fn base(e: Env) -> Asset {
+        Asset::Other(Symbol::new(&e, "USD"))
+    }
+
+    fn assets(e: Env) -> Vec<Asset> {
+        e.storage()
+            .instance()
+            .get(&MockKey::Assets)
+            .unwrap_or(Vec::new(&e))
+    }
+
+    fn decimals(e: Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&MockKey::Decimals)
+            .unwrap_or(DEFAULT_DECIMALS)
+    }
+
+    fn price(e: Env, asset: Asset, timestamp: u64) -> Option<PriceData> {
+        read_prices(&e, &asset)
+            .iter()
+            .find(|p| p.timestamp == timestamp)
+    }
+
+    fn lastprice(e: Env, asset: Asset) -> Option<PriceData> {
+        read_prices(&e, &asset).last()
+    }
+
+    fn prices(e: Env, asset: Asset, records: u32) -> Option<Vec<PriceData>> {
+        last_records(&e, &asset, records)
+    }
+
+    fn x_last_price(e: Env, base_asset: Asset, quote_asset: Asset) -> Option<PriceData> {
+        let base = Self::lastprice(e.clone(), base_asset)?;
+        let quote = Self::lastprice(e.clone(), quote_asset)?;
+        cross(&e, &base, &quote)
+    }
+
+    fn x_price(e: Env, base_asset: Asset, quote_asset: Asset, timestamp: u64) -> Option<PriceData> {
+        let base = Self::price(e.clone(), base_asset, timestamp)?;
+        let quote = Self::price(e.clone(), quote_asset, timestamp)?;
+        cross(&e, &base, &quote)
+    }
+
+    fn x_prices(
+        e: Env,
+        base_asset: Asset,
+        quote_asset: Asset,
+        records: u32,
+    ) -> Option<Vec<PriceData>> {
+        let base = last_records(&e, &base_asset, records)?;
+        let quote = last_records(&e, &quote_asset, records)?;
+        let mut out = Vec::new(&e);
+        for (b, q) in base.iter().zip(quote.iter()) {
+            out.push_back(cross(&e, &b, &q)?);
+        }
+        Some(out)
+    }
+
+    fn twap(e: Env, asset: Asset, records: u32) -> Option<i128> {
+        average(&last_records(&e, &asset, records)?)
+    }
+
+    fn x_twap(e: Env, base_asset: Asset, quote_asset: Asset, records: u32) -> Option<i128> {
+        average(&Self::x_prices(e, base_asset, quote_asset, records)?)
+    }
+
+    fn resolution(e: Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&MockKey::Resolution)
+            .unwrap_or(DEFAULT_RESOLUTION)
+    }
+
+    fn period(_e: Env) -> Option<u64> {
+        Some(DEFAULT_PERIOD)
+    }
+
+    fn last_timestamp(e: Env) -> u64 {
+        if let Some(ts) = e.storage().instance().get(&MockKey::LastTimestamp) {
+            return ts;
+        }
+        // Otherwise the newest record across all quoted assets
+        Self::assets(e.clone())
+            .iter()
+            .filter_map(|a| read_prices(&e, &a).last())
+            .map(|p| p.timestamp)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn version(_e: Env) -> u32 {
+        1
+    }
+
+    fn admin(_e: Env) -> Option<Address> {
+        None
+    }
+}
@@ -0,0 +1,398 @@
+//! Limit order book for YES shares.
+//!
+//! Traders post bids (KALE for YES shares) and asks (YES shares for KALE)
+//! until a market's `close_ts`. Bids escrow their KALE and asks escrow their
+//! shares, so a fill only moves what is already held. Orders match by
+//! price-time priority at the resting (earlier) order's price, and may fill
+//! partially. Bought shares land in the buyer's `Stake` and pay out through
+//! `claim` like any other position, and count towards the buyer's
+//! `max_user_stake` when filled. Fills cost the buyer rounded up to the
+//! stroop, so shares never change hands for nothing. Orders are at least the
+//! market's `min_bet`, a remainder below it is cancelled rather than left
+//! resting, and a full side of the book evicts its worst-priced order for a
+//! better one, so dust orders can't lock the book.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Vec};
+
+use crate::commit_reveal;
+use crate::seasons;
+use crate::weighting::share_weight;
+use crate::{
+    events, now, read_address, read_market, read_stake, require_initialized, require_not_paused,
+    token_client, validate_amount, write_stake, DataKey, Error, FarmersMarket, FarmersMarketArgs,
+    FarmersMarketClient, Market, Stake,
+};
+
+// Prices are KALE stroops per share, scaled by PRICE_SCALE (6_500 = 0.65)
+pub const PRICE_SCALE: i128 = 10_000;
+pub const MAX_ORDERS_PER_SIDE: u32 = 50;
+// Fills attempted when an order is placed; the rest wait for match_orders
+pub const MAX_FILLS_PER_CALL: u32 = 10;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum OrderSide {
+    Bid, // buy YES shares with KALE
+    Ask, // sell YES shares for KALE
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Order {
+    pub id: u32,
+    pub market_id: u32,
+    pub trader: Address,
+    pub side: OrderSide,
+    pub price: i128,
+    pub amount: i128, // shares still unfilled
+    pub escrow: i128, // KALE still held for a bid
+    pub weight: i128, // stake weight of the shares held for an ask
+    pub open: bool,
+}
+
+/// KALE paid for `amount` shares at `price`, rounded up
+fn cost(amount: i128, price: i128) -> i128 {
+    (amount * price + PRICE_SCALE - 1) / PRICE_SCALE
+}
+
+fn read_order(e: &Env, id: u32) -> Result<Order, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Order(id))
+        .ok_or(Error::OrderNotFound)
+}
+
+fn write_order(e: &Env, order: &Order) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::Order(order.id), order);
+}
+
+fn read_book(e: &Env, market_id: u32, side: OrderSide) -> Vec<u32> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::OrderBook(market_id, side))
+        .unwrap_or(Vec::new(e))
+}
+
+fn write_book(e: &Env, market_id: u32, side: OrderSide, ids: &Vec<u32>) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::OrderBook(market_id, side), ids);
+}
+
+fn adjust_open_asks(e: &Env, market_id: u32, trader: &Address, delta: i32) {
+    let key = DataKey::OpenAsks(market_id, trader.clone());
+    let count: u32 = e.storage().persistent().get(&key).unwrap_or(0);
+    let count = count.saturating_add_signed(delta);
+    if count == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &count);
+    }
+}
+
+/// Asks a user still has resting on a market. Their shares are in escrow,
+/// so `claim` waits until these are cancelled or filled.
+pub(crate) fn open_asks(e: &Env, market_id: u32, trader: &Address) -> u32 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::OpenAsks(market_id, trader.clone()))
+        .unwrap_or(0)
+}
+
+/// Whether `a` goes ahead of `b` on the same side of the book
+fn ahead_of(side: OrderSide, a: &Order, b: &Order) -> bool {
+    match side {
+        OrderSide::Bid => a.price > b.price,
+        OrderSide::Ask => a.price < b.price,
+    }
+}
+
+/// Insert behind every order at the same or a better price. A full side
+/// drops its worst order to make room, if the new one is better.
+fn insert_order(e: &Env, order: &Order) -> Result<(), Error> {
+    let mut ids = read_book(e, order.market_id, order.side);
+    if ids.len() >= MAX_ORDERS_PER_SIDE {
+        let mut worst = read_order(e, ids.last_unchecked())?;
+        if !ahead_of(order.side, order, &worst) {
+            return Err(Error::OrderBookFull);
+        }
+        ids.pop_back();
+        close_order(e, &mut worst)?;
+    }
+    let mut pos = ids.len();
+    for (i, id) in ids.iter().enumerate() {
+        if ahead_of(order.side, order, &read_order(e, id)?) {
+            pos = i as u32;
+            break;
+        }
+    }
+    ids.insert(pos, order.id);
+    write_book(e, order.market_id, order.side, &ids);
+    Ok(())
+}
+
+fn remove_from_book(e: &Env, order: &Order) {
+    let mut ids = read_book(e, order.market_id, order.side);
+    if let Some(i) = ids.first_index_of(order.id) {
+        ids.remove(i);
+        write_book(e, order.market_id, order.side, &ids);
+    }
+}
+
+/// Hand a closed order's leftover escrow back to its trader
+fn release_escrow(e: &Env, order: &mut Order) -> Result<(), Error> {
+    match order.side {
+        OrderSide::Bid => {
+            if order.escrow > 0 {
+                let token_addr = read_address(e, &DataKey::KaleToken)?;
+                token_client(e, &token_addr).transfer(
+                    &e.current_contract_address(),
+                    &order.trader,
+                    &order.escrow,
+                );
+            }
+        }
+        OrderSide::Ask => {
+            if order.amount > 0 {
+                let mut stake = read_stake(e, order.market_id, &order.trader);
+                stake.yes += order.amount;
+                stake.yes_weight += order.weight;
+                write_stake(e, order.market_id, &order.trader, &stake);
+            }
+            seasons::release_ask_cost(e, order.market_id, order.id, &order.trader);
+            adjust_open_asks(e, order.market_id, &order.trader, -1);
+        }
+    }
+    order.escrow = 0;
+    order.weight = 0;
+    order.open = false;
+    Ok(())
+}
+
+/// Take an order off the market and refund its escrow
+fn close_order(e: &Env, order: &mut Order) -> Result<(), Error> {
+    let refund = order.escrow;
+    remove_from_book(e, order);
+    release_escrow(e, order)?;
+    write_order(e, order);
+
+    let stake = read_stake(e, order.market_id, &order.trader);
+    events::order_cancelled(e, order, refund, &stake);
+    Ok(())
+}
+
+/// Take a filled order off the book, and cancel one whose remainder fell
+/// below the market's minimum so dust never rests
+fn retire_if_done(e: &Env, order: &mut Order, min_bet: i128) -> Result<(), Error> {
+    if order.amount == 0 {
+        remove_from_book(e, order);
+        // A bid filled below its limit gets the difference back
+        release_escrow(e, order)?;
+    } else if order.amount < min_bet {
+        close_order(e, order)?;
+    }
+    Ok(())
+}
+
+/// Room left under the market's cap for `trader` to take on more stake
+fn stake_room(e: &Env, market: &Market, trader: &Address, stake: &Stake) -> i128 {
+    let cap = market.limits.max_user_stake;
+    if cap == 0 {
+        return i128::MAX;
+    }
+    let sealed = commit_reveal::pending_commitment(e, market.id, trader);
+    (cap - stake.yes - stake.no - sealed).max(0)
+}
+
+/// Cross the best bid and ask while they overlap, up to `max_fills` times
+fn match_book(e: &Env, market: &Market, max_fills: u32) -> Result<u32, Error> {
+    let token_addr = read_address(e, &DataKey::KaleToken)?;
+    let token = token_client(e, &token_addr);
+    let mut fills = 0;
+
+    while fills < max_fills {
+        let bids = read_book(e, market.id, OrderSide::Bid);
+        let asks = read_book(e, market.id, OrderSide::Ask);
+        let (Some(bid_id), Some(ask_id)) = (bids.first(), asks.first()) else {
+            break;
+        };
+        let mut bid = read_order(e, bid_id)?;
+        let mut ask = read_order(e, ask_id)?;
+        if bid.price < ask.price {
+            break;
+        }
+        let mut buyer = read_stake(e, market.id, &bid.trader);
+
+        // The order that was resting first sets the price
+        let price = if bid.id < ask.id {
+            bid.price
+        } else {
+            ask.price
+        };
+        // The buyer may have reached the cap since bidding, and rounding
+        // each fill up can leave the escrow short of the last shares
+        let room = stake_room(e, market, &bid.trader, &buyer);
+        let affordable = bid.escrow.saturating_mul(PRICE_SCALE) / price;
+        let amount = bid.amount.min(ask.amount).min(room).min(affordable);
+        if amount == 0 || amount < market.limits.min_bet {
+            close_order(e, &mut bid)?;
+            fills += 1;
+            continue;
+        }
+        let paid = cost(amount, price);
+        // Shares keep the weight they were staked with
+        let weight = if amount == ask.amount {
+            ask.weight
+        } else {
+            ask.weight * amount / ask.amount
+        };
+
+        seasons::record_fill(e, &bid, &ask, amount, paid);
+        bid.amount -= amount;
+        bid.escrow -= paid;
+        ask.amount -= amount;
+        ask.weight -= weight;
+
+        buyer.yes += amount;
+        buyer.yes_weight += weight;
+        write_stake(e, market.id, &bid.trader, &buyer);
+        token.transfer(&e.current_contract_address(), &ask.trader, &paid);
+
+        events::order_filled(e, &bid, &ask, price, amount, &buyer);
+
+        retire_if_done(e, &mut bid, market.limits.min_bet)?;
+        retire_if_done(e, &mut ask, market.limits.min_bet)?;
+        write_order(e, &bid);
+        write_order(e, &ask);
+        fills += 1;
+    }
+    Ok(fills)
+}
+
+fn open_for_trading(e: &Env, market_id: u32) -> Result<Market, Error> {
+    let market = read_market(e, market_id)?;
+    if market.resolved {
+        return Err(Error::MarketClosed);
+    }
+    if now(e) >= market.close_ts {
+        return Err(Error::BetsClosed);
+    }
+    Ok(market)
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Post a limit order on a market's YES shares. It matches against the
+    /// book right away and any unfilled remainder rests.
+    pub fn place_order(
+        e: Env,
+        trader: Address,
+        market_id: u32,
+        side: OrderSide,
+        price: i128,
+        amount: i128,
+    ) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        trader.require_auth();
+        require_not_paused(&e)?;
+        validate_amount(price)?;
+        validate_amount(amount)?;
+        let market = open_for_trading(&e, market_id)?;
+        if amount < market.limits.min_bet {
+            return Err(Error::BetTooSmall);
+        }
+
+        let mut escrow = 0;
+        let mut weight = 0;
+        let mut stake = read_stake(&e, market_id, &trader);
+        let held = stake.yes;
+        match side {
+            OrderSide::Bid => {
+                if amount > stake_room(&e, &market, &trader, &stake) {
+                    return Err(Error::UserStakeCapExceeded);
+                }
+                escrow = cost(amount, price);
+                validate_amount(escrow)?;
+                let token_addr = read_address(&e, &DataKey::KaleToken)?;
+                token_client(&e, &token_addr).transfer(
+                    &trader,
+                    &e.current_contract_address(),
+                    &escrow,
+                );
+            }
+            OrderSide::Ask => {
+                if stake.yes < amount {
+                    return Err(Error::InsufficientShares);
+                }
+                weight = share_weight(&stake, true, amount);
+                stake.yes -= amount;
+                stake.yes_weight -= weight;
+                write_stake(&e, market_id, &trader, &stake);
+                adjust_open_asks(&e, market_id, &trader, 1);
+            }
+        }
+
+        let id: u32 = e
+            .storage()
+            .instance()
+            .get(&DataKey::NextOrderId)
+            .unwrap_or(0);
+        e.storage().instance().set(&DataKey::NextOrderId, &(id + 1));
+
+        let order = Order {
+            id,
+            market_id,
+            trader,
+            side,
+            price,
+            amount,
+            escrow,
+            weight,
+            open: true,
+        };
+        insert_order(&e, &order)?;
+        write_order(&e, &order);
+        if side == OrderSide::Ask {
+            seasons::escrow_ask_cost(&e, market_id, id, &order.trader, amount, held);
+        }
+        events::order_placed(&e, &order, &stake);
+
+        match_book(&e, &market, MAX_FILLS_PER_CALL)?;
+        Ok(id)
+    }
+
+    /// Withdraw an open order and get its remaining escrow back. Allowed at
+    /// any time, including after the market closes.
+    pub fn cancel_order(e: Env, order_id: u32) -> Result<(), Error> {
+        require_initialized(&e)?;
+        let mut order = read_order(&e, order_id)?;
+        order.trader.require_auth();
+        if !order.open {
+            return Err(Error::OrderNotFound);
+        }
+        close_order(&e, &mut order)
+    }
+
+    /// Cross any overlapping orders left after placement hit its fill limit
+    pub fn match_orders(e: Env, market_id: u32, max_fills: u32) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        let market = open_for_trading(&e, market_id)?;
+        match_book(&e, &market, max_fills)
+    }
+
+    pub fn get_order(e: Env, order_id: u32) -> Result<Order, Error> {
+        read_order(&e, order_id)
+    }
+
+    /// One side of a market's book, best price first
+    pub fn get_order_book(e: Env, market_id: u32, side: OrderSide) -> Vec<Order> {
+        let mut orders = Vec::new(&e);
+        for id in read_book(&e, market_id, side).iter() {
+            if let Ok(order) = read_order(&e, id) {
+                orders.push_back(order);
+            }
+        }
+        orders
+    }
+}
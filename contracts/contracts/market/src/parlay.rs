@@ -0,0 +1,304 @@
+//! Parlay tickets: one stake riding on several markets at once.
+//!
+//! Tickets are backed by a house liquidity pool the admin funds. Each leg's
+//! odds are locked from the market's pools when the ticket is bought, and the
+//! house reserves the ticket's full potential profit up front, so a winning
+//! ticket can always be paid. A ticket settles once every leg has resolved;
+//! a leg that resolves `Invalid` is dropped from the multiplier.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Vec};
+
+use crate::dispute::require_final;
+use crate::{
+    events, now, payout_outcome, read_address, read_market, require_initialized,
+    require_not_paused, require_role, token_client, validate_amount, DataKey, Error, FarmersMarket,
+    FarmersMarketArgs, FarmersMarketClient, Outcome, Role,
+};
+
+pub const MIN_PARLAY_LEGS: u32 = 2;
+pub const MAX_PARLAY_LEGS: u32 = 5;
+
+// Leg odds are fixed-point multipliers, 10_000 = 1x
+pub const ODDS_SCALE: i128 = 10_000;
+// Longest odds a single leg may be priced at
+pub const MAX_LEG_ODDS: i128 = 10 * ODDS_SCALE;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlayPick {
+    pub market_id: u32,
+    pub side_yes: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlayLeg {
+    pub market_id: u32,
+    pub side_yes: bool,
+    pub odds: i128, // total pool / side pool at purchase, scaled by ODDS_SCALE
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlayTicket {
+    pub id: u32,
+    pub owner: Address,
+    pub legs: Vec<ParlayLeg>,
+    pub stake: i128,
+    pub potential_payout: i128, // payout if every leg wins
+    pub settled: bool,
+    pub payout: i128, // what settlement actually paid
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ParlayHouse {
+    pub balance: i128,  // KALE the house owns
+    pub reserved: i128, // potential profit owed to open tickets
+}
+
+fn read_house(e: &Env) -> ParlayHouse {
+    e.storage()
+        .instance()
+        .get(&DataKey::ParlayHouse)
+        .unwrap_or(ParlayHouse {
+            balance: 0,
+            reserved: 0,
+        })
+}
+
+fn write_house(e: &Env, house: &ParlayHouse) {
+    e.storage().instance().set(&DataKey::ParlayHouse, house);
+}
+
+fn read_ticket(e: &Env, id: u32) -> Result<ParlayTicket, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Parlay(id))
+        .ok_or(Error::ParlayNotFound)
+}
+
+fn write_ticket(e: &Env, ticket: &ParlayTicket) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::Parlay(ticket.id), ticket);
+}
+
+/// Lock each pick's odds from its market's current pools. A leg is only
+/// priced off a side holding at least the ticket's stake and at no more than
+/// `MAX_LEG_ODDS`, so a dust bet on a thin side can't buy a ticket at
+/// runaway odds.
+fn price_legs(e: &Env, picks: &Vec<ParlayPick>, stake: i128) -> Result<Vec<ParlayLeg>, Error> {
+    if picks.len() < MIN_PARLAY_LEGS || picks.len() > MAX_PARLAY_LEGS {
+        return Err(Error::InvalidParlay);
+    }
+
+    let mut legs = Vec::new(e);
+    for pick in picks.iter() {
+        if legs
+            .iter()
+            .any(|leg: ParlayLeg| leg.market_id == pick.market_id)
+        {
+            return Err(Error::InvalidParlay);
+        }
+
+        let m = read_market(e, pick.market_id)?;
+        if m.resolved {
+            return Err(Error::MarketClosed);
+        }
+        if now(e) >= m.close_ts {
+            return Err(Error::BetsClosed);
+        }
+
+        let side_pool = if pick.side_yes { m.yes_pool } else { m.no_pool };
+        if side_pool == 0 || side_pool < stake {
+            return Err(Error::ParlayLegUnpriced);
+        }
+        let odds = (m.yes_pool + m.no_pool) * ODDS_SCALE / side_pool;
+        if odds > MAX_LEG_ODDS {
+            return Err(Error::ParlayLegUnpriced);
+        }
+        legs.push_back(ParlayLeg {
+            market_id: pick.market_id,
+            side_yes: pick.side_yes,
+            odds,
+        });
+    }
+    Ok(legs)
+}
+
+/// Stake multiplied through the odds of the given legs
+fn parlay_payout(stake: i128, legs: &Vec<ParlayLeg>) -> Result<i128, Error> {
+    let mut payout = stake;
+    for leg in legs.iter() {
+        payout = payout.checked_mul(leg.odds).ok_or(Error::InvalidAmount)? / ODDS_SCALE;
+    }
+    Ok(payout)
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Quote what a parlay would pay if every leg wins at today's odds
+    pub fn quote_parlay(e: Env, picks: Vec<ParlayPick>, stake: i128) -> Result<i128, Error> {
+        validate_amount(stake)?;
+        parlay_payout(stake, &price_legs(&e, &picks, stake)?)
+    }
+
+    /// Buy a parlay ticket. `min_payout` guards against odds moving between
+    /// quote and purchase.
+    pub fn place_parlay(
+        e: Env,
+        user: Address,
+        picks: Vec<ParlayPick>,
+        stake: i128,
+        min_payout: i128,
+    ) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        user.require_auth();
+        require_not_paused(&e)?;
+        validate_amount(stake)?;
+
+        let legs = price_legs(&e, &picks, stake)?;
+        let potential_payout = parlay_payout(stake, &legs)?;
+        if potential_payout < min_payout {
+            return Err(Error::ParlaySlippage);
+        }
+
+        // The house must be able to cover the ticket winning outright
+        let mut house = read_house(&e);
+        let profit = potential_payout - stake;
+        if house.balance - house.reserved < profit {
+            return Err(Error::InsufficientHouseLiquidity);
+        }
+        house.reserved += profit;
+        write_house(&e, &house);
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&user, &e.current_contract_address(), &stake);
+
+        let id: u32 = e
+            .storage()
+            .instance()
+            .get(&DataKey::NextParlayId)
+            .unwrap_or(0);
+        e.storage()
+            .instance()
+            .set(&DataKey::NextParlayId, &(id + 1));
+
+        let ticket = ParlayTicket {
+            id,
+            owner: user,
+            legs,
+            stake,
+            potential_payout,
+            settled: false,
+            payout: 0,
+        };
+        write_ticket(&e, &ticket);
+
+        events::parlay_placed(&e, &ticket);
+        Ok(id)
+    }
+
+    /// Settle a ticket once all of its legs have resolved. Anyone can call
+    /// this; winnings always go to the ticket owner.
+    pub fn settle_parlay(e: Env, ticket_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+
+        let mut ticket = read_ticket(&e, ticket_id)?;
+        if ticket.settled {
+            return Err(Error::AlreadyResolved);
+        }
+
+        let mut won = true;
+        let mut live_legs = Vec::new(&e);
+        for leg in ticket.legs.iter() {
+            let m = read_market(&e, leg.market_id)?;
+            if !m.resolved {
+                return Err(Error::NotResolved);
+            }
+            require_final(&e, leg.market_id)?;
+            match payout_outcome(&m) {
+                // Voided legs are refunded by dropping them from the multiplier
+                Outcome::Invalid => {}
+                Outcome::Yes if leg.side_yes => live_legs.push_back(leg),
+                Outcome::No if !leg.side_yes => live_legs.push_back(leg),
+                _ => won = false,
+            }
+        }
+
+        let payout = if won {
+            parlay_payout(ticket.stake, &live_legs)?
+        } else {
+            0
+        };
+
+        // Release the reservation; the house keeps a losing stake and
+        // funds whatever a winner earns above theirs
+        let mut house = read_house(&e);
+        house.reserved -= ticket.potential_payout - ticket.stake;
+        house.balance += ticket.stake - payout;
+        write_house(&e, &house);
+
+        ticket.settled = true;
+        ticket.payout = payout;
+        write_ticket(&e, &ticket);
+
+        if payout > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            token_client(&e, &token_addr).transfer(
+                &e.current_contract_address(),
+                &ticket.owner,
+                &payout,
+            );
+        }
+
+        events::parlay_settled(&e, &ticket);
+        Ok(payout)
+    }
+
+    /// Add Admin KALE to the parlay house
+    pub fn fund_parlay_house(e: Env, admin: Address, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        validate_amount(amount)?;
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&admin, &e.current_contract_address(), &amount);
+
+        let mut house = read_house(&e);
+        house.balance += amount;
+        write_house(&e, &house);
+
+        events::parlay_house_updated(&e, &house, amount);
+        Ok(())
+    }
+
+    /// Withdraw house KALE not reserved for open tickets to the treasury
+    pub fn withdraw_parlay_house(e: Env, admin: Address, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        validate_amount(amount)?;
+
+        let mut house = read_house(&e);
+        if house.balance - house.reserved < amount {
+            return Err(Error::InsufficientHouseLiquidity);
+        }
+        house.balance -= amount;
+        write_house(&e, &house);
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        let treasury = read_address(&e, &DataKey::Treasury)?;
+        token_client(&e, &token_addr).transfer(&e.current_contract_address(), &treasury, &amount);
+
+        events::parlay_house_updated(&e, &house, -amount);
+        Ok(())
+    }
+
+    pub fn get_parlay(e: Env, ticket_id: u32) -> Result<ParlayTicket, Error> {
+        read_ticket(&e, ticket_id)
+    }
+
+    pub fn get_parlay_house(e: Env) -> ParlayHouse {
+        read_house(&e)
+    }
+}
@@ -0,0 +1,203 @@
+//! Role-based access control.
+//!
+//! Every privileged entrypoint takes the caller's address and checks that it
+//! holds the role for that power. `init` gives the deployer Admin and every
+//! operational role except Resolver, which goes to the given resolver, so a
+//! fresh deployment works out of the box. Admins then grant and revoke roles
+//! as duties are split up. The last Admin can't be revoked, so the contract
+//! always has someone who can hand out roles. Each role's holders are kept
+//! one entry apiece with a count, so integrators can page through the
+//! current Admins and Resolvers.
+//!
+//! Failed role checks return `MissingRole`. Error codes 3 and 4, the old
+//! `NotResolver` and `NotAdmin`, stay reserved.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Vec};
+
+use crate::{
+    events, require_initialized, DataKey, Error, FarmersMarket, FarmersMarketArgs,
+    FarmersMarketClient,
+};
+
+/// A set of privileged powers. An address may hold any number of roles.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    Admin,         // roles, treasury, creation policy, bet limits, templates, parlay house
+    Resolver,      // settles markets
+    Moderator,     // cancels markets and manages the creator allowlist
+    FeeManager,    // protocol fee split and keeper bounties
+    Pauser,        // halts new bets, markets, orders and liquidity
+    OracleManager, // points the contract at a Reflector oracle
+}
+
+/// Storage keys for a role's holders, nested under `DataKey::RoleMembers`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum RoleKey {
+    Count(Role),
+    Member(Role, u32), // (role, n): the nth holder
+}
+
+fn read_role_count(e: &Env, role: Role) -> u32 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::RoleMembers(RoleKey::Count(role)))
+        .unwrap_or(0)
+}
+
+fn read_role_member(e: &Env, role: Role, n: u32) -> Option<Address> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::RoleMembers(RoleKey::Member(role, n)))
+}
+
+/// Put `account` in slot `n` of the role's list and remember where it sits
+fn write_role_member(e: &Env, role: Role, n: u32, account: &Address) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::RoleMembers(RoleKey::Member(role, n)), account);
+    e.storage()
+        .persistent()
+        .set(&DataKey::Role(role, account.clone()), &n);
+}
+
+pub(crate) fn holds_role(e: &Env, role: Role, account: &Address) -> bool {
+    e.storage()
+        .persistent()
+        .has(&DataKey::Role(role, account.clone()))
+}
+
+/// The role's first holder, for the single-holder getters
+fn first_member(e: &Env, role: Role) -> Result<Address, Error> {
+    read_role_member(e, role, 0).ok_or(Error::MissingRole)
+}
+
+/// Authenticate `caller` and check it holds `role`
+pub(crate) fn require_role(e: &Env, caller: &Address, role: Role) -> Result<(), Error> {
+    caller.require_auth();
+    if !holds_role(e, role, caller) {
+        return Err(Error::MissingRole);
+    }
+    Ok(())
+}
+
+/// Give `account` a role it doesn't already hold
+pub(crate) fn set_role(e: &Env, role: Role, account: &Address, sender: &Address) {
+    if holds_role(e, role, account) {
+        return;
+    }
+    let count = read_role_count(e, role);
+    write_role_member(e, role, count, account);
+    e.storage()
+        .persistent()
+        .set(&DataKey::RoleMembers(RoleKey::Count(role)), &(count + 1));
+
+    events::role_granted(e, role, account.clone(), sender.clone());
+}
+
+/// Refuse new KALE into the contract while it is paused
+pub(crate) fn require_not_paused(e: &Env) -> Result<(), Error> {
+    let paused: bool = e
+        .storage()
+        .instance()
+        .get(&DataKey::Paused)
+        .unwrap_or(false);
+    if paused {
+        return Err(Error::ContractPaused);
+    }
+    Ok(())
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Give `account` a role (Admin only)
+    pub fn grant_role(e: Env, admin: Address, role: Role, account: Address) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        set_role(&e, role, &account, &admin);
+        Ok(())
+    }
+
+    /// Take a role away from `account` (Admin only). The last Admin stays.
+    pub fn revoke_role(e: Env, admin: Address, role: Role, account: Address) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        let held: Option<u32> = e
+            .storage()
+            .persistent()
+            .get(&DataKey::Role(role, account.clone()));
+        let Some(n) = held else {
+            return Err(Error::MissingRole);
+        };
+        let count = read_role_count(&e, role);
+        if role == Role::Admin && count <= 1 {
+            return Err(Error::LastAdmin);
+        }
+
+        // Move the last holder into the freed slot so the list stays dense
+        let last = count - 1;
+        if n != last {
+            if let Some(moved) = read_role_member(&e, role, last) {
+                write_role_member(&e, role, n, &moved);
+            }
+        }
+        e.storage()
+            .persistent()
+            .remove(&DataKey::RoleMembers(RoleKey::Member(role, last)));
+        e.storage()
+            .persistent()
+            .remove(&DataKey::Role(role, account.clone()));
+        e.storage()
+            .persistent()
+            .set(&DataKey::RoleMembers(RoleKey::Count(role)), &last);
+
+        events::role_revoked(&e, role, account, admin);
+        Ok(())
+    }
+
+    /// Halt or resume new bets, commitments, markets, orders, liquidity and
+    /// parlays (Pauser only). Claims, refunds and settlement keep working.
+    pub fn set_paused(e: Env, pauser: Address, paused: bool) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &pauser, Role::Pauser)?;
+        e.storage().instance().set(&DataKey::Paused, &paused);
+
+        events::pause_updated(&e, paused, pauser);
+        Ok(())
+    }
+
+    pub fn has_role(e: Env, role: Role, account: Address) -> bool {
+        holds_role(&e, role, &account)
+    }
+
+    pub fn get_role_count(e: Env, role: Role) -> u32 {
+        read_role_count(&e, role)
+    }
+
+    /// Page through the holders of `role`. Revoking a role moves its last
+    /// holder into the freed place, so the order isn't the grant order.
+    pub fn get_role_members(e: Env, role: Role, start: u32, limit: u32) -> Vec<Address> {
+        let end = read_role_count(&e, role).min(start.saturating_add(limit));
+        let mut page = Vec::new(&e);
+        for n in start..end {
+            if let Some(account) = read_role_member(&e, role, n) {
+                page.push_back(account);
+            }
+        }
+        page
+    }
+
+    /// The first Admin, for integrations that expect a single admin
+    pub fn get_admin(e: Env) -> Result<Address, Error> {
+        first_member(&e, Role::Admin)
+    }
+
+    /// The first Resolver, for integrations that expect a single resolver
+    pub fn get_resolver(e: Env) -> Result<Address, Error> {
+        first_member(&e, Role::Resolver)
+    }
+
+    pub fn is_paused(e: Env) -> bool {
+        require_not_paused(&e).is_err()
+    }
+}
@@ -0,0 +1,470 @@
+//! Seasons and leaderboards.
+//!
+//! An Admin schedules seasons back to back, each with a ranking metric and
+//! a KALE prize pool. While a season runs, every `bet` (or reveal) and
+//! `claim` updates the player's stats for it: volume, markets played, wins
+//! and net PnL. Volume only counts the net exposure a bet adds, so betting
+//! both sides of a market doesn't inflate it. PnL is KALE claimed or
+//! received for shares minus KALE bet or paid for them. Shares carry the
+//! KALE paid for them as a cost basis, so a free transfer moves that cost
+//! from sender to receiver. Each season keeps a leaderboard of the best
+//! `LEADERBOARD_SIZE` players by its metric, refreshed whenever one of them
+//! scores. Once the season ends anyone can finalize it, paying the pool to
+//! the top of the board by `prize_bps`. Shares left unpaid because too few
+//! players scored go to the treasury.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, String, Vec};
+
+use crate::orderbook::Order;
+use crate::roles::{require_role, Role};
+use crate::{
+    events, now, read_address, require_initialized, token_client, validate_amount, DataKey, Error,
+    FarmersMarket, FarmersMarketArgs, FarmersMarketClient, Stake,
+};
+
+// Players a season's leaderboard tracks
+pub const LEADERBOARD_SIZE: u32 = 50;
+// Most ranks a season can pay
+pub const MAX_PRIZE_RANKS: u32 = 10;
+
+/// Storage keys for this module, nested under `DataKey::Seasons`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum SeasonKey {
+    NextId,
+    Season(u32),
+    Stats(u32, Address),      // (season_id, player)
+    Board(u32),               // best players first
+    Cost(u32, bool, Address), // (market_id, side_yes, holder): KALE paid for held shares
+    AskCost(u32),             // order_id -> cost of the shares an ask escrows
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum RankMetric {
+    Volume,
+    MarketsPlayed,
+    Wins,
+    NetPnl,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SeasonConfig {
+    pub name: String,
+    pub start_ts: u64,
+    pub end_ts: u64,
+    pub metric: RankMetric,
+    pub prize_bps: Vec<u32>, // pool share per rank, best first; sums to 10,000
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Season {
+    pub id: u32,
+    pub config: SeasonConfig,
+    pub prize_pool: i128,
+    pub finalized: bool,
+}
+
+/// A player's record within one season
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct SeasonStats {
+    pub volume: i128,
+    pub markets: u32,
+    pub wins: u32,
+    pub pnl: i128, // KALE claimed or received minus KALE bet or paid
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Standing {
+    pub player: Address,
+    pub score: i128, // the player's value for the season's metric
+}
+
+fn next_season_id(e: &Env) -> u32 {
+    e.storage()
+        .instance()
+        .get(&DataKey::Seasons(SeasonKey::NextId))
+        .unwrap_or(0)
+}
+
+/// An unknown season id is bad input; seasons have no error code of their
+/// own, to stay under the contracterror case limit
+fn read_season(e: &Env, season_id: u32) -> Result<Season, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Seasons(SeasonKey::Season(season_id)))
+        .ok_or(Error::InvalidAmount)
+}
+
+fn write_season(e: &Env, season: &Season) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::Seasons(SeasonKey::Season(season.id)), season);
+}
+
+fn read_stats(e: &Env, season_id: u32, player: &Address) -> SeasonStats {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Seasons(SeasonKey::Stats(
+            season_id,
+            player.clone(),
+        )))
+        .unwrap_or_default()
+}
+
+fn read_board(e: &Env, season_id: u32) -> Vec<Standing> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Seasons(SeasonKey::Board(season_id)))
+        .unwrap_or(Vec::new(e))
+}
+
+fn validate_season_config(config: &SeasonConfig) -> Result<(), Error> {
+    if config.end_ts <= config.start_ts {
+        return Err(Error::InvalidTime);
+    }
+    let ranks = config.prize_bps.len();
+    if ranks == 0 || ranks > MAX_PRIZE_RANKS {
+        return Err(Error::InvalidAmount);
+    }
+    let total: u32 = config.prize_bps.iter().sum();
+    if total != 10_000 {
+        return Err(Error::InvalidAmount);
+    }
+    Ok(())
+}
+
+/// The season running right now, if any. Seasons never overlap, so walk
+/// back past the ones scheduled for later.
+fn active_season(e: &Env) -> Option<Season> {
+    let t = now(e);
+    let mut id = next_season_id(e);
+    while id > 0 {
+        id -= 1;
+        let season = read_season(e, id).ok()?;
+        if t >= season.config.start_ts {
+            return (t < season.config.end_ts).then_some(season);
+        }
+    }
+    None
+}
+
+fn score(stats: &SeasonStats, metric: RankMetric) -> i128 {
+    match metric {
+        RankMetric::Volume => stats.volume,
+        RankMetric::MarketsPlayed => stats.markets as i128,
+        RankMetric::Wins => stats.wins as i128,
+        RankMetric::NetPnl => stats.pnl,
+    }
+}
+
+/// Move a player to their new place on the board. Ties keep the player
+/// who got there first ahead.
+fn rerank(e: &Env, season_id: u32, player: &Address, score: i128) {
+    let mut board = read_board(e, season_id);
+    let was_on = board.iter().position(|s| s.player == *player);
+    if let Some(i) = was_on {
+        board.remove(i as u32);
+    }
+    let at = board
+        .iter()
+        .position(|s| s.score < score)
+        .map_or(board.len(), |i| i as u32);
+    if at < LEADERBOARD_SIZE {
+        board.insert(
+            at,
+            Standing {
+                player: player.clone(),
+                score,
+            },
+        );
+        if board.len() > LEADERBOARD_SIZE {
+            board.pop_back();
+        }
+    } else if was_on.is_none() {
+        // Not good enough to be tracked
+        return;
+    }
+    e.storage()
+        .persistent()
+        .set(&DataKey::Seasons(SeasonKey::Board(season_id)), &board);
+}
+
+/// Apply `update` to a player's stats for the running season
+fn record(e: &Env, player: &Address, update: impl FnOnce(&mut SeasonStats)) {
+    let Some(season) = active_season(e) else {
+        return;
+    };
+    let key = DataKey::Seasons(SeasonKey::Stats(season.id, player.clone()));
+    let mut stats: SeasonStats = e.storage().persistent().get(&key).unwrap_or_default();
+    update(&mut stats);
+    e.storage().persistent().set(&key, &stats);
+    rerank(e, season.id, player, score(&stats, season.config.metric));
+}
+
+fn cost_key(market_id: u32, side_yes: bool, holder: &Address) -> DataKey {
+    DataKey::Seasons(SeasonKey::Cost(market_id, side_yes, holder.clone()))
+}
+
+fn add_cost(e: &Env, key: &DataKey, amount: i128) {
+    let cost: i128 = e.storage().persistent().get(key).unwrap_or(0);
+    let cost = cost + amount;
+    if cost > 0 {
+        e.storage().persistent().set(key, &cost);
+    } else {
+        e.storage().persistent().remove(key);
+    }
+}
+
+/// Take the cost of `shares` out of a holding of `held` shares
+fn take_cost(e: &Env, key: &DataKey, shares: i128, held: i128) -> i128 {
+    let cost: i128 = e.storage().persistent().get(key).unwrap_or(0);
+    if cost == 0 || held <= 0 {
+        return 0;
+    }
+    let part = if shares >= held {
+        cost
+    } else {
+        cost * shares / held
+    };
+    add_cost(e, key, -part);
+    part
+}
+
+/// Count a bet towards the running season. Volume grows by the net
+/// exposure the bet adds, so betting both sides of a market doesn't count.
+pub(crate) fn record_bet(
+    e: &Env,
+    market_id: u32,
+    player: &Address,
+    side_yes: bool,
+    amount: i128,
+    before: &Stake,
+    after: &Stake,
+) {
+    add_cost(e, &cost_key(market_id, side_yes, player), amount);
+    let added = (after.yes - after.no).abs() - (before.yes - before.no).abs();
+    record(e, player, |stats| {
+        stats.volume += added.max(0);
+        stats.pnl -= amount;
+        if before.yes + before.no == 0 {
+            stats.markets += 1;
+        }
+    });
+}
+
+/// Count a claim towards the running season. `payout` is what the player
+/// received, net of fees; refunds of an `Invalid` market aren't wins.
+pub(crate) fn record_claim(e: &Env, market_id: u32, player: &Address, payout: i128, won: bool) {
+    for side_yes in [true, false] {
+        e.storage()
+            .persistent()
+            .remove(&cost_key(market_id, side_yes, player));
+    }
+    record(e, player, |stats| {
+        stats.pnl += payout;
+        if won {
+            stats.wins += 1;
+        }
+    });
+}
+
+/// Carry the cost of freely transferred shares from sender to receiver.
+/// `held` is the sender's balance on that side before the transfer.
+pub(crate) fn record_transfer(
+    e: &Env,
+    market_id: u32,
+    side_yes: bool,
+    from: &Address,
+    to: &Address,
+    amount: i128,
+    held: i128,
+) {
+    let cost = take_cost(e, &cost_key(market_id, side_yes, from), amount, held);
+    if cost == 0 {
+        return;
+    }
+    add_cost(e, &cost_key(market_id, side_yes, to), cost);
+    record(e, from, |stats| stats.pnl += cost);
+    record(e, to, |stats| stats.pnl -= cost);
+}
+
+/// Hold the cost of the YES shares an ask escrows with the order
+pub(crate) fn escrow_ask_cost(
+    e: &Env,
+    market_id: u32,
+    order_id: u32,
+    trader: &Address,
+    amount: i128,
+    held: i128,
+) {
+    let cost = take_cost(e, &cost_key(market_id, true, trader), amount, held);
+    add_cost(e, &DataKey::Seasons(SeasonKey::AskCost(order_id)), cost);
+}
+
+/// Give a closed ask's leftover cost back to its trader with the shares
+pub(crate) fn release_ask_cost(e: &Env, market_id: u32, order_id: u32, trader: &Address) {
+    let key = DataKey::Seasons(SeasonKey::AskCost(order_id));
+    let cost: i128 = e.storage().persistent().get(&key).unwrap_or(0);
+    e.storage().persistent().remove(&key);
+    add_cost(e, &cost_key(market_id, true, trader), cost);
+}
+
+/// Count an order-book fill of `amount` YES shares for `paid` KALE, before
+/// the orders are reduced by it
+pub(crate) fn record_fill(e: &Env, bid: &Order, ask: &Order, amount: i128, paid: i128) {
+    // The sold shares' cost is realized by the seller's proceeds
+    take_cost(
+        e,
+        &DataKey::Seasons(SeasonKey::AskCost(ask.id)),
+        amount,
+        ask.amount,
+    );
+    add_cost(e, &cost_key(bid.market_id, true, &bid.trader), paid);
+    if paid > 0 {
+        record(e, &bid.trader, |stats| stats.pnl -= paid);
+        record(e, &ask.trader, |stats| stats.pnl += paid);
+    }
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Schedule a season and fund its prize pool from the Admin (Admin
+    /// only). It must start after the last scheduled season ends.
+    pub fn create_season(
+        e: Env,
+        admin: Address,
+        config: SeasonConfig,
+        prize_pool: i128,
+    ) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        validate_season_config(&config)?;
+        if prize_pool < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let id = next_season_id(&e);
+        if id > 0 && config.start_ts < read_season(&e, id - 1)?.config.end_ts {
+            return Err(Error::InvalidTime);
+        }
+        if config.end_ts <= now(&e) {
+            return Err(Error::InvalidTime);
+        }
+
+        if prize_pool > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            token_client(&e, &token_addr).transfer(
+                &admin,
+                &e.current_contract_address(),
+                &prize_pool,
+            );
+        }
+        e.storage()
+            .instance()
+            .set(&DataKey::Seasons(SeasonKey::NextId), &(id + 1));
+        let season = Season {
+            id,
+            config,
+            prize_pool,
+            finalized: false,
+        };
+        write_season(&e, &season);
+
+        events::season_created(&e, &season);
+        Ok(id)
+    }
+
+    /// Add KALE to a season's prize pool until it is finalized
+    pub fn fund_season(e: Env, funder: Address, season_id: u32, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        funder.require_auth();
+        validate_amount(amount)?;
+
+        let mut season = read_season(&e, season_id)?;
+        if season.finalized {
+            return Err(Error::AlreadyResolved);
+        }
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&funder, &e.current_contract_address(), &amount);
+        season.prize_pool += amount;
+        write_season(&e, &season);
+
+        events::season_funded(&e, season_id, funder, amount, season.prize_pool);
+        Ok(())
+    }
+
+    /// Pay out an ended season's prizes. Anyone can call this. Only players
+    /// with a positive score place. Returns the KALE paid to players.
+    pub fn finalize_season(e: Env, season_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+
+        let mut season = read_season(&e, season_id)?;
+        if season.finalized {
+            return Err(Error::AlreadyResolved);
+        }
+        if now(&e) < season.config.end_ts {
+            return Err(Error::InvalidTime);
+        }
+        season.finalized = true;
+        write_season(&e, &season);
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        let token = token_client(&e, &token_addr);
+        let board = read_board(&e, season_id);
+        let mut paid = 0;
+        for (rank, bps) in season.config.prize_bps.iter().enumerate() {
+            let Some(standing) = board.get(rank as u32) else {
+                break;
+            };
+            if standing.score <= 0 {
+                break;
+            }
+            let prize = season.prize_pool * bps as i128 / 10_000;
+            if prize > 0 {
+                token.transfer(&e.current_contract_address(), &standing.player, &prize);
+                paid += prize;
+            }
+            events::season_prize_paid(&e, season_id, standing.player, rank as u32 + 1, prize);
+        }
+
+        // Unplaced ranks and rounding dust
+        let left = season.prize_pool - paid;
+        if left > 0 {
+            let treasury = read_address(&e, &DataKey::Treasury)?;
+            token.transfer(&e.current_contract_address(), &treasury, &left);
+        }
+
+        events::season_finalized(&e, &season, paid);
+        Ok(paid)
+    }
+
+    pub fn get_season(e: Env, season_id: u32) -> Option<Season> {
+        read_season(&e, season_id).ok()
+    }
+
+    /// The season stats are being recorded to right now
+    pub fn get_active_season(e: Env) -> Option<Season> {
+        active_season(&e)
+    }
+
+    pub fn get_season_count(e: Env) -> u32 {
+        next_season_id(&e)
+    }
+
+    pub fn get_season_stats(e: Env, season_id: u32, player: Address) -> SeasonStats {
+        read_stats(&e, season_id, &player)
+    }
+
+    /// A season's best players by its metric, best first
+    pub fn get_leaderboard(e: Env, season_id: u32, limit: u32) -> Vec<Standing> {
+        let board = read_board(&e, season_id);
+        let mut top = Vec::new(&e);
+        for standing in board.iter().take(limit as usize) {
+            top.push_back(standing);
+        }
+        top
+    }
+}
@@ -0,0 +1,226 @@
+//! Transferable YES/NO outcome shares.
+//!
+//! An embedded multi-token ledger over `Stake`: every market has a YES and a
+//! NO share, one share per stroop staked on that side. The functions mirror
+//! the SEP-41 token interface with the share picked by `(market_id,
+//! side_yes)`. Shares move freely until the market resolves, after which
+//! `claim` redeems them and their balance drops to zero.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, String};
+
+use crate::commit_reveal;
+use crate::seasons;
+use crate::weighting::share_weight;
+use crate::{
+    events, read_market, read_stake, require_initialized, validate_amount, write_stake, DataKey,
+    Error, FarmersMarket, FarmersMarketArgs, FarmersMarketClient, Market, Stake,
+};
+
+// Shares are denominated in KALE stroops
+pub const SHARE_DECIMALS: u32 = 7;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct ShareAllowance {
+    pub amount: i128,
+    pub expiration_ledger: u32,
+}
+
+fn side_balance(stake: &Stake, side_yes: bool) -> i128 {
+    if stake.claimed {
+        0
+    } else if side_yes {
+        stake.yes
+    } else {
+        stake.no
+    }
+}
+
+fn add_to_side(stake: &mut Stake, side_yes: bool, amount: i128, weight: i128) {
+    if side_yes {
+        stake.yes += amount;
+        stake.yes_weight += weight;
+    } else {
+        stake.no += amount;
+        stake.no_weight += weight;
+    }
+}
+
+fn allowance_key(market_id: u32, side_yes: bool, from: &Address, spender: &Address) -> DataKey {
+    DataKey::ShareAllowance(market_id, side_yes, from.clone(), spender.clone())
+}
+
+fn read_allowance(e: &Env, key: &DataKey) -> i128 {
+    match e.storage().persistent().get::<_, ShareAllowance>(key) {
+        Some(a) if a.expiration_ledger >= e.ledger().sequence() => a.amount,
+        _ => 0,
+    }
+}
+
+/// Open market whose shares can still change hands
+fn tradable_market(e: &Env, market_id: u32) -> Result<Market, Error> {
+    let market = read_market(e, market_id)?;
+    if market.resolved {
+        return Err(Error::MarketClosed);
+    }
+    Ok(market)
+}
+
+/// Move shares between holders. The receiver is held to the market's
+/// per-user stake cap, the same as if they had bet.
+pub(crate) fn move_shares(
+    e: &Env,
+    market: &Market,
+    from: &Address,
+    to: &Address,
+    side_yes: bool,
+    amount: i128,
+) -> Result<(), Error> {
+    validate_amount(amount)?;
+
+    let mut from_stake = read_stake(e, market.id, from);
+    if side_balance(&from_stake, side_yes) < amount {
+        return Err(Error::InsufficientShares);
+    }
+    if from == to {
+        return Ok(());
+    }
+
+    let mut to_stake = read_stake(e, market.id, to);
+    let cap = market.limits.max_user_stake;
+    let sealed = commit_reveal::pending_commitment(e, market.id, to);
+    if cap > 0 && to_stake.yes + to_stake.no + sealed + amount > cap {
+        return Err(Error::UserStakeCapExceeded);
+    }
+
+    let held = side_balance(&from_stake, side_yes);
+    // Shares keep the weight they were staked with
+    let weight = share_weight(&from_stake, side_yes, amount);
+    add_to_side(&mut from_stake, side_yes, -amount, -weight);
+    add_to_side(&mut to_stake, side_yes, amount, weight);
+    write_stake(e, market.id, from, &from_stake);
+    write_stake(e, market.id, to, &to_stake);
+    seasons::record_transfer(e, market.id, side_yes, from, to, amount, held);
+
+    events::shares_transferred(
+        e,
+        market.id,
+        side_yes,
+        amount,
+        (from, &from_stake),
+        (to, &to_stake),
+    );
+    Ok(())
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    pub fn share_balance(e: Env, market_id: u32, side_yes: bool, id: Address) -> i128 {
+        side_balance(&read_stake(&e, market_id, &id), side_yes)
+    }
+
+    pub fn share_transfer(
+        e: Env,
+        from: Address,
+        to: Address,
+        market_id: u32,
+        side_yes: bool,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        from.require_auth();
+        let market = tradable_market(&e, market_id)?;
+        move_shares(&e, &market, &from, &to, side_yes, amount)
+    }
+
+    pub fn share_transfer_from(
+        e: Env,
+        spender: Address,
+        from: Address,
+        to: Address,
+        market_id: u32,
+        side_yes: bool,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        spender.require_auth();
+        let market = tradable_market(&e, market_id)?;
+
+        let key = allowance_key(market_id, side_yes, &from, &spender);
+        let allowance = read_allowance(&e, &key);
+        if allowance < amount {
+            return Err(Error::InsufficientAllowance);
+        }
+        move_shares(&e, &market, &from, &to, side_yes, amount)?;
+
+        let mut stored: ShareAllowance = e.storage().persistent().get(&key).unwrap();
+        stored.amount = allowance - amount;
+        e.storage().persistent().set(&key, &stored);
+        Ok(())
+    }
+
+    /// Let `spender` move up to `amount` of `from`'s shares until
+    /// `expiration_ledger`. An amount of 0 revokes the allowance.
+    pub fn share_approve(
+        e: Env,
+        from: Address,
+        spender: Address,
+        market_id: u32,
+        side_yes: bool,
+        amount: i128,
+        expiration_ledger: u32,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        from.require_auth();
+        read_market(&e, market_id)?;
+
+        if amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if amount > 0 && expiration_ledger < e.ledger().sequence() {
+            return Err(Error::InvalidTime);
+        }
+
+        let key = allowance_key(market_id, side_yes, &from, &spender);
+        e.storage().persistent().set(
+            &key,
+            &ShareAllowance {
+                amount,
+                expiration_ledger,
+            },
+        );
+
+        events::shares_approved(
+            &e,
+            market_id,
+            side_yes,
+            from,
+            spender,
+            amount,
+            expiration_ledger,
+        );
+        Ok(())
+    }
+
+    pub fn share_allowance(
+        e: Env,
+        from: Address,
+        spender: Address,
+        market_id: u32,
+        side_yes: bool,
+    ) -> i128 {
+        read_allowance(&e, &allowance_key(market_id, side_yes, &from, &spender))
+    }
+
+    pub fn share_decimals(_e: Env) -> u32 {
+        SHARE_DECIMALS
+    }
+
+    /// Shares are named after their market's question
+    pub fn share_name(e: Env, market_id: u32) -> Result<String, Error> {
+        Ok(read_market(&e, market_id)?.question)
+    }
+
+    pub fn share_symbol(e: Env, side_yes: bool) -> String {
+        String::from_str(&e, if side_yes { "YES" } else { "NO" })
+    }
+}
@@ -0,0 +1,355 @@
+//! Resolver staking and slashing.
+//!
+//! With `min_stake` set, an address needs both the `Resolver` role and at
+//! least that much KALE staked here to call `resolve`. Each resolution it
+//! makes stays open until the market's dispute window closes or its ballot
+//! settles, and the whole stake is locked while any are open. A resolver
+//! a market names as its `ResolverChoice::Account` can't take its stake
+//! below `min_stake` until that market settles, or it would be left unable
+//! to resolve it. A ballot that
+//! overturns a resolution slashes `slash_bps` of the resolver's stake into
+//! the market's compensation pot. `claim` pays that pot to the bettors the
+//! corrected outcome pays, the same way as the pool. If nobody can be paid,
+//! it goes to the treasury instead.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Vec};
+
+use crate::dispute::require_final;
+use crate::roles::{require_role, Role};
+use crate::{
+    events, payout_outcome, read_address, require_initialized, token_client, validate_amount,
+    winning_pool, DataKey, Error, FarmersMarket, FarmersMarketArgs, FarmersMarketClient, Market,
+    NoWinnerPolicy, Outcome, ResolverChoice,
+};
+
+/// Storage keys for this module, nested under `DataKey::Staking`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum StakingKey {
+    Config,
+    Record(Address),
+    Open(Address),         // resolutions still open to dispute
+    Assigned(Address),     // unsettled markets naming the resolver as theirs
+    History(Address, u32), // (resolver, n): the nth market it resolved
+    Resolver(u32),         // who resolved a market
+    Compensation(u32),     // slashed resolver stake owed to a market's bettors
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct ResolverStakeConfig {
+    pub min_stake: i128, // KALE a resolver must stake, 0 = no stake needed
+    pub slash_bps: u32,  // share of the stake taken per overturned resolution
+}
+
+/// A resolver's stake and track record
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct ResolverRecord {
+    pub stake: i128,
+    pub resolved: u32,
+    pub overturned: u32,
+    pub slashed: i128, // KALE lost to slashing so far
+}
+
+fn read_config(e: &Env) -> ResolverStakeConfig {
+    e.storage()
+        .instance()
+        .get(&DataKey::Staking(StakingKey::Config))
+        .unwrap_or_default()
+}
+
+fn read_record(e: &Env, resolver: &Address) -> ResolverRecord {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Staking(StakingKey::Record(resolver.clone())))
+        .unwrap_or_default()
+}
+
+fn write_record(e: &Env, resolver: &Address, record: &ResolverRecord) {
+    e.storage().persistent().set(
+        &DataKey::Staking(StakingKey::Record(resolver.clone())),
+        record,
+    );
+}
+
+fn read_ids(e: &Env, key: &DataKey) -> Vec<u32> {
+    e.storage().persistent().get(key).unwrap_or(Vec::new(e))
+}
+
+/// The markets in `ids` whose outcome can still be disputed
+fn still_open(e: &Env, ids: &Vec<u32>) -> Vec<u32> {
+    let mut open = Vec::new(e);
+    for id in ids.iter() {
+        if require_final(e, id).is_err() {
+            open.push_back(id);
+        }
+    }
+    open
+}
+
+/// A resolver's resolutions that can still be disputed, forgetting the
+/// ones that have since become final
+fn prune_open_resolutions(e: &Env, resolver: &Address) -> Vec<u32> {
+    let key = DataKey::Staking(StakingKey::Open(resolver.clone()));
+    let ids = read_ids(e, &key);
+    let open = still_open(e, &ids);
+    if open.len() != ids.len() {
+        e.storage().persistent().set(&key, &open);
+    }
+    open
+}
+
+fn read_compensation(e: &Env, market_id: u32) -> i128 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Staking(StakingKey::Compensation(market_id)))
+        .unwrap_or(0)
+}
+
+fn read_assigned(e: &Env, resolver: &Address) -> u32 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Staking(StakingKey::Assigned(resolver.clone())))
+        .unwrap_or(0)
+}
+
+fn write_assigned(e: &Env, resolver: &Address, count: u32) {
+    let key = DataKey::Staking(StakingKey::Assigned(resolver.clone()));
+    if count == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &count);
+    }
+}
+
+/// Count a new market against the resolver it names, if any
+pub(crate) fn assign_market(e: &Env, market: &Market) {
+    if let ResolverChoice::Account(resolver) = &market.config.resolver {
+        write_assigned(e, resolver, read_assigned(e, resolver) + 1);
+    }
+}
+
+/// Release a settled market's hold on the resolver it named, if any
+pub(crate) fn release_market(e: &Env, market: &Market) {
+    if let ResolverChoice::Account(resolver) = &market.config.resolver {
+        write_assigned(e, resolver, read_assigned(e, resolver).saturating_sub(1));
+    }
+}
+
+/// Check a resolver has the stake `resolve` needs
+pub(crate) fn require_staked(e: &Env, resolver: &Address) -> Result<(), Error> {
+    if read_record(e, resolver).stake < read_config(e).min_stake {
+        return Err(Error::InsufficientResolverStake);
+    }
+    Ok(())
+}
+
+/// Remember who resolved a market, for slashing and the resolver's history
+pub(crate) fn record_resolution(e: &Env, resolver: &Address, market_id: u32) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::Staking(StakingKey::Resolver(market_id)), resolver);
+
+    // One entry per resolution; `resolved` doubles as the history's length
+    let mut record = read_record(e, resolver);
+    e.storage().persistent().set(
+        &DataKey::Staking(StakingKey::History(resolver.clone(), record.resolved)),
+        &market_id,
+    );
+    record.resolved += 1;
+    write_record(e, resolver, &record);
+
+    if require_final(e, market_id).is_err() {
+        let mut open = prune_open_resolutions(e, resolver);
+        open.push_back(market_id);
+        e.storage()
+            .persistent()
+            .set(&DataKey::Staking(StakingKey::Open(resolver.clone())), &open);
+    }
+}
+
+/// Slash whoever resolved a market a ballot just overturned
+pub(crate) fn slash_resolver(e: &Env, market: &Market) -> Result<(), Error> {
+    let Some(resolver) = e
+        .storage()
+        .persistent()
+        .get::<_, Address>(&DataKey::Staking(StakingKey::Resolver(market.id)))
+    else {
+        // Settled by a keeper or the oracle, nobody to slash
+        return Ok(());
+    };
+    let mut record = read_record(e, &resolver);
+    let amount = record.stake * read_config(e).slash_bps as i128 / 10_000;
+    record.stake -= amount;
+    record.slashed += amount;
+    record.overturned += 1;
+    write_record(e, &resolver, &record);
+
+    let nobody_to_pay = market.yes_pool + market.no_pool == 0
+        || (winning_pool(market) == Some(0)
+            && market.config.no_winner_policy == NoWinnerPolicy::Treasury);
+    if amount > 0 {
+        if nobody_to_pay {
+            let token_addr = read_address(e, &DataKey::KaleToken)?;
+            let treasury = read_address(e, &DataKey::Treasury)?;
+            token_client(e, &token_addr).transfer(
+                &e.current_contract_address(),
+                &treasury,
+                &amount,
+            );
+        } else {
+            e.storage().persistent().set(
+                &DataKey::Staking(StakingKey::Compensation(market.id)),
+                &(read_compensation(e, market.id) + amount),
+            );
+        }
+    }
+
+    events::resolver_slashed(e, market.id, resolver, amount, &record);
+    Ok(())
+}
+
+/// A position's cut of the compensation pot: pro-rata by weight for
+/// winners, by raw stake when everyone is refunded
+pub(crate) fn compensation_share(
+    e: &Env,
+    m: &Market,
+    yes: i128,
+    no: i128,
+    yes_weight: i128,
+    no_weight: i128,
+) -> i128 {
+    let pot = read_compensation(e, m.id);
+    if pot == 0 {
+        return 0;
+    }
+    let (share, of) = match payout_outcome(m) {
+        Outcome::Yes => (yes_weight, m.yes_weight),
+        Outcome::No => (no_weight, m.no_weight),
+        Outcome::Invalid => (yes + no, m.yes_pool + m.no_pool),
+    };
+    if of > 0 {
+        pot * share / of
+    } else {
+        0
+    }
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Set the minimum resolver stake and the slash per overturned
+    /// resolution (Admin only)
+    pub fn set_resolver_stake_config(
+        e: Env,
+        admin: Address,
+        config: ResolverStakeConfig,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        if config.min_stake < 0 || config.slash_bps > 10_000 {
+            return Err(Error::InvalidAmount);
+        }
+        e.storage()
+            .instance()
+            .set(&DataKey::Staking(StakingKey::Config), &config);
+
+        events::resolver_stake_config_updated(&e, config);
+        Ok(())
+    }
+
+    /// Add KALE to a resolver's stake
+    pub fn stake_resolver(e: Env, resolver: Address, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        resolver.require_auth();
+        validate_amount(amount)?;
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&resolver, &e.current_contract_address(), &amount);
+
+        let mut record = read_record(&e, &resolver);
+        record.stake += amount;
+        write_record(&e, &resolver, &record);
+
+        events::resolver_staked(&e, resolver, amount, record.stake);
+        Ok(())
+    }
+
+    /// Withdraw stake. Only possible once none of the resolver's
+    /// resolutions can still be disputed, and never below `min_stake` while
+    /// a market it was assigned is unsettled.
+    pub fn unstake_resolver(e: Env, resolver: Address, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        resolver.require_auth();
+        validate_amount(amount)?;
+
+        if !prune_open_resolutions(&e, &resolver).is_empty() {
+            return Err(Error::DisputePending);
+        }
+        let mut record = read_record(&e, &resolver);
+        if amount > record.stake {
+            return Err(Error::InvalidAmount);
+        }
+        if read_assigned(&e, &resolver) > 0 && record.stake - amount < read_config(&e).min_stake {
+            return Err(Error::InsufficientResolverStake);
+        }
+        record.stake -= amount;
+        write_record(&e, &resolver, &record);
+
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&e.current_contract_address(), &resolver, &amount);
+
+        events::resolver_unstaked(&e, resolver, amount, record.stake);
+        Ok(())
+    }
+
+    pub fn get_resolver_stake_config(e: Env) -> ResolverStakeConfig {
+        read_config(&e)
+    }
+
+    pub fn get_resolver_record(e: Env, resolver: Address) -> ResolverRecord {
+        read_record(&e, &resolver)
+    }
+
+    /// Share of a resolver's resolutions that stood, in basis points
+    pub fn get_resolver_accuracy(e: Env, resolver: Address) -> u32 {
+        let record = read_record(&e, &resolver);
+        if record.resolved == 0 {
+            return 10_000;
+        }
+        (record.resolved - record.overturned) * 10_000 / record.resolved
+    }
+
+    /// Page through the markets a resolver has resolved, oldest first
+    pub fn get_resolver_history(e: Env, resolver: Address, start: u32, limit: u32) -> Vec<u32> {
+        let end = read_record(&e, &resolver)
+            .resolved
+            .min(start.saturating_add(limit));
+        let mut page = Vec::new(&e);
+        for i in start..end {
+            let key = DataKey::Staking(StakingKey::History(resolver.clone(), i));
+            if let Some(id) = e.storage().persistent().get(&key) {
+                page.push_back(id);
+            }
+        }
+        page
+    }
+
+    /// Resolutions that still lock the resolver's stake
+    pub fn get_open_resolutions(e: Env, resolver: Address) -> Vec<u32> {
+        still_open(
+            &e,
+            &read_ids(&e, &DataKey::Staking(StakingKey::Open(resolver))),
+        )
+    }
+
+    /// Unsettled markets assigned to the resolver, which keep `min_stake`
+    /// of its stake locked
+    pub fn get_open_assignments(e: Env, resolver: Address) -> u32 {
+        read_assigned(&e, &resolver)
+    }
+
+    /// Slashed KALE waiting to be paid out with a market's claims
+    pub fn get_compensation(e: Env, market_id: u32) -> i128 {
+        read_compensation(&e, market_id)
+    }
+}
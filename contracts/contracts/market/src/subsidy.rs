@@ -0,0 +1,122 @@
+//! Creator subsidies.
+//!
+//! A creator can seed a market with KALE that goes to winners on top of the
+//! pool, and anyone can top it up until the market resolves. `claim` pays
+//! each winner the subsidy pro-rata to their winning stake. If nobody can
+//! win (the market is `Invalid` or the winning side is empty) funders take
+//! their contributions back with `refund_subsidy`.
+use soroban_sdk::{contractimpl, Address, Env};
+
+use crate::dispute::require_final;
+use crate::{
+    events, payout_outcome, read_address, read_market, require_initialized, token_client,
+    validate_amount, winning_pool, DataKey, Error, FarmersMarket, FarmersMarketArgs,
+    FarmersMarketClient, Market, Outcome,
+};
+
+fn read_subsidy(e: &Env, market_id: u32) -> i128 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Subsidy(market_id))
+        .unwrap_or(0)
+}
+
+fn read_contribution(e: &Env, market_id: u32, funder: &Address) -> i128 {
+    e.storage()
+        .persistent()
+        .get(&DataKey::SubsidyFunder(market_id, funder.clone()))
+        .unwrap_or(0)
+}
+
+/// Whether a resolved market has no winners to pay its subsidy to
+fn subsidy_refundable(m: &Market) -> bool {
+    payout_outcome(m) == Outcome::Invalid || winning_pool(m) == Some(0)
+}
+
+/// Take `amount` from `funder` into a market's subsidy
+pub(crate) fn fund_subsidy(
+    e: &Env,
+    market_id: u32,
+    funder: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    let token_addr = read_address(e, &DataKey::KaleToken)?;
+    token_client(e, &token_addr).transfer(funder, &e.current_contract_address(), &amount);
+
+    let total = read_subsidy(e, market_id) + amount;
+    e.storage()
+        .persistent()
+        .set(&DataKey::Subsidy(market_id), &total);
+    let contributed = read_contribution(e, market_id, funder) + amount;
+    e.storage().persistent().set(
+        &DataKey::SubsidyFunder(market_id, funder.clone()),
+        &contributed,
+    );
+
+    events::subsidy_added(e, market_id, funder.clone(), amount, total);
+    Ok(())
+}
+
+/// A winning stake's cut of the subsidy, split by weight like the pool
+pub(crate) fn subsidy_share(e: &Env, m: &Market, winning_weight: i128) -> i128 {
+    let pool_weight = match payout_outcome(m) {
+        Outcome::Yes => m.yes_weight,
+        Outcome::No => m.no_weight,
+        Outcome::Invalid => 0,
+    };
+    if pool_weight > 0 {
+        read_subsidy(e, m.id) * winning_weight / pool_weight
+    } else {
+        0
+    }
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Add KALE to a market's winner subsidy. Anyone can top it up until
+    /// the market resolves.
+    pub fn add_subsidy(e: Env, funder: Address, market_id: u32, amount: i128) -> Result<(), Error> {
+        require_initialized(&e)?;
+        funder.require_auth();
+        validate_amount(amount)?;
+
+        let market = read_market(&e, market_id)?;
+        if market.resolved {
+            return Err(Error::MarketClosed);
+        }
+        fund_subsidy(&e, market_id, &funder, amount)
+    }
+
+    /// Return a funder's contribution when the market has no winners
+    pub fn refund_subsidy(e: Env, funder: Address, market_id: u32) -> Result<i128, Error> {
+        require_initialized(&e)?;
+        funder.require_auth();
+
+        let market = read_market(&e, market_id)?;
+        if !market.resolved {
+            return Err(Error::NotResolved);
+        }
+        require_final(&e, market_id)?;
+        let amount = read_contribution(&e, market_id, &funder);
+        if amount == 0 || !subsidy_refundable(&market) {
+            return Err(Error::NothingToClaim);
+        }
+
+        e.storage()
+            .persistent()
+            .remove(&DataKey::SubsidyFunder(market_id, funder.clone()));
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&e.current_contract_address(), &funder, &amount);
+
+        events::subsidy_refunded(&e, market_id, funder, amount);
+        Ok(amount)
+    }
+
+    pub fn get_subsidy(e: Env, market_id: u32) -> i128 {
+        read_subsidy(&e, market_id)
+    }
+
+    pub fn get_subsidy_contribution(e: Env, market_id: u32, funder: Address) -> i128 {
+        read_contribution(&e, market_id, &funder)
+    }
+}
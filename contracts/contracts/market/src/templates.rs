@@ -0,0 +1,395 @@
+//! Recurring market templates.
+//!
+//! The admin registers a template once ("Will BTC close above ${price}
+//! today?", daily) and anyone can then spawn each period's market from it.
+//! The strike is set from the Reflector spot price at spawn time plus the
+//! template's offset, kept in oracle decimals, and the caller is paid a keeper tip from KALE
+//! deposited on the template.
+use soroban_sdk::{contractimpl, contracttype, Address, Env, String, Symbol, Vec};
+
+use crate::keeper::{keeper_settleable, market_oracle, write_price_condition, PriceCondition};
+use crate::reflector::{Asset as ReflectorAsset, ReflectorClient};
+use crate::{
+    events, insert_market, market_limits, now, read_address, read_market, require_initialized,
+    require_not_paused, require_role, token_client, validate_amount, validate_market_config,
+    validate_metadata, DataKey, Error, FarmersMarket, FarmersMarketArgs, FarmersMarketClient,
+    Market, MarketConfig, MarketMetadata, Outcome, Role,
+};
+
+pub const MAX_TEMPLATE_QUESTION_LEN: u32 = 200;
+// Oldest spot price a spawn will set the strike from
+pub const MAX_SPOT_PRICE_AGE: u64 = 15 * 60;
+// Furthest above spot a strike may be set, 10_000 = double the spot price
+pub const MAX_PRICE_OFFSET_BPS: i32 = 10_000;
+const PRICE_PLACEHOLDER: &[u8] = b"{price}";
+// An i128 has at most 39 digits, plus a decimal point and a leading zero
+const PRICE_BUF_LEN: usize = 41;
+// Room for the question with the placeholder swapped for a price
+const RENDER_BUF_LEN: usize = MAX_TEMPLATE_QUESTION_LEN as usize + PRICE_BUF_LEN;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketTemplate {
+    pub question: String, // "{price}" is replaced with the strike in USD, e.g. 0.2625
+    pub asset: Symbol,    // Reflector ticker, e.g. BTC
+    pub price_offset_bps: i32, // strike relative to spot, 500 = 5% above
+    pub start_ts: u64,    // start of the first period
+    pub cadence: u64,     // period length, e.g. 86400 for daily
+    pub close_offset: u64, // betting closes this long after the period starts
+    pub resolution_offset: u64,
+    pub keeper_tip: i128,
+    pub config: MarketConfig,
+    pub metadata: MarketMetadata,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TemplateState {
+    pub active: bool,
+    pub next_period_ts: u64, // earliest start of the next period to spawn
+    pub spawned: u32,
+    pub tip_balance: i128, // KALE deposited for keeper tips
+    pub creator: Address,  // admin who registered the template
+}
+
+/// Links a spawned market back to its template and the previous instance
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TemplateInstance {
+    pub template_id: u32,
+    pub sequence: u32,
+    pub period_start: u64,
+    pub spot_price: i128,
+    pub strike_price: i128, // in oracle decimals
+    pub prev_market_id: Option<u32>,
+}
+
+fn read_template(e: &Env, id: u32) -> Result<MarketTemplate, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Template(id))
+        .ok_or(Error::TemplateNotFound)
+}
+
+fn read_template_state(e: &Env, id: u32) -> Result<TemplateState, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::TemplateState(id))
+        .ok_or(Error::TemplateNotFound)
+}
+
+fn write_template_state(e: &Env, id: u32, state: &TemplateState) {
+    e.storage()
+        .persistent()
+        .set(&DataKey::TemplateState(id), state);
+}
+
+/// The template's `n`th spawned market; `TemplateState.spawned` counts them
+fn read_template_market(e: &Env, id: u32, n: u32) -> Option<u32> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::TemplateMarkets(id, n))
+}
+
+fn validate_template(t: &MarketTemplate) -> Result<(), Error> {
+    if t.question.len() > MAX_TEMPLATE_QUESTION_LEN {
+        return Err(Error::MetadataTooLong);
+    }
+    if t.cadence == 0 || t.close_offset == 0 || t.resolution_offset <= t.close_offset {
+        return Err(Error::InvalidTime);
+    }
+    // Betting on one period's market closes before the next one opens
+    if t.close_offset > t.cadence {
+        return Err(Error::InvalidTime);
+    }
+    // Spawned markets have no creator to take a subsidy from, and settle
+    // from the oracle
+    if t.keeper_tip < 0
+        || t.price_offset_bps <= -10_000
+        || t.price_offset_bps > MAX_PRICE_OFFSET_BPS
+        || t.config.subsidy != 0
+        || !keeper_settleable(&t.config)
+    {
+        return Err(Error::InvalidAmount);
+    }
+    validate_market_config(&t.config)?;
+    validate_metadata(&t.metadata)
+}
+
+/// Write a price with `decimals` places as a decimal number into `buf`,
+/// without trailing zeros in the fraction. Returns the length written.
+fn format_price(price: i128, decimals: usize, buf: &mut [u8; PRICE_BUF_LEN]) -> usize {
+    // Least significant first, with at least one digit before the point
+    let mut digits = [b'0'; PRICE_BUF_LEN];
+    let mut n = 0;
+    let mut rest = price;
+    while rest > 0 || n <= decimals {
+        digits[n] = b'0' + (rest % 10) as u8;
+        n += 1;
+        rest /= 10;
+    }
+    let mut frac_from = 0;
+    while frac_from < decimals && digits[frac_from] == b'0' {
+        frac_from += 1;
+    }
+
+    let mut o = 0;
+    for d in digits[decimals..n].iter().rev() {
+        buf[o] = *d;
+        o += 1;
+    }
+    if frac_from < decimals {
+        buf[o] = b'.';
+        o += 1;
+        for d in digits[frac_from..decimals].iter().rev() {
+            buf[o] = *d;
+            o += 1;
+        }
+    }
+    o
+}
+
+/// Fill the "{price}" placeholder in a template question with a price in
+/// `decimals` places, e.g. 2625 with 4 decimals reads 0.2625
+fn render_question(e: &Env, format: &String, price: i128, decimals: u32) -> Result<String, Error> {
+    // Leaves room in the buffer for the point and a leading zero
+    if price < 0 || decimals as usize > PRICE_BUF_LEN - 3 {
+        return Err(Error::InvalidAmount);
+    }
+    let len = format.len() as usize;
+    let mut src = [0u8; MAX_TEMPLATE_QUESTION_LEN as usize];
+    format.copy_into_slice(&mut src[..len]);
+
+    let mut digits = [0u8; PRICE_BUF_LEN];
+    let n_digits = format_price(price, decimals as usize, &mut digits);
+
+    let mut out = [0u8; RENDER_BUF_LEN];
+    let mut o = 0;
+    let mut i = 0;
+    while i < len {
+        if src[i..len].starts_with(PRICE_PLACEHOLDER) && o + n_digits <= RENDER_BUF_LEN {
+            for d in digits[..n_digits].iter() {
+                out[o] = *d;
+                o += 1;
+            }
+            i += PRICE_PLACEHOLDER.len();
+        } else if o < RENDER_BUF_LEN {
+            out[o] = src[i];
+            o += 1;
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    Ok(String::from_bytes(e, &out[..o]))
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Register a recurring market template (Admin only). Spawned markets
+    /// name the registering admin as their creator.
+    pub fn create_template(e: Env, admin: Address, template: MarketTemplate) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+        validate_template(&template)?;
+        market_limits(&e, &template.config)?;
+
+        let id: u32 = e
+            .storage()
+            .instance()
+            .get(&DataKey::NextTemplateId)
+            .unwrap_or(0);
+        e.storage()
+            .instance()
+            .set(&DataKey::NextTemplateId, &(id + 1));
+
+        let state = TemplateState {
+            active: true,
+            next_period_ts: template.start_ts,
+            spawned: 0,
+            tip_balance: 0,
+            creator: admin,
+        };
+        e.storage()
+            .persistent()
+            .set(&DataKey::Template(id), &template);
+        write_template_state(&e, id, &state);
+
+        events::template_updated(&e, id, &state);
+        Ok(id)
+    }
+
+    /// Pause or resume spawning from a template (Admin only)
+    pub fn set_template_active(
+        e: Env,
+        admin: Address,
+        template_id: u32,
+        active: bool,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        require_role(&e, &admin, Role::Admin)?;
+
+        let mut state = read_template_state(&e, template_id)?;
+        state.active = active;
+        write_template_state(&e, template_id, &state);
+
+        events::template_updated(&e, template_id, &state);
+        Ok(())
+    }
+
+    /// Deposit KALE that pays keeper tips for a template
+    pub fn fund_template(
+        e: Env,
+        from: Address,
+        template_id: u32,
+        amount: i128,
+    ) -> Result<(), Error> {
+        require_initialized(&e)?;
+        from.require_auth();
+        validate_amount(amount)?;
+
+        let mut state = read_template_state(&e, template_id)?;
+        let token_addr = read_address(&e, &DataKey::KaleToken)?;
+        token_client(&e, &token_addr).transfer(&from, &e.current_contract_address(), &amount);
+
+        state.tip_balance += amount;
+        write_template_state(&e, template_id, &state);
+
+        events::template_updated(&e, template_id, &state);
+        Ok(())
+    }
+
+    /// Create the current period's market from a template. Anyone can call
+    /// this once the previous period has ended; the caller earns the keeper
+    /// tip while the template's tip balance lasts.
+    pub fn spawn_from_template(e: Env, keeper: Address, template_id: u32) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        keeper.require_auth();
+        require_not_paused(&e)?;
+
+        let template = read_template(&e, template_id)?;
+        let mut state = read_template_state(&e, template_id)?;
+        if !state.active {
+            return Err(Error::TemplateInactive);
+        }
+        let t = now(&e);
+        if t < state.next_period_ts {
+            return Err(Error::TemplateNotDue);
+        }
+
+        // Skip periods nobody spawned rather than backfilling them
+        let missed = (t - state.next_period_ts) / template.cadence;
+        let period_start = state.next_period_ts + missed * template.cadence;
+        let close_ts = period_start + template.close_offset;
+        if close_ts <= t {
+            return Err(Error::TemplateNotDue);
+        }
+
+        let oracle_addr = market_oracle(&e, &template.config)?;
+        let oracle = ReflectorClient::new(&e, &oracle_addr);
+        let spot = oracle
+            .lastprice(&ReflectorAsset::Other(template.asset.clone()))
+            .ok_or(Error::OraclePriceUnavailable)?;
+        if spot.timestamp.saturating_add(MAX_SPOT_PRICE_AGE) < t {
+            return Err(Error::OraclePriceStale);
+        }
+        // Kept in oracle decimals so sub-dollar assets don't round to zero
+        let strike = spot
+            .price
+            .checked_mul(10_000 + template.price_offset_bps as i128)
+            .and_then(|scaled| scaled.checked_div(10_000))
+            .ok_or(Error::InvalidAmount)?;
+
+        let market = Market {
+            id: 0,
+            question: render_question(&e, &template.question, strike, oracle.decimals())?,
+            creator: state.creator.clone(),
+            close_ts,
+            resolution_ts: period_start + template.resolution_offset,
+            resolved: false,
+            outcome: Outcome::Invalid,
+            yes_pool: 0,
+            no_pool: 0,
+            yes_weight: 0,
+            no_weight: 0,
+            created_ts: t,
+            limits: market_limits(&e, &template.config)?,
+            config: template.config,
+            bond: 0,
+            committed: 0,
+        };
+        let market_id = insert_market(&e, market, template.metadata)?;
+
+        let prev_market_id = match state.spawned {
+            0 => None,
+            n => read_template_market(&e, template_id, n - 1),
+        };
+        let instance = TemplateInstance {
+            template_id,
+            sequence: state.spawned,
+            period_start,
+            spot_price: spot.price,
+            strike_price: strike,
+            prev_market_id,
+        };
+        e.storage()
+            .persistent()
+            .set(&DataKey::MarketTemplate(market_id), &instance);
+        // Spawned markets settle themselves from the oracle
+        write_price_condition(
+            &e,
+            market_id,
+            &PriceCondition {
+                asset: template.asset,
+                strike: instance.strike_price,
+            },
+        );
+        e.storage().persistent().set(
+            &DataKey::TemplateMarkets(template_id, state.spawned),
+            &market_id,
+        );
+
+        let tip = template.keeper_tip.min(state.tip_balance);
+        state.next_period_ts = period_start + template.cadence;
+        state.spawned += 1;
+        state.tip_balance -= tip;
+        write_template_state(&e, template_id, &state);
+
+        if tip > 0 {
+            let token_addr = read_address(&e, &DataKey::KaleToken)?;
+            token_client(&e, &token_addr).transfer(&e.current_contract_address(), &keeper, &tip);
+        }
+
+        events::market_spawned(&e, template_id, market_id, keeper, tip);
+        Ok(market_id)
+    }
+
+    pub fn get_template(e: Env, template_id: u32) -> Result<MarketTemplate, Error> {
+        read_template(&e, template_id)
+    }
+
+    pub fn get_template_state(e: Env, template_id: u32) -> Result<TemplateState, Error> {
+        read_template_state(&e, template_id)
+    }
+
+    /// Template and sequence a market was spawned from, if any
+    pub fn get_template_instance(e: Env, market_id: u32) -> Option<TemplateInstance> {
+        e.storage()
+            .persistent()
+            .get(&DataKey::MarketTemplate(market_id))
+    }
+
+    /// Page through a template's markets, oldest first
+    pub fn list_template_markets(e: Env, template_id: u32, start: u32, limit: u32) -> Vec<Market> {
+        let spawned = read_template_state(&e, template_id).map_or(0, |s| s.spawned);
+        let end = spawned.min(start.saturating_add(limit));
+        let mut markets = Vec::new(&e);
+        for n in start..end {
+            let id = read_template_market(&e, template_id, n);
+            if let Some(m) = id.and_then(|id| read_market(&e, id).ok()) {
+                markets.push_back(m);
+            }
+        }
+        markets
+    }
+}
@@ -1,19 +1,125 @@
 #![cfg(test)]
 
 use super::*;
+use dispute::DisputeConfig;
+use keeper::{
+    BountyConfig, KeeperAction, PriceCondition, MARKET_TTL_EXTEND_TO, MARKET_TTL_THRESHOLD,
+    MIN_INVALIDATE_GRACE,
+};
+use mock_reflector::{MockReflector, MockReflectorClient};
+use orderbook::MAX_ORDERS_PER_SIDE;
+use parlay::{ParlayPick, ODDS_SCALE};
+use reflector::{Asset as ReflectorAsset, PriceData};
+use seasons::{RankMetric, SeasonConfig, SeasonStats, Standing};
 use soroban_sdk::{
-    testutils::{Address as _, Ledger},
-    token, Address, Env, String,
+    symbol_short,
+    testutils::{storage::Persistent as _, Address as _, Events, Ledger},
+    token, Address, BytesN, Env, IntoVal, String, Val, Vec,
 };
+use staking::{ResolverRecord, ResolverStakeConfig};
+use templates::{MarketTemplate, MAX_PRICE_OFFSET_BPS};
+use timelock::{TimelockAction, MAX_TIMELOCK_DELAY, TIMELOCK_GRACE_SECS};
 
 // Helper function to set up KALE SAC for testing
-fn setup_kale_sac(e: &Env) -> (Address, token::StellarAssetClient) {
+fn setup_kale_sac(e: &Env) -> (Address, token::StellarAssetClient<'_>) {
     let admin = Address::generate(e);
     let kale_sac = e.register_stellar_asset_contract_v2(admin.clone());
     let kale_admin = token::StellarAssetClient::new(e, &kale_sac.address());
     (kale_sac.address(), kale_admin)
 }
 
+/// Events the market contract published during the last invocation
+fn market_events(e: &Env, contract_id: &Address) -> Vec<(Vec<Val>, Val)> {
+    let mut out = Vec::new(e);
+    for (addr, topics, data) in e.events().all().iter() {
+        if &addr == contract_id {
+            out.push_back((topics, data));
+        }
+    }
+    out
+}
+
+/// Register a mock oracle and point the market contract at it
+fn setup_mock_oracle<'a>(
+    e: &Env,
+    client: &FarmersMarketClient,
+    admin: &Address,
+) -> MockReflectorClient<'a> {
+    let oracle_id = e.register(MockReflector, ());
+    run_timelocked(client, admin, TimelockAction::SetOracle(oracle_id.clone()));
+    MockReflectorClient::new(e, &oracle_id)
+}
+
+/// Schedule and execute a governance action under the default zero delay
+fn run_timelocked(client: &FarmersMarketClient, proposer: &Address, action: TimelockAction) {
+    let action_id = client.schedule_action(proposer, &action);
+    client.execute_action(&action_id);
+}
+
+fn ticker(e: &Env, symbol: &str) -> ReflectorAsset {
+    ReflectorAsset::Other(Symbol::new(e, symbol))
+}
+
+/// Price series from (price, timestamp) pairs, oldest first
+fn price_series(e: &Env, points: &[(i128, u64)]) -> Vec<PriceData> {
+    let mut series = Vec::new(e);
+    for (price, timestamp) in points {
+        series.push_back(PriceData {
+            price: *price,
+            timestamp: *timestamp,
+        });
+    }
+    series
+}
+
+fn picks(e: &Env, legs: &[(u32, bool)]) -> Vec<ParlayPick> {
+    let mut out = Vec::new(e);
+    for (market_id, side_yes) in legs {
+        out.push_back(ParlayPick {
+            market_id: *market_id,
+            side_yes: *side_yes,
+        });
+    }
+    out
+}
+
+// USD amount with Reflector's default 14 decimals
+const USD: i128 = 100_000_000_000_000;
+
+fn default_config() -> MarketConfig {
+    MarketConfig {
+        no_winner_policy: NoWinnerPolicy::Refund,
+        bet_limits: BetLimitsChoice::Default,
+        subsidy: 0,
+        early_bonus_bps: 0,
+        commit_reveal: CommitRevealMode::Off,
+        resolver: ResolverChoice::Default,
+    }
+}
+
+fn default_metadata(e: &Env) -> MarketMetadata {
+    MarketMetadata {
+        description: String::from_str(e, "Resolves YES if it rains in San Francisco."),
+        category: Category::Weather,
+        tags: Vec::new(e),
+        resolution_source: String::from_str(e, "https://weather.gov"),
+        content_hash: None,
+    }
+}
+
+fn create_test_market(e: &Env, client: &FarmersMarketClient, creator: &Address) -> u32 {
+    let question = String::from_str(e, "Will it rain tomorrow?");
+    let now = e.ledger().timestamp();
+    client.create_market(
+        creator,
+        &question,
+        &(now + 3600),
+        &(now + 7200),
+        &default_config(),
+        &default_metadata(e),
+    )
+}
+
 fn setup_test_market(
     e: &Env,
     client: &FarmersMarketClient,
@@ -31,7 +137,14 @@ fn setup_test_market(
     let close_ts = now + 3600; // 1 hour from now
     let resolution_ts = now + 7200; // 2 hours from now
 
-    client.create_market(creator, &question, &close_ts, &resolution_ts)
+    client.create_market(
+        creator,
+        &question,
+        &close_ts,
+        &resolution_ts,
+        &default_config(),
+        &default_metadata(e),
+    )
 }
 
 #[test]
@@ -49,8 +162,9 @@ fn test_init_contract() {
     client.init(&admin, &resolver, &kale_sac);
 
     // Verify initialization
-    assert_eq!(client.get_admin(), admin);
-    assert_eq!(client.get_resolver(), resolver);
+    assert!(client.has_role(&Role::Admin, &admin));
+    assert!(client.has_role(&Role::Resolver, &resolver));
+    assert!(!client.has_role(&Role::Resolver, &admin));
     assert_eq!(client.get_kale_token(), kale_sac);
 }
 
@@ -119,6 +233,7 @@ fn test_bet_functionality() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)]
 fn test_resolve_and_claim() {
     let env = Env::default();
     let admin = Address::generate(&env);
@@ -146,7 +261,7 @@ fn test_resolve_and_claim() {
     });
 
     // Resolve market as YES
-    client.resolve(&market_id, &Outcome::Yes);
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
 
     // Verify market is resolved
     let market = client.get_market(&market_id);
@@ -198,6 +313,7 @@ fn test_odds_calculation() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)]
 fn test_invalid_operations() {
     let env = Env::default();
     let admin = Address::generate(&env);
@@ -227,7 +343,7 @@ fn test_invalid_operations() {
     });
 
     // Resolve and claim should work
-    client.resolve(&market_id, &Outcome::Yes);
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
     let _payout = client.claim(&bettor, &market_id);
 }
 
@@ -280,6 +396,7 @@ fn test_kale_integration_functions() {
 }
 
 #[test]
+#[allow(clippy::inconsistent_digit_grouping, clippy::assertions_on_constants)]
 fn test_reflector_integration_functions_exist() {
     // This test verifies that all Reflector oracle functions compile and are exported
     let env = Env::default();
@@ -314,3 +431,3677 @@ fn test_reflector_integration_functions_exist() {
 
     assert!(true); // Test passes if we get here without compilation errors
 }
+
+#[test]
+fn test_single_sided_market_refunds() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor1 = Address::generate(&env);
+    let bettor2 = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+
+    // Everyone bets NO, then the market resolves YES
+    kale_admin.mint(&bettor1, &1000);
+    kale_admin.mint(&bettor2, &3000);
+    client.bet(&bettor1, &market_id, &false, &1000);
+    client.bet(&bettor2, &market_id, &false, &3000);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+
+    // Refund policy: the stranded NO pool goes back to its stakers
+    assert_eq!(client.claim(&bettor1, &market_id), 1000);
+    assert_eq!(client.claim(&bettor2, &market_id), 3000);
+    assert_eq!(client.get_total_locked_kale(), 0);
+    assert_eq!(
+        client.try_claim(&bettor1, &market_id),
+        Err(Ok(Error::NothingToClaim))
+    );
+}
+
+#[test]
+fn test_single_sided_market_sweeps_to_treasury() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    assert_eq!(client.get_treasury(), admin);
+    client.set_treasury(&admin, &treasury);
+    assert_eq!(client.get_treasury(), treasury);
+
+    let now = env.ledger().timestamp();
+    let config = MarketConfig {
+        no_winner_policy: NoWinnerPolicy::Treasury,
+        bet_limits: BetLimitsChoice::Default,
+        subsidy: 0,
+        early_bonus_bps: 0,
+        commit_reveal: CommitRevealMode::Off,
+        resolver: ResolverChoice::Default,
+    };
+    let market_id = client.create_market(
+        &creator,
+        &String::from_str(&env, "Will it rain tomorrow?"),
+        &(now + 3600),
+        &(now + 7200),
+        &config,
+        &default_metadata(&env),
+    );
+
+    kale_admin.mint(&bettor, &2000);
+    client.bet(&bettor, &market_id, &true, &2000);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::No);
+
+    // Treasury policy: the whole pool is swept on resolve
+    assert_eq!(kale.balance(&treasury), 2000);
+    assert_eq!(client.get_total_locked_kale(), 0);
+    assert_eq!(
+        client.try_claim(&bettor, &market_id),
+        Err(Ok(Error::NothingToClaim))
+    );
+}
+
+#[test]
+fn test_empty_market_resolution() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let user = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+
+    let now = env.ledger().timestamp();
+    let question = String::from_str(&env, "Will it rain tomorrow?");
+    let refund_id = client.create_market(
+        &creator,
+        &question,
+        &(now + 3600),
+        &(now + 7200),
+        &default_config(),
+        &default_metadata(&env),
+    );
+    let treasury_id = client.create_market(
+        &creator,
+        &question,
+        &(now + 3600),
+        &(now + 7200),
+        &MarketConfig {
+            no_winner_policy: NoWinnerPolicy::Treasury,
+            bet_limits: BetLimitsChoice::Default,
+            subsidy: 0,
+            early_bonus_bps: 0,
+            commit_reveal: CommitRevealMode::Off,
+            resolver: ResolverChoice::Default,
+        },
+        &default_metadata(&env),
+    );
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+
+    // Nothing staked: resolving works under either policy and moves no KALE
+    client.resolve(&resolver, &refund_id, &Outcome::Yes);
+    client.resolve(&resolver, &treasury_id, &Outcome::No);
+    assert_eq!(kale.balance(&admin), 0);
+    assert_eq!(
+        client.try_claim(&user, &refund_id),
+        Err(Ok(Error::NothingToClaim))
+    );
+    assert_eq!(
+        client.try_claim(&user, &treasury_id),
+        Err(Ok(Error::NothingToClaim))
+    );
+}
+
+#[test]
+fn test_creation_bond_returned_on_resolve() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_creation_policy(
+        &admin,
+        &CreationPolicy {
+            bond: 500,
+            allowlist_enabled: false,
+            max_per_window: 0,
+            window_secs: 0,
+        },
+    );
+
+    kale_admin.mint(&creator, &500);
+    let market_id = create_test_market(&env, &client, &creator);
+
+    // Bond is locked in the contract while the market is open
+    assert_eq!(kale.balance(&creator), 0);
+    assert_eq!(client.get_market(&market_id).bond, 500);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::No);
+
+    assert_eq!(kale.balance(&creator), 500);
+    assert_eq!(client.get_market(&market_id).bond, 0);
+}
+
+#[test]
+fn test_creation_bond_waits_for_dispute_window() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let disputer = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_creation_policy(
+        &admin,
+        &CreationPolicy {
+            bond: 500,
+            allowlist_enabled: false,
+            max_per_window: 0,
+            window_secs: 0,
+        },
+    );
+    client.set_dispute_config(
+        &admin,
+        &DisputeConfig {
+            bond: 100,
+            window_secs: 3600,
+            voting_secs: 7200,
+        },
+    );
+
+    kale_admin.mint(&creator, &1_000);
+    kale_admin.mint(&disputer, &100);
+    let quiet = create_test_market(&env, &client, &creator);
+    let disputed = create_test_market(&env, &client, &creator);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &quiet, &Outcome::No);
+    client.resolve(&resolver, &disputed, &Outcome::No);
+
+    // The bond stays locked while the outcome can still be disputed
+    assert_eq!(kale.balance(&creator), 0);
+    assert_eq!(client.get_market(&quiet).bond, 500);
+    assert_eq!(
+        client.try_release_bond(&quiet),
+        Err(Ok(Error::DisputePending))
+    );
+    client.dispute_outcome(&disputer, &disputed, &Outcome::Yes);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3600;
+    });
+    assert_eq!(client.release_bond(&quiet), 500);
+    assert_eq!(kale.balance(&creator), 500);
+    assert_eq!(
+        client.try_release_bond(&quiet),
+        Err(Ok(Error::NothingToClaim))
+    );
+
+    // A ballot keeps it locked until it settles
+    assert_eq!(
+        client.try_release_bond(&disputed),
+        Err(Ok(Error::DisputePending))
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7200;
+    });
+    client.settle_dispute(&disputed);
+    assert_eq!(kale.balance(&creator), 1_000);
+    assert_eq!(client.get_market(&disputed).bond, 0);
+}
+
+#[test]
+fn test_cancel_market_slashes_bond() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_treasury(&admin, &treasury);
+    client.set_creation_policy(
+        &admin,
+        &CreationPolicy {
+            bond: 500,
+            allowlist_enabled: false,
+            max_per_window: 0,
+            window_secs: 0,
+        },
+    );
+
+    kale_admin.mint(&creator, &1000);
+    kale_admin.mint(&bettor, &1000);
+    let market_id = create_test_market(&env, &client, &creator);
+    let ambiguous = create_test_market(&env, &client, &creator);
+    client.bet(&bettor, &market_id, &true, &600);
+
+    client.cancel_market(&admin, &market_id, &String::from_str(&env, "spam"), &true);
+
+    // Bond is slashed, bets are rejected and stakers get refunded
+    assert_eq!(kale.balance(&treasury), 500);
+    assert_eq!(kale.balance(&creator), 0);
+    assert_eq!(
+        client.try_bet(&bettor, &market_id, &true, &100),
+        Err(Ok(Error::MarketClosed))
+    );
+    assert_eq!(client.claim(&bettor, &market_id), 600);
+    assert_eq!(kale.balance(&bettor), 1000);
+    assert_eq!(
+        client.try_cancel_market(&admin, &market_id, &String::from_str(&env, "spam"), &true),
+        Err(Ok(Error::AlreadyResolved))
+    );
+
+    // An honest but ambiguous market is voided without slashing
+    let reason = String::from_str(&env, "Ambiguous: which city?");
+    client.cancel_market(&admin, &ambiguous, &reason, &false);
+    let evs = market_events(&env, &contract_id);
+    let ev: events::MarketCancelled = evs.get_unchecked(0).1.into_val(&env);
+    assert_eq!(ev.bond_slashed, 0);
+    let (topics, _) = evs.last_unchecked();
+    assert_eq!(
+        topics,
+        (symbol_short!("BondBack"), ambiguous, creator.clone()).into_val(&env)
+    );
+    assert_eq!(kale.balance(&creator), 500);
+    assert_eq!(kale.balance(&treasury), 500);
+}
+
+#[test]
+fn test_creator_allowlist() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_creation_policy(
+        &admin,
+        &CreationPolicy {
+            bond: 0,
+            allowlist_enabled: true,
+            max_per_window: 0,
+            window_secs: 0,
+        },
+    );
+
+    let question = String::from_str(&env, "Will it rain tomorrow?");
+    let now = env.ledger().timestamp();
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &default_config(),
+            &default_metadata(&env)
+        ),
+        Err(Ok(Error::CreatorNotAllowed))
+    );
+
+    client.set_creator_allowed(&admin, &creator, &true);
+    assert!(client.is_creator_allowed(&creator));
+    create_test_market(&env, &client, &creator);
+
+    client.set_creator_allowed(&admin, &creator, &false);
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &default_config(),
+            &default_metadata(&env)
+        ),
+        Err(Ok(Error::CreatorNotAllowed))
+    );
+}
+
+#[test]
+fn test_creator_rate_limit() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let other = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+
+    // A limit without a window is rejected
+    let mut policy = CreationPolicy {
+        bond: 0,
+        allowlist_enabled: false,
+        max_per_window: 2,
+        window_secs: 0,
+    };
+    assert_eq!(
+        client.try_set_creation_policy(&admin, &policy),
+        Err(Ok(Error::InvalidTime))
+    );
+    policy.window_secs = 86400;
+    client.set_creation_policy(&admin, &policy);
+    assert_eq!(client.get_creation_policy(), policy);
+
+    create_test_market(&env, &client, &creator);
+    create_test_market(&env, &client, &creator);
+
+    let question = String::from_str(&env, "Will it rain tomorrow?");
+    let now = env.ledger().timestamp();
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &default_config(),
+            &default_metadata(&env)
+        ),
+        Err(Ok(Error::CreationRateLimited))
+    );
+
+    // Limits are per creator
+    create_test_market(&env, &client, &other);
+
+    // A new window resets the count
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 86400;
+    });
+    create_test_market(&env, &client, &creator);
+}
+
+#[test]
+fn test_cancel_market_refunds_all_stakers() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor1 = Address::generate(&env);
+    let bettor2 = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    assert_eq!(client.get_cancel_reason(&market_id), None);
+
+    kale_admin.mint(&bettor1, &1000);
+    kale_admin.mint(&bettor2, &1000);
+    client.bet(&bettor1, &market_id, &true, &400);
+    client.bet(&bettor1, &market_id, &false, &100);
+    client.bet(&bettor2, &market_id, &false, &700);
+
+    // Cancelling still works once betting has closed
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3700;
+    });
+    let reason = String::from_str(&env, "Ambiguous: which city?");
+    client.cancel_market(&admin, &market_id, &reason, &false);
+
+    let market = client.get_market(&market_id);
+    assert!(market.resolved);
+    assert_eq!(market.outcome, Outcome::Invalid);
+    assert_eq!(client.get_cancel_reason(&market_id), Some(reason));
+
+    // The resolver can no longer pick an outcome
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3600;
+    });
+    assert_eq!(
+        client.try_resolve(&resolver, &market_id, &Outcome::Yes),
+        Err(Ok(Error::AlreadyResolved))
+    );
+
+    assert_eq!(client.claim(&bettor1, &market_id), 500);
+    assert_eq!(client.claim(&bettor2, &market_id), 700);
+    assert_eq!(kale.balance(&bettor1), 1000);
+    assert_eq!(kale.balance(&bettor2), 1000);
+    assert_eq!(client.get_total_locked_kale(), 0);
+}
+
+#[test]
+fn test_market_metadata() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+
+    let mut metadata = MarketMetadata {
+        description: String::from_str(&env, "Resolves on the Reflector close price."),
+        category: Category::Crypto,
+        tags: Vec::from_array(
+            &env,
+            [
+                String::from_str(&env, "btc"),
+                String::from_str(&env, "price"),
+            ],
+        ),
+        resolution_source: String::from_str(&env, "https://reflector.network"),
+        content_hash: Some(String::from_str(
+            &env,
+            "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+        )),
+    };
+    let now = env.ledger().timestamp();
+    let question = String::from_str(&env, "Will BTC close above 100k?");
+    let market_id = client.create_market(
+        &creator,
+        &question,
+        &(now + 3600),
+        &(now + 7200),
+        &default_config(),
+        &metadata,
+    );
+    assert_eq!(client.get_metadata(&market_id), metadata);
+
+    // Length limits are enforced
+    metadata.tags = Vec::from_array(
+        &env,
+        [
+            String::from_str(&env, "a"),
+            String::from_str(&env, "b"),
+            String::from_str(&env, "c"),
+            String::from_str(&env, "d"),
+            String::from_str(&env, "e"),
+            String::from_str(&env, "f"),
+        ],
+    );
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &default_config(),
+            &metadata,
+        ),
+        Err(Ok(Error::TooManyTags))
+    );
+    metadata.tags = Vec::from_array(
+        &env,
+        [String::from_str(
+            &env,
+            "this-tag-is-far-too-long-to-be-stored-on-chain",
+        )],
+    );
+    assert_eq!(
+        client.try_update_metadata(&market_id, &metadata),
+        Err(Ok(Error::MetadataTooLong))
+    );
+
+    let long_question = String::from_bytes(&env, &[b'?'; 257]);
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &long_question,
+            &(now + 3600),
+            &(now + 7200),
+            &default_config(),
+            &default_metadata(&env),
+        ),
+        Err(Ok(Error::MetadataTooLong))
+    );
+}
+
+#[test]
+fn test_list_markets_by_category() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+
+    let weather1 = create_test_market(&env, &client, &creator);
+    let weather2 = create_test_market(&env, &client, &creator);
+    let weather3 = create_test_market(&env, &client, &creator);
+
+    let weather = client.list_markets_by_category(&Category::Weather, &0, &10);
+    assert_eq!(weather.len(), 3);
+    assert_eq!(weather.get_unchecked(0).id, weather1);
+    assert_eq!(weather.get_unchecked(2).id, weather3);
+
+    // Pagination
+    let page = client.list_markets_by_category(&Category::Weather, &1, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get_unchecked(0).id, weather2);
+    assert_eq!(
+        client
+            .list_markets_by_category(&Category::Weather, &5, &10)
+            .len(),
+        0
+    );
+    assert_eq!(
+        client
+            .list_markets_by_category(&Category::Farming, &0, &10)
+            .len(),
+        0
+    );
+
+    // Changing category moves the market between listings
+    let mut metadata = default_metadata(&env);
+    metadata.category = Category::Farming;
+    client.update_metadata(&weather2, &metadata);
+
+    let farming = client.list_markets_by_category(&Category::Farming, &0, &10);
+    assert_eq!(farming.len(), 1);
+    assert_eq!(farming.get_unchecked(0).id, weather2);
+    let weather = client.list_markets_by_category(&Category::Weather, &0, &10);
+    assert_eq!(weather.len(), 2);
+    assert_eq!(weather.get_unchecked(1).id, weather3);
+    // The moved market leaves a gap rather than shifting later entries
+    assert_eq!(client.get_category_count(&Category::Weather), 3);
+    assert_eq!(client.get_category_count(&Category::Farming), 1);
+    let page = client.list_markets_by_category(&Category::Weather, &1, &1);
+    assert_eq!(page.len(), 0);
+}
+
+#[test]
+fn test_update_metadata_rules() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+
+    let mut metadata = default_metadata(&env);
+    metadata.description = String::from_str(&env, "Resolves YES on any measurable rain.");
+    client.update_metadata(&market_id, &metadata);
+
+    // Only the creator can sign for the update
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, creator);
+    assert_eq!(client.get_metadata(&market_id), metadata);
+
+    // Metadata is frozen once someone has bet
+    kale_admin.mint(&bettor, &100);
+    client.bet(&bettor, &market_id, &true, &100);
+    assert_eq!(
+        client.try_update_metadata(&market_id, &default_metadata(&env)),
+        Err(Ok(Error::MetadataLocked))
+    );
+}
+
+#[test]
+fn test_default_bet_limits() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let whale = Address::generate(&env);
+    let minnow = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+
+    let limits = BetLimits {
+        min_bet: 10,
+        max_bet: 500,
+        max_user_stake: 800,
+        max_pool: 1000,
+    };
+    assert_eq!(
+        client.try_set_default_bet_limits(
+            &admin,
+            &BetLimits {
+                min_bet: 600,
+                ..limits.clone()
+            }
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+    client.set_default_bet_limits(&admin, &limits);
+    assert_eq!(client.get_default_bet_limits(), limits);
+
+    // Markets without their own limits snapshot the defaults
+    let market_id = create_test_market(&env, &client, &creator);
+    assert_eq!(client.get_market(&market_id).limits, limits);
+
+    kale_admin.mint(&whale, &5000);
+    kale_admin.mint(&minnow, &5000);
+
+    assert_eq!(
+        client.try_bet(&minnow, &market_id, &true, &9),
+        Err(Ok(Error::BetTooSmall))
+    );
+    assert_eq!(
+        client.try_bet(&whale, &market_id, &true, &501),
+        Err(Ok(Error::BetTooLarge))
+    );
+
+    client.bet(&whale, &market_id, &true, &500);
+    assert_eq!(
+        client.try_bet(&whale, &market_id, &false, &301),
+        Err(Ok(Error::UserStakeCapExceeded))
+    );
+    client.bet(&whale, &market_id, &false, &300);
+
+    assert_eq!(
+        client.try_bet(&minnow, &market_id, &false, &201),
+        Err(Ok(Error::PoolCapExceeded))
+    );
+    client.bet(&minnow, &market_id, &false, &200);
+
+    let market = client.get_market(&market_id);
+    assert_eq!(market.yes_pool + market.no_pool, 1000);
+}
+
+#[test]
+fn test_market_bet_limits_override_defaults() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_default_bet_limits(
+        &admin,
+        &BetLimits {
+            min_bet: 2,
+            max_bet: 100,
+            max_user_stake: 0,
+            max_pool: 0,
+        },
+    );
+
+    let limits = BetLimits {
+        min_bet: 5,
+        max_bet: 50,
+        max_user_stake: 0,
+        max_pool: 0,
+    };
+    let now = env.ledger().timestamp();
+    let market_id = client.create_market(
+        &creator,
+        &String::from_str(&env, "Will the farm stall sell out?"),
+        &(now + 3600),
+        &(now + 7200),
+        &MarketConfig {
+            no_winner_policy: NoWinnerPolicy::Refund,
+            bet_limits: BetLimitsChoice::Custom(limits.clone()),
+            subsidy: 0,
+            early_bonus_bps: 0,
+            commit_reveal: CommitRevealMode::Off,
+            resolver: ResolverChoice::Default,
+        },
+        &default_metadata(&env),
+    );
+    assert_eq!(client.get_market(&market_id).limits, limits);
+
+    kale_admin.mint(&bettor, &1000);
+    client.bet(&bettor, &market_id, &true, &5);
+    assert_eq!(
+        client.try_bet(&bettor, &market_id, &true, &51),
+        Err(Ok(Error::BetTooLarge))
+    );
+
+    // Invalid per-market limits are rejected at creation
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &String::from_str(&env, "Will the farm stall sell out?"),
+            &(now + 3600),
+            &(now + 7200),
+            &MarketConfig {
+                no_winner_policy: NoWinnerPolicy::Refund,
+                bet_limits: BetLimitsChoice::Custom(BetLimits {
+                    min_bet: -1,
+                    ..limits
+                }),
+                subsidy: 0,
+                early_bonus_bps: 0,
+                commit_reveal: CommitRevealMode::Off,
+                resolver: ResolverChoice::Default,
+            },
+            &default_metadata(&env),
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    // Custom limits can't be looser than the Admin's
+    for loose in [
+        BetLimits {
+            min_bet: 1,
+            ..limits.clone()
+        },
+        BetLimits {
+            max_bet: 0,
+            ..limits.clone()
+        },
+        BetLimits {
+            max_bet: 101,
+            ..limits.clone()
+        },
+    ] {
+        assert_eq!(
+            client.try_create_market(
+                &creator,
+                &String::from_str(&env, "Will the farm stall sell out?"),
+                &(now + 3600),
+                &(now + 7200),
+                &MarketConfig {
+                    bet_limits: BetLimitsChoice::Custom(loose),
+                    ..default_config()
+                },
+                &default_metadata(&env),
+            ),
+            Err(Ok(Error::InvalidAmount))
+        );
+    }
+}
+
+#[test]
+fn test_market_lifecycle_events() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor1 = Address::generate(&env);
+    let bettor2 = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 1_000;
+    });
+
+    client.init(&admin, &resolver, &kale_sac);
+    // One grant per initial role, then Init
+    let evs = market_events(&env, &contract_id);
+    assert_eq!(evs.len(), 7);
+    let (topics, _) = evs.get_unchecked(5);
+    assert_eq!(
+        topics,
+        (symbol_short!("RoleGrant"), Role::Resolver, resolver.clone()).into_val(&env)
+    );
+    let (topics, data) = evs.get_unchecked(6);
+    assert_eq!(topics, (symbol_short!("Init"),).into_val(&env));
+    let init: events::Initialized = data.into_val(&env);
+    assert_eq!(init.version, events::EVENT_VERSION);
+    assert_eq!(init.admin, admin);
+    assert_eq!(init.kale_token, kale_sac);
+
+    let market_id = create_test_market(&env, &client, &creator);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Created"), market_id, creator.clone()).into_val(&env)
+    );
+    let created: events::MarketCreated = data.into_val(&env);
+    assert_eq!(
+        created,
+        events::MarketCreated {
+            version: events::EVENT_VERSION,
+            market_id,
+            creator: creator.clone(),
+            question: String::from_str(&env, "Will it rain tomorrow?"),
+            category: Category::Weather,
+            close_ts: 4_600,
+            resolution_ts: 8_200,
+            bond: 0,
+            timestamp: 1_000,
+        }
+    );
+
+    kale_admin.mint(&bettor1, &1000);
+    kale_admin.mint(&bettor2, &1000);
+    client.bet(&bettor1, &market_id, &true, &300);
+    client.bet(&bettor2, &market_id, &false, &100);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Bet"), market_id, bettor2.clone()).into_val(&env)
+    );
+    let bet: events::BetPlaced = data.into_val(&env);
+    assert_eq!(
+        bet,
+        events::BetPlaced {
+            version: events::EVENT_VERSION,
+            market_id,
+            user: bettor2.clone(),
+            side_yes: false,
+            amount: 100,
+            user_yes: 0,
+            user_no: 100,
+            yes_pool: 300,
+            no_pool: 100,
+            timestamp: 1_000,
+        }
+    );
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 9_000;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Resolved"), market_id).into_val(&env)
+    );
+    let resolved: events::MarketResolved = data.into_val(&env);
+    assert_eq!(resolved.outcome, Outcome::Yes);
+    assert_eq!((resolved.yes_pool, resolved.no_pool), (300, 100));
+    assert_eq!(resolved.timestamp, 9_000);
+
+    client.claim(&bettor1, &market_id);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Claimed"), market_id, bettor1.clone()).into_val(&env)
+    );
+    let claimed: events::Claimed = data.into_val(&env);
+    assert_eq!(claimed.payout, 400);
+    assert_eq!(claimed.version, events::EVENT_VERSION);
+}
+
+#[test]
+fn test_admin_and_moderation_events() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+
+    client.set_treasury(&admin, &treasury);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(topics, (symbol_short!("Treasury"),).into_val(&env));
+    let ev: events::TreasuryUpdated = data.into_val(&env);
+    assert_eq!(ev.treasury, treasury);
+
+    let policy = CreationPolicy {
+        bond: 50,
+        allowlist_enabled: true,
+        max_per_window: 0,
+        window_secs: 0,
+    };
+    client.set_creation_policy(&admin, &policy);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(topics, (symbol_short!("Policy"),).into_val(&env));
+    let ev: events::CreationPolicyUpdated = data.into_val(&env);
+    assert_eq!(ev.policy, policy);
+
+    let limits = BetLimits {
+        min_bet: 1,
+        max_bet: 0,
+        max_user_stake: 0,
+        max_pool: 0,
+    };
+    client.set_default_bet_limits(&admin, &limits);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(topics, (symbol_short!("Limits"),).into_val(&env));
+    let ev: events::DefaultBetLimitsUpdated = data.into_val(&env);
+    assert_eq!(ev.limits, limits);
+
+    client.set_creator_allowed(&admin, &creator, &true);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Allowed"), creator.clone()).into_val(&env)
+    );
+    let ev: events::CreatorAllowed = data.into_val(&env);
+    assert!(ev.allowed);
+
+    kale_admin.mint(&creator, &50);
+    let market_id = create_test_market(&env, &client, &creator);
+
+    let mut metadata = default_metadata(&env);
+    metadata.category = Category::Farming;
+    client.update_metadata(&market_id, &metadata);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Metadata"), market_id).into_val(&env)
+    );
+    let ev: events::MetadataUpdated = data.into_val(&env);
+    assert_eq!(ev.category, Category::Farming);
+
+    let reason = String::from_str(&env, "duplicate market");
+    client.cancel_market(&admin, &market_id, &reason, &true);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(topics, (symbol_short!("Cancel"), market_id).into_val(&env));
+    let ev: events::MarketCancelled = data.into_val(&env);
+    assert_eq!(ev.reason, reason);
+    assert_eq!(ev.bond_slashed, 50);
+}
+
+#[test]
+fn test_settlement_side_events() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_creation_policy(
+        &admin,
+        &CreationPolicy {
+            bond: 25,
+            allowlist_enabled: false,
+            max_per_window: 0,
+            window_secs: 0,
+        },
+    );
+
+    kale_admin.mint(&creator, &25);
+    kale_admin.mint(&bettor, &100);
+    let now = env.ledger().timestamp();
+    let market_id = client.create_market(
+        &creator,
+        &String::from_str(&env, "Will it rain tomorrow?"),
+        &(now + 3600),
+        &(now + 7200),
+        &MarketConfig {
+            no_winner_policy: NoWinnerPolicy::Treasury,
+            bet_limits: BetLimitsChoice::Default,
+            subsidy: 0,
+            early_bonus_bps: 0,
+            commit_reveal: CommitRevealMode::Off,
+            resolver: ResolverChoice::Default,
+        },
+        &default_metadata(&env),
+    );
+    client.bet(&bettor, &market_id, &true, &100);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::No);
+
+    // Resolved, then the bond refund, then the sweep of the orphaned pool
+    let evs = market_events(&env, &contract_id);
+    assert_eq!(evs.len(), 3);
+    let (topics, _) = evs.get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Resolved"), market_id).into_val(&env)
+    );
+    let (topics, data) = evs.get_unchecked(1);
+    assert_eq!(
+        topics,
+        (symbol_short!("BondBack"), market_id, creator.clone()).into_val(&env)
+    );
+    let ev: events::BondReturned = data.into_val(&env);
+    assert_eq!(ev.amount, 25);
+    let (topics, data) = evs.get_unchecked(2);
+    assert_eq!(
+        topics,
+        (symbol_short!("Swept"), market_id, admin.clone()).into_val(&env)
+    );
+    let ev: events::PoolSwept = data.into_val(&env);
+    assert_eq!(ev.amount, 100);
+}
+
+#[test]
+fn test_oracle_functions_with_mock_reflector() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+    let oracle = setup_mock_oracle(&env, &client, &admin);
+
+    // The timelocked change publishes Executed, then the update itself
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(1);
+    assert_eq!(topics, (symbol_short!("Oracle"),).into_val(&env));
+    let ev: events::OracleUpdated = data.into_val(&env);
+    assert_eq!(ev.oracle, oracle.address);
+    assert_eq!(client.get_reflector_oracle(), oracle.address);
+
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(100_000 * USD, 600), (110_000 * USD, 900)]),
+    );
+    oracle.set_prices(
+        &ticker(&env, "ETH"),
+        &price_series(&env, &[(4_400 * USD, 900)]),
+    );
+
+    let btc = client.get_btc_price();
+    assert_eq!(btc.price, 110_000 * USD);
+    assert_eq!(btc.timestamp, 900);
+    assert_eq!(client.get_eth_price().price, 4_400 * USD);
+
+    assert!(client.is_btc_above_price(&105_000));
+    assert!(!client.is_btc_above_price(&120_000));
+    assert!(client.is_eth_above_price(&4_400));
+    assert!(!client.is_eth_above_price(&5_000));
+
+    // ETH/BTC = 0.04 with 14 decimals
+    assert_eq!(client.get_eth_btc_ratio(), 4 * USD / 100);
+
+    // Only two records exist, so the 5-period TWAP averages both
+    assert_eq!(client.get_btc_twap(), 105_000 * USD);
+
+    assert_eq!(client.get_oracle_info(), (14, 300, 900));
+    assert_eq!(client.format_price_to_usd(&(110_000 * USD)), 11_000_000);
+
+    assert_eq!(client.demo_btc_200k_resolution(), Outcome::No);
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(210_000 * USD, 1_200)]),
+    );
+    assert_eq!(client.demo_btc_200k_resolution(), Outcome::Yes);
+}
+
+#[test]
+fn test_oracle_missing_assets() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+    let oracle = setup_mock_oracle(&env, &client, &admin);
+
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(100_000 * USD, 600)]),
+    );
+
+    // ETH is not quoted
+    assert_eq!(
+        client.try_get_eth_price(),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+    assert_eq!(
+        client.try_is_eth_above_price(&1),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+    assert_eq!(
+        client.try_get_eth_btc_ratio(),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+
+    // Delisting BTC takes every BTC path down with it
+    oracle.remove_asset(&ticker(&env, "BTC"));
+    assert_eq!(oracle.assets().len(), 0);
+    assert_eq!(
+        client.try_get_btc_price(),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+    assert_eq!(
+        client.try_get_btc_twap(),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+    assert_eq!(
+        client.try_demo_btc_200k_resolution(),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+}
+
+#[test]
+fn test_oracle_decimals_and_stale_feed() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+    let oracle = setup_mock_oracle(&env, &client, &admin);
+
+    // An 8-decimal feed ticking every minute
+    oracle.configure(&8, &60);
+    let usd8: i128 = 100_000_000;
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(
+            &env,
+            &[
+                (90_000 * usd8, 60),
+                (95_000 * usd8, 120),
+                (100_000 * usd8, 180),
+                (105_000 * usd8, 240),
+                (110_000 * usd8, 300),
+                (115_000 * usd8, 360),
+            ],
+        ),
+    );
+    oracle.set_prices(
+        &ticker(&env, "ETH"),
+        &price_series(&env, &[(5_750 * usd8, 360)]),
+    );
+
+    assert!(client.is_btc_above_price(&115_000));
+    assert!(!client.is_btc_above_price(&115_001));
+    assert_eq!(client.format_price_to_usd(&(115_000 * usd8)), 11_500_000);
+    assert_eq!(client.get_eth_btc_ratio(), 5 * usd8 / 100);
+
+    // TWAP only looks at the newest five records
+    assert_eq!(client.get_btc_twap(), 105_000 * usd8);
+
+    // A feed that stopped updating still serves its last price; the
+    // timestamps are what reveal it is stale
+    oracle.set_last_timestamp(&360);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 86_400;
+    });
+    assert_eq!(client.get_oracle_info(), (8, 60, 360));
+    let btc = client.get_btc_price();
+    assert_eq!(btc.timestamp, 360);
+    assert!(env.ledger().timestamp() - btc.timestamp > 60);
+}
+
+#[test]
+fn test_parlay_all_legs_win() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let punter = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let btc = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    let eth = create_test_market(&env, &client, &creator);
+
+    // YES pays 4x on BTC and 2x on ETH
+    kale_admin.mint(&bettor, &800);
+    client.bet(&bettor, &btc, &true, &100);
+    client.bet(&bettor, &btc, &false, &300);
+    client.bet(&bettor, &eth, &true, &200);
+    client.bet(&bettor, &eth, &false, &200);
+
+    let ticket_picks = picks(&env, &[(btc, true), (eth, true)]);
+    assert_eq!(client.quote_parlay(&ticket_picks, &100), 800);
+
+    // Nothing to back the ticket yet
+    kale_admin.mint(&punter, &100);
+    assert_eq!(
+        client.try_place_parlay(&punter, &ticket_picks, &100, &800),
+        Err(Ok(Error::InsufficientHouseLiquidity))
+    );
+
+    kale_admin.mint(&admin, &1000);
+    client.fund_parlay_house(&admin, &1000);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(topics, (symbol_short!("House"),).into_val(&env));
+    let ev: events::ParlayHouseUpdated = data.into_val(&env);
+    assert_eq!((ev.amount, ev.balance), (1000, 1000));
+
+    // Odds can't have moved below what the punter was quoted
+    assert_eq!(
+        client.try_place_parlay(&punter, &ticket_picks, &100, &801),
+        Err(Ok(Error::ParlaySlippage))
+    );
+    let ticket_id = client.place_parlay(&punter, &ticket_picks, &100, &800);
+
+    let ticket = client.get_parlay(&ticket_id);
+    assert_eq!(ticket.potential_payout, 800);
+    assert_eq!(ticket.legs.get_unchecked(0).odds, 4 * ODDS_SCALE);
+    assert_eq!(ticket.legs.get_unchecked(1).odds, 2 * ODDS_SCALE);
+    let house = client.get_parlay_house();
+    assert_eq!((house.balance, house.reserved), (1000, 700));
+
+    // Reserved liquidity can't be withdrawn
+    assert_eq!(
+        client.try_withdraw_parlay_house(&admin, &301),
+        Err(Ok(Error::InsufficientHouseLiquidity))
+    );
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &btc, &Outcome::Yes);
+    assert_eq!(
+        client.try_settle_parlay(&ticket_id),
+        Err(Ok(Error::NotResolved))
+    );
+    client.resolve(&resolver, &eth, &Outcome::Yes);
+
+    // Anyone can settle; the owner is paid
+    assert_eq!(client.settle_parlay(&ticket_id), 800);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("ParlayEnd"), ticket_id, punter.clone()).into_val(&env)
+    );
+    let ev: events::ParlaySettled = data.into_val(&env);
+    assert_eq!(ev.payout, 800);
+    assert_eq!(kale.balance(&punter), 800);
+
+    let house = client.get_parlay_house();
+    assert_eq!((house.balance, house.reserved), (300, 0));
+    assert_eq!(
+        client.try_settle_parlay(&ticket_id),
+        Err(Ok(Error::AlreadyResolved))
+    );
+
+    client.withdraw_parlay_house(&admin, &300);
+    assert_eq!(kale.balance(&admin), 300);
+}
+
+#[test]
+fn test_parlay_void_and_losing_legs() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let punter = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let rain = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    let frost = create_test_market(&env, &client, &creator);
+    let hail = create_test_market(&env, &client, &creator);
+
+    kale_admin.mint(&bettor, &1000);
+    client.bet(&bettor, &rain, &true, &100);
+    client.bet(&bettor, &rain, &false, &100);
+    client.bet(&bettor, &frost, &true, &300);
+    client.bet(&bettor, &frost, &false, &100);
+    client.bet(&bettor, &hail, &true, &100);
+
+    // Malformed tickets
+    assert_eq!(
+        client.try_quote_parlay(&picks(&env, &[(rain, true)]), &10),
+        Err(Ok(Error::InvalidParlay))
+    );
+    assert_eq!(
+        client.try_quote_parlay(&picks(&env, &[(rain, true), (rain, false)]), &10),
+        Err(Ok(Error::InvalidParlay))
+    );
+    // Nobody has bet NO on hail, so there are no odds to lock
+    assert_eq!(
+        client.try_quote_parlay(&picks(&env, &[(rain, true), (hail, false)]), &10),
+        Err(Ok(Error::ParlayLegUnpriced))
+    );
+    // Dust on the thin side doesn't price it, and covering the stake still
+    // leaves hail NO at 11x, past the per-leg cap
+    client.bet(&bettor, &hail, &false, &1);
+    assert_eq!(
+        client.try_quote_parlay(&picks(&env, &[(rain, true), (hail, false)]), &10),
+        Err(Ok(Error::ParlayLegUnpriced))
+    );
+    client.bet(&bettor, &hail, &false, &9);
+    assert_eq!(
+        client.try_quote_parlay(&picks(&env, &[(rain, true), (hail, false)]), &10),
+        Err(Ok(Error::ParlayLegUnpriced))
+    );
+    assert_eq!(
+        client.quote_parlay(&picks(&env, &[(rain, true), (hail, true)]), &10),
+        22
+    );
+
+    kale_admin.mint(&admin, &1000);
+    client.fund_parlay_house(&admin, &1000);
+    kale_admin.mint(&punter, &200);
+
+    // rain YES 2x, frost NO 4x
+    let voided = client.place_parlay(
+        &punter,
+        &picks(&env, &[(rain, true), (frost, false)]),
+        &100,
+        &0,
+    );
+    // rain NO 2x, frost YES 1.33x
+    let lost = client.place_parlay(
+        &punter,
+        &picks(&env, &[(rain, false), (frost, true)]),
+        &100,
+        &0,
+    );
+    assert_eq!(client.get_parlay(&lost).potential_payout, 266);
+    assert_eq!(client.get_parlay_house().reserved, 700 + 166);
+
+    // frost is cancelled, which voids that leg on both tickets
+    client.cancel_market(&admin, &frost, &String::from_str(&env, "duplicate"), &true);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &rain, &Outcome::Yes);
+
+    // The surviving 2x leg pays; the losing ticket pays nothing
+    assert_eq!(client.settle_parlay(&voided), 200);
+    assert_eq!(client.settle_parlay(&lost), 0);
+    assert!(client.get_parlay(&lost).settled);
+    assert_eq!(kale.balance(&punter), 200);
+
+    // The house paid 100 profit and kept a 100 stake
+    let house = client.get_parlay_house();
+    assert_eq!((house.balance, house.reserved), (1000, 0));
+}
+
+fn daily_btc_template(e: &Env) -> MarketTemplate {
+    MarketTemplate {
+        question: String::from_str(e, "Will BTC close above ${price} today?"),
+        asset: Symbol::new(e, "BTC"),
+        price_offset_bps: 500,
+        start_ts: e.ledger().timestamp(),
+        cadence: 86_400,
+        close_offset: 72_000,
+        resolution_offset: 86_400,
+        keeper_tip: 10,
+        config: default_config(),
+        metadata: MarketMetadata {
+            category: Category::Crypto,
+            ..default_metadata(e)
+        },
+    }
+}
+
+#[test]
+fn test_spawn_markets_from_template() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let keeper = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+    let oracle = setup_mock_oracle(&env, &client, &admin);
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(100_000 * USD, 0)]),
+    );
+
+    let template_id = client.create_template(&admin, &daily_btc_template(&env));
+    kale_admin.mint(&admin, &15);
+    client.fund_template(&admin, &template_id, &15);
+
+    // Strike is spot + 5%
+    let first = client.spawn_from_template(&keeper, &template_id);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(2);
+    assert_eq!(
+        topics,
+        (symbol_short!("Spawned"), template_id, first).into_val(&env)
+    );
+    let ev: events::MarketSpawned = data.into_val(&env);
+    assert_eq!((ev.keeper.clone(), ev.tip), (keeper.clone(), 10));
+
+    let market = client.get_market(&first);
+    assert_eq!(
+        market.question,
+        String::from_str(&env, "Will BTC close above $105000 today?")
+    );
+    assert_eq!((market.close_ts, market.resolution_ts), (72_000, 86_400));
+    assert_eq!(market.creator, admin);
+    assert_eq!(client.get_metadata(&first).category, Category::Crypto);
+    let instance = client.get_template_instance(&first).unwrap();
+    assert_eq!((instance.sequence, instance.prev_market_id), (0, None));
+    assert_eq!(instance.strike_price, 105_000 * USD);
+    assert_eq!(
+        client.get_price_condition(&first),
+        Some(PriceCondition {
+            asset: Symbol::new(&env, "BTC"),
+            strike: 105_000 * USD,
+        })
+    );
+    assert_eq!(kale.balance(&keeper), 10);
+
+    // One market per period
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &template_id),
+        Err(Ok(Error::TemplateNotDue))
+    );
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 86_400 + 60;
+    });
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(120_000 * USD, 86_400)]),
+    );
+    let second = client.spawn_from_template(&keeper, &template_id);
+    let instance = client.get_template_instance(&second).unwrap();
+    assert_eq!(
+        (instance.sequence, instance.prev_market_id),
+        (1, Some(first))
+    );
+    assert_eq!(
+        client.get_market(&second).question,
+        String::from_str(&env, "Will BTC close above $126000 today?")
+    );
+    // Only 5 KALE was left for the tip
+    assert_eq!(kale.balance(&keeper), 15);
+
+    // Missed periods are skipped, not backfilled
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 4 * 86_400 + 60;
+    });
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(110_000 * USD, 4 * 86_400)]),
+    );
+    let third = client.spawn_from_template(&keeper, &template_id);
+    assert_eq!(client.get_market(&third).close_ts, 4 * 86_400 + 72_000);
+    assert_eq!(kale.balance(&keeper), 15);
+    let state = client.get_template_state(&template_id);
+    assert_eq!((state.spawned, state.tip_balance), (3, 0));
+    assert_eq!(state.next_period_ts, 5 * 86_400);
+
+    let history = client.list_template_markets(&template_id, &0, &10);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.get_unchecked(2).id, third);
+
+    // Sub-dollar strikes keep their decimals
+    oracle.set_prices(
+        &ticker(&env, "XLM"),
+        &price_series(&env, &[(USD / 4, 4 * 86_400)]),
+    );
+    let xlm_template = client.create_template(
+        &admin,
+        &MarketTemplate {
+            question: String::from_str(&env, "Will XLM close above ${price} today?"),
+            asset: Symbol::new(&env, "XLM"),
+            start_ts: 4 * 86_400,
+            ..daily_btc_template(&env)
+        },
+    );
+    let xlm = client.spawn_from_template(&keeper, &xlm_template);
+    assert_eq!(
+        client.get_market(&xlm).question,
+        String::from_str(&env, "Will XLM close above $0.2625 today?")
+    );
+    assert_eq!(
+        client.get_template_instance(&xlm).unwrap().strike_price,
+        USD / 10_000 * 2625
+    );
+
+    client.set_template_active(&admin, &template_id, &false);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 5 * 86_400;
+    });
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &template_id),
+        Err(Ok(Error::TemplateInactive))
+    );
+}
+
+#[test]
+fn test_template_validation() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let keeper = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+
+    // Betting must close within the period and before resolution
+    let mut template = daily_btc_template(&env);
+    template.close_offset = 90_000;
+    assert_eq!(
+        client.try_create_template(&admin, &template),
+        Err(Ok(Error::InvalidTime))
+    );
+    template.close_offset = 86_400;
+    assert_eq!(
+        client.try_create_template(&admin, &template),
+        Err(Ok(Error::InvalidTime))
+    );
+    let mut template = daily_btc_template(&env);
+    template.price_offset_bps = MAX_PRICE_OFFSET_BPS + 1;
+    assert_eq!(
+        client.try_create_template(&admin, &template),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    // Spawning needs a price; nothing is quoted yet
+    let template_id = client.create_template(&admin, &daily_btc_template(&env));
+    let oracle = setup_mock_oracle(&env, &client, &admin);
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &template_id),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+
+    // Late in a period, after betting would have closed, there is nothing to spawn
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(100_000 * USD, 0)]),
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 80_000;
+    });
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &template_id),
+        Err(Ok(Error::TemplateNotDue))
+    );
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &7),
+        Err(Ok(Error::TemplateNotFound))
+    );
+
+    // The next period is due, but a day-old spot price can't set its strike
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 86_400 + 60;
+    });
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &template_id),
+        Err(Ok(Error::OraclePriceStale))
+    );
+
+    // A spot price too large to offset is refused rather than overflowing
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(i128::MAX / 2, 86_400)]),
+    );
+    assert_eq!(
+        client.try_spawn_from_template(&keeper, &template_id),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_settle_market_from_price_condition() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let keeper = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    let oracle = setup_mock_oracle(&env, &client, &admin);
+    let condition = PriceCondition {
+        asset: Symbol::new(&env, "BTC"),
+        strike: 100_000 * USD,
+    };
+    client.set_price_condition(&market_id, &condition);
+    let (topics, data) = market_events(&env, &contract_id).last_unchecked();
+    assert_eq!(
+        topics,
+        (symbol_short!("PriceCond"), market_id).into_val(&env)
+    );
+    let ev: events::PriceConditionSet = data.into_val(&env);
+    assert_eq!(ev.condition, condition);
+    assert_eq!(
+        client.get_price_condition(&market_id),
+        Some(condition.clone())
+    );
+
+    kale_admin.mint(&alice, &300);
+    kale_admin.mint(&bob, &100);
+    client.bet(&alice, &market_id, &true, &300);
+    client.bet(&bob, &market_id, &false, &100);
+    // The terms can't change under existing bets
+    assert_eq!(
+        client.try_set_price_condition(&market_id, &condition),
+        Err(Ok(Error::MetadataLocked))
+    );
+
+    kale_admin.mint(&admin, &20);
+    client.fund_bounty_pool(&admin, &20);
+    client.set_bounty_config(
+        &admin,
+        &BountyConfig {
+            settle_tip: 5,
+            invalidate_tip: 0,
+            ttl_tip: 0,
+            invalidate_grace: MIN_INVALIDATE_GRACE,
+        },
+    );
+
+    assert_eq!(
+        client.try_settle_market(&keeper, &market_id),
+        Err(Ok(Error::InvalidTime))
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+
+    // The latest price predates resolution
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(101_000 * USD, 7000)]),
+    );
+    assert_eq!(
+        client.try_settle_market(&keeper, &market_id),
+        Err(Ok(Error::OraclePriceUnavailable))
+    );
+
+    // The price at resolution decides, not wherever it went since
+    oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(101_000 * USD, 7200), (99_000 * USD, 7500)]),
+    );
+    assert_eq!(client.settle_market(&keeper, &market_id), 5);
+    let (topics, data) = market_events(&env, &contract_id).last_unchecked();
+    assert_eq!(
+        topics,
+        (symbol_short!("Bounty"), market_id, keeper.clone()).into_val(&env)
+    );
+    let ev: events::KeeperPaid = data.into_val(&env);
+    assert_eq!((ev.action, ev.tip), (KeeperAction::Settle, 5));
+
+    assert_eq!(client.get_market(&market_id).outcome, Outcome::Yes);
+    assert_eq!(kale.balance(&keeper), 5);
+    assert_eq!(client.get_bounty_pool(), 15);
+    assert_eq!(
+        client.try_settle_market(&keeper, &market_id),
+        Err(Ok(Error::AlreadyResolved))
+    );
+
+    // A 10% fee on alice's 100 profit, half of it back into the bounty pool
+    run_timelocked(
+        &client,
+        &admin,
+        TimelockAction::SetFeeConfig(FeeConfig {
+            fee_bps: 1000,
+            bounty_share_bps: 5000,
+            lp_share_bps: 0,
+        }),
+    );
+    assert_eq!(client.claim(&alice, &market_id), 390);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Fee"), market_id, alice.clone()).into_val(&env)
+    );
+    let ev: events::FeeCollected = data.into_val(&env);
+    assert_eq!((ev.fee, ev.to_bounty), (10, 5));
+    assert_eq!(kale.balance(&alice), 390);
+    assert_eq!(kale.balance(&admin), 5);
+    assert_eq!(client.get_bounty_pool(), 20);
+
+    // Markets without a price condition wait for the resolver
+    let manual = create_test_market(&env, &client, &creator);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    assert_eq!(
+        client.try_settle_market(&keeper, &manual),
+        Err(Ok(Error::NoPriceCondition))
+    );
+}
+
+#[test]
+fn test_force_invalidate_and_ttl_bounties() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let keeper = Address::generate(&env);
+    let alice = Address::generate(&env);
+    // Long enough for every entry to outlive one extension window
+    env.ledger().with_mut(|ledger| {
+        ledger.min_persistent_entry_ttl = MARKET_TTL_EXTEND_TO;
+    });
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    let other_id = create_test_market(&env, &client, &creator);
+    kale_admin.mint(&alice, &400);
+    client.bet(&alice, &market_id, &true, &300);
+    client.bet(&alice, &other_id, &true, &100);
+
+    kale_admin.mint(&admin, &20);
+    client.fund_bounty_pool(&admin, &20);
+    let bounty = BountyConfig {
+        settle_tip: 0,
+        invalidate_tip: 7,
+        ttl_tip: 3,
+        invalidate_grace: MIN_INVALIDATE_GRACE - 1,
+    };
+    // A short grace would let keepers void markets before the resolvers act
+    assert_eq!(
+        client.try_set_bounty_config(&admin, &bounty),
+        Err(Ok(Error::InvalidAmount))
+    );
+    client.set_bounty_config(
+        &admin,
+        &BountyConfig {
+            invalidate_grace: MIN_INVALIDATE_GRACE,
+            ..bounty
+        },
+    );
+    run_timelocked(
+        &client,
+        &admin,
+        TimelockAction::SetFeeConfig(FeeConfig {
+            fee_bps: 1000,
+            bounty_share_bps: 10_000,
+            lp_share_bps: 0,
+        }),
+    );
+
+    // The resolver still has the grace period to act
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7200 + MIN_INVALIDATE_GRACE - 1;
+    });
+    assert_eq!(
+        client.try_force_invalidate(&keeper, &market_id),
+        Err(Ok(Error::InvalidTime))
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 1;
+    });
+    assert_eq!(client.force_invalidate(&keeper, &market_id), 7);
+    assert_eq!(client.get_market(&market_id).outcome, Outcome::Invalid);
+    assert_eq!(
+        client.try_force_invalidate(&keeper, &market_id),
+        Err(Ok(Error::AlreadyResolved))
+    );
+
+    // Refunds never pay the fee
+    assert_eq!(client.claim(&alice, &market_id), 300);
+    assert_eq!(client.get_bounty_pool(), 13);
+
+    // Resolved markets bump for free, and so do fresh entries
+    let stakers = Vec::from_array(&env, [alice.clone()]);
+    assert_eq!(client.bump_market_ttl(&keeper, &market_id, &stakers), 0);
+    assert_eq!(client.bump_market_ttl(&keeper, &other_id, &stakers), 0);
+
+    // One TTL tip per market per extension window, once it could be due
+    env.ledger().with_mut(|ledger| {
+        ledger.sequence_number += MARKET_TTL_EXTEND_TO - MARKET_TTL_THRESHOLD + 1;
+    });
+    assert_eq!(client.bump_market_ttl(&keeper, &other_id, &stakers), 3);
+    assert_eq!(client.bump_market_ttl(&keeper, &other_id, &stakers), 0);
+    assert_eq!(kale.balance(&keeper), 10);
+    assert_eq!(client.get_bounty_pool(), 10);
+
+    // The staker's position is extended along with the market
+    env.as_contract(&contract_id, || {
+        let storage = env.storage().persistent();
+        assert_eq!(
+            storage.get_ttl(&DataKey::Market(other_id)),
+            MARKET_TTL_EXTEND_TO
+        );
+        assert_eq!(
+            storage.get_ttl(&DataKey::Stake(other_id, alice.clone())),
+            MARKET_TTL_EXTEND_TO
+        );
+    });
+}
+
+#[test]
+fn test_bet_from_allowance() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let relayer = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    kale_admin.mint(&owner, &1000);
+
+    // No allowance yet
+    assert_eq!(
+        client.try_bet_from(&relayer, &owner, &market_id, &true, &100),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+
+    kale.approve(&owner, &relayer, &300, &1000);
+    client.bet_from(&relayer, &owner, &market_id, &true, &200);
+    assert_eq!(env.auths()[0].0, relayer, "only the spender signs the bet");
+
+    // The stake is the owner's, paid from the owner's balance
+    let stake = client.get_stake(&market_id, &owner);
+    assert_eq!(stake.yes, 200);
+    assert_eq!(client.get_stake(&market_id, &relayer).yes, 0);
+    assert_eq!(kale.balance(&owner), 800);
+    assert_eq!(kale.allowance(&owner, &relayer), 100);
+
+    // The remaining allowance doesn't cover another 200
+    assert_eq!(
+        client.try_bet_from(&relayer, &owner, &market_id, &false, &200),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+    assert_eq!(client.get_market(&market_id).no_pool, 0);
+
+    client.bet_from(&relayer, &owner, &market_id, &false, &100);
+    assert_eq!(kale.allowance(&owner, &relayer), 0);
+
+    // Winnings go to the owner, not the relayer
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(client.claim(&owner, &market_id), 300);
+    assert_eq!(kale.balance(&owner), 1000);
+    assert_eq!(kale.balance(&relayer), 0);
+}
+
+#[test]
+fn test_transferable_outcome_shares() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let dave = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    env.ledger().with_mut(|ledger| {
+        ledger.sequence_number = 100;
+    });
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    kale_admin.mint(&alice, &300);
+    kale_admin.mint(&bob, &100);
+    client.bet(&alice, &market_id, &true, &300);
+    client.bet(&bob, &market_id, &false, &100);
+
+    assert_eq!(client.share_symbol(&true), String::from_str(&env, "YES"));
+    assert_eq!(client.share_decimals(), 7);
+    assert_eq!(client.share_balance(&market_id, &true, &alice), 300);
+
+    client.share_transfer(&alice, &carol, &market_id, &true, &100);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (
+            symbol_short!("ShareXfer"),
+            market_id,
+            alice.clone(),
+            carol.clone()
+        )
+            .into_val(&env)
+    );
+    let ev: events::SharesTransferred = data.into_val(&env);
+    assert_eq!((ev.from_yes, ev.to_yes), (200, 100));
+
+    // Stakes move, pools don't
+    assert_eq!(client.get_stake(&market_id, &alice).yes, 200);
+    assert_eq!(client.get_stake(&market_id, &carol).yes, 100);
+    assert_eq!(client.get_market(&market_id).yes_pool, 300);
+    assert_eq!(
+        client.try_share_transfer(&carol, &alice, &market_id, &true, &101),
+        Err(Ok(Error::InsufficientShares))
+    );
+    assert_eq!(
+        client.try_share_transfer(&carol, &alice, &market_id, &false, &1),
+        Err(Ok(Error::InsufficientShares))
+    );
+
+    // Allowances work like SEP-41 ones
+    assert_eq!(
+        client.try_share_approve(&carol, &dave, &market_id, &true, &50, &99),
+        Err(Ok(Error::InvalidTime))
+    );
+    client.share_approve(&carol, &dave, &market_id, &true, &50, &200);
+    assert_eq!(client.share_allowance(&carol, &dave, &market_id, &true), 50);
+    assert_eq!(
+        client.try_share_transfer_from(&dave, &carol, &dave, &market_id, &true, &60),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+    client.share_transfer_from(&dave, &carol, &dave, &market_id, &true, &50);
+    assert_eq!(client.share_allowance(&carol, &dave, &market_id, &true), 0);
+    assert_eq!(client.share_balance(&market_id, &true, &dave), 50);
+
+    // Allowances lapse at their expiration ledger
+    client.share_approve(&alice, &dave, &market_id, &true, &10, &150);
+    env.ledger().with_mut(|ledger| {
+        ledger.sequence_number = 151;
+    });
+    assert_eq!(client.share_allowance(&alice, &dave, &market_id, &true), 0);
+
+    // Resolution freezes shares; claim redeems them
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(
+        client.try_share_transfer(&alice, &carol, &market_id, &true, &1),
+        Err(Ok(Error::MarketClosed))
+    );
+    assert_eq!(client.claim(&dave, &market_id), 66);
+    assert_eq!(client.share_balance(&market_id, &true, &dave), 0);
+    assert_eq!(client.claim(&alice, &market_id), 266);
+    assert_eq!(client.claim(&carol, &market_id), 66);
+}
+
+#[test]
+fn test_order_book_matching() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let dave = Address::generate(&env);
+    let eve = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    kale_admin.mint(&alice, &300);
+    kale_admin.mint(&bob, &100);
+    client.bet(&alice, &market_id, &true, &300);
+    client.bet(&bob, &market_id, &false, &100);
+
+    assert_eq!(
+        client.try_place_order(&alice, &market_id, &OrderSide::Ask, &5_000, &301),
+        Err(Ok(Error::InsufficientShares))
+    );
+
+    // Asks escrow shares and sort cheapest first
+    let high_ask = client.place_order(&alice, &market_id, &OrderSide::Ask, &6_000, &100);
+    let low_ask = client.place_order(&alice, &market_id, &OrderSide::Ask, &5_000, &100);
+    assert_eq!(client.get_stake(&market_id, &alice).yes, 100);
+    let asks = client.get_order_book(&market_id, &OrderSide::Ask);
+    assert_eq!(asks.get_unchecked(0).id, low_ask);
+
+    // A crossing bid sweeps both asks at their own prices and gets the
+    // difference back: 100 * 0.5 + 50 * 0.6 = 80 of 105 escrowed
+    kale_admin.mint(&carol, &105);
+    client.place_order(&carol, &market_id, &OrderSide::Bid, &7_000, &150);
+    let fills = market_events(&env, &contract_id);
+    let (topics, data) = fills.get_unchecked(1);
+    assert_eq!(
+        topics,
+        (
+            symbol_short!("Fill"),
+            market_id,
+            carol.clone(),
+            alice.clone()
+        )
+            .into_val(&env)
+    );
+    let ev: events::OrderFilled = data.into_val(&env);
+    assert_eq!((ev.ask_id, ev.price, ev.amount), (low_ask, 5_000, 100));
+
+    assert_eq!(kale.balance(&carol), 25);
+    assert_eq!(kale.balance(&alice), 80);
+    assert_eq!(client.get_stake(&market_id, &carol).yes, 150);
+    assert_eq!(client.get_order(&high_ask).amount, 50);
+    assert!(!client.get_order(&low_ask).open);
+    assert!(client
+        .get_order_book(&market_id, &OrderSide::Bid)
+        .is_empty());
+
+    // Bids below the best ask rest in price-time order
+    kale_admin.mint(&dave, &22);
+    kale_admin.mint(&eve, &22);
+    let dave_bid = client.place_order(&dave, &market_id, &OrderSide::Bid, &5_500, &40);
+    let eve_bid = client.place_order(&eve, &market_id, &OrderSide::Bid, &5_500, &40);
+    let bids = client.get_order_book(&market_id, &OrderSide::Bid);
+    assert_eq!(bids.get_unchecked(0).id, dave_bid);
+    assert_eq!(bids.get_unchecked(1).id, eve_bid);
+    assert_eq!(client.match_orders(&market_id, &10), 0);
+
+    // Trading stops at close but cancelling doesn't
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3700;
+    });
+    assert_eq!(
+        client.try_place_order(&dave, &market_id, &OrderSide::Bid, &5_500, &1),
+        Err(Ok(Error::BetsClosed))
+    );
+    client.cancel_order(&dave_bid);
+    assert_eq!(kale.balance(&dave), 22);
+    assert_eq!(
+        client.try_cancel_order(&dave_bid),
+        Err(Ok(Error::OrderNotFound))
+    );
+
+    // Escrowed shares come back before the seller can claim
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3600;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(
+        client.try_claim(&alice, &market_id),
+        Err(Ok(Error::OpenOrders))
+    );
+    client.cancel_order(&high_ask);
+    assert_eq!(client.get_stake(&market_id, &alice).yes, 150);
+
+    // Bought shares pay out like bet ones: 150 * 400 / 300
+    assert_eq!(client.claim(&carol, &market_id), 200);
+    assert_eq!(client.claim(&alice, &market_id), 200);
+    client.cancel_order(&eve_bid);
+    assert_eq!(kale.balance(&contract_id), 0);
+}
+
+#[test]
+fn test_order_book_limits() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let dave = Address::generate(&env);
+    let eve = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    let now = env.ledger().timestamp();
+    let market_id = client.create_market(
+        &creator,
+        &String::from_str(&env, "Will the cider press open?"),
+        &(now + 3600),
+        &(now + 7200),
+        &MarketConfig {
+            bet_limits: BetLimitsChoice::Custom(BetLimits {
+                min_bet: 10,
+                max_bet: 0,
+                max_user_stake: 100,
+                max_pool: 0,
+            }),
+            ..default_config()
+        },
+        &default_metadata(&env),
+    );
+    kale_admin.mint(&alice, &100);
+    kale_admin.mint(&bob, &100);
+    client.bet(&alice, &market_id, &true, &100);
+    client.bet(&bob, &market_id, &false, &100);
+
+    // Orders are at least a minimum bet
+    assert_eq!(
+        client.try_place_order(&bob, &market_id, &OrderSide::Bid, &5_000, &5),
+        Err(Ok(Error::BetTooSmall))
+    );
+
+    // A bid only fills up to the buyer's stake cap at fill time; the rest
+    // of it is cancelled and refunded
+    kale_admin.mint(&carol, &80);
+    let carol_bid = client.place_order(&carol, &market_id, &OrderSide::Bid, &5_000, &60);
+    client.bet(&carol, &market_id, &true, &50);
+    let ask = client.place_order(&alice, &market_id, &OrderSide::Ask, &5_000, &60);
+    assert_eq!(client.get_stake(&market_id, &carol).yes, 100);
+    assert!(!client.get_order(&carol_bid).open);
+    assert_eq!(kale.balance(&carol), 5);
+    assert_eq!(kale.balance(&alice), 25);
+    assert_eq!(client.get_order(&ask).amount, 10);
+
+    // Fills round up, so shares never change hands for nothing
+    let frank = Address::generate(&env);
+    kale_admin.mint(&frank, &9);
+    let dust_bid = client.place_order(&frank, &market_id, &OrderSide::Bid, &3, &10);
+    client.place_order(&carol, &market_id, &OrderSide::Ask, &3, &10);
+    assert!(!client.get_order(&dust_bid).open);
+    assert_eq!(client.get_stake(&market_id, &frank).yes, 10);
+    assert_eq!(kale.balance(&carol), 6);
+
+    // A remainder below the minimum is cancelled rather than left resting
+    let frank_bid = client.place_order(&frank, &market_id, &OrderSide::Bid, &5_000, &15);
+    assert!(!client.get_order(&frank_bid).open);
+    assert!(!client.get_order(&ask).open);
+    assert_eq!(client.get_stake(&market_id, &frank).yes, 20);
+    assert_eq!(kale.balance(&frank), 3);
+    assert_eq!(kale.balance(&alice), 30);
+
+    // A full side evicts its worst order for a better one
+    kale_admin.mint(&dave, &(MAX_ORDERS_PER_SIDE as i128));
+    let mut last = 0;
+    for _ in 0..MAX_ORDERS_PER_SIDE {
+        last = client.place_order(&dave, &market_id, &OrderSide::Bid, &1_000, &10);
+    }
+    assert_eq!(kale.balance(&dave), 0);
+    kale_admin.mint(&eve, &2);
+    assert_eq!(
+        client.try_place_order(&eve, &market_id, &OrderSide::Bid, &1_000, &10),
+        Err(Ok(Error::OrderBookFull))
+    );
+    let eve_bid = client.place_order(&eve, &market_id, &OrderSide::Bid, &2_000, &10);
+    let bids = client.get_order_book(&market_id, &OrderSide::Bid);
+    assert_eq!(bids.len(), MAX_ORDERS_PER_SIDE);
+    assert_eq!(bids.get_unchecked(0).id, eve_bid);
+    assert!(!client.get_order(&last).open);
+    assert_eq!(kale.balance(&dave), 1);
+}
+
+#[test]
+fn test_liquidity_pools() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let lp1 = Address::generate(&env);
+    let lp2 = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    let market_id = setup_test_market(&env, &client, &admin, &resolver, &kale_sac, &creator);
+    let voided = create_test_market(&env, &client, &creator);
+    run_timelocked(
+        &client,
+        &admin,
+        TimelockAction::SetFeeConfig(FeeConfig {
+            fee_bps: 1000,
+            bounty_share_bps: 0,
+            lp_share_bps: 5000,
+        }),
+    );
+
+    // Deposits seed both sides evenly
+    kale_admin.mint(&lp1, &151);
+    kale_admin.mint(&lp2, &100);
+    client.add_liquidity(&lp1, &market_id, &100);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("LpAdd"), market_id, lp1.clone()).into_val(&env)
+    );
+    let ev: events::LiquidityAdded = data.into_val(&env);
+    assert_eq!((ev.yes_pool, ev.no_pool), (50, 50));
+
+    client.add_liquidity(&lp2, &market_id, &100);
+    client.add_liquidity(&lp1, &voided, &51);
+    assert_eq!(client.get_odds(&market_id), 5000);
+    assert_eq!(client.get_lp_shares(&market_id, &lp1), 100);
+
+    kale_admin.mint(&alice, &100);
+    client.bet(&alice, &market_id, &true, &100);
+
+    // Liquidity stays in until the market resolves
+    assert_eq!(
+        client.try_remove_liquidity(&lp1, &market_id),
+        Err(Ok(Error::NotResolved))
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    assert_eq!(
+        client.try_add_liquidity(&lp2, &market_id, &10),
+        Err(Ok(Error::BetsClosed))
+    );
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+
+    // Alice wins 150 on 100; half of her 5 fee goes to the LPs
+    assert_eq!(client.claim(&alice, &market_id), 145);
+    assert_eq!(client.get_liquidity_pool(&market_id).fees, 2);
+
+    // The LPs' 100 YES liquidity pays 150, split with the fees
+    assert_eq!(client.remove_liquidity(&lp1, &market_id), 76);
+    assert_eq!(client.remove_liquidity(&lp2, &market_id), 76);
+    assert_eq!(
+        client.try_remove_liquidity(&lp2, &market_id),
+        Err(Ok(Error::NothingToClaim))
+    );
+
+    // A voided market hands deposits back in full
+    client.cancel_market(&admin, &voided, &String::from_str(&env, "duplicate"), &true);
+    assert_eq!(client.remove_liquidity(&lp1, &voided), 51);
+}
+
+#[test]
+fn test_creator_subsidy() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+
+    let question = String::from_str(&env, "Will it rain tomorrow?");
+    let now = env.ledger().timestamp();
+    let subsidized = |subsidy: i128| {
+        client.try_create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &MarketConfig {
+                subsidy,
+                ..default_config()
+            },
+            &default_metadata(&env),
+        )
+    };
+    assert_eq!(subsidized(-1), Err(Ok(Error::InvalidAmount)));
+
+    // The creator seeds 60 and anyone can top it up
+    kale_admin.mint(&creator, &90);
+    let market_id = subsidized(60).unwrap().unwrap();
+    let voided = subsidized(30).unwrap().unwrap();
+    kale_admin.mint(&bob, &40);
+    client.add_subsidy(&bob, &market_id, &40);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Subsidy"), market_id, bob.clone()).into_val(&env)
+    );
+    let ev: events::SubsidyAdded = data.into_val(&env);
+    assert_eq!(ev.total, 100);
+
+    kale_admin.mint(&alice, &100);
+    kale_admin.mint(&carol, &300);
+    kale_admin.mint(&bob, &200);
+    client.bet(&alice, &market_id, &true, &100);
+    client.bet(&carol, &market_id, &true, &300);
+    client.bet(&bob, &market_id, &false, &200);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7300;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+
+    // Winners split the subsidy by stake on top of the pool
+    assert_eq!(client.claim(&alice, &market_id), 150 + 25);
+    assert_eq!(client.claim(&carol, &market_id), 450 + 75);
+    assert_eq!(
+        client.try_refund_subsidy(&bob, &market_id),
+        Err(Ok(Error::NothingToClaim))
+    );
+    assert_eq!(
+        client.try_add_subsidy(&bob, &market_id, &1),
+        Err(Ok(Error::MarketClosed))
+    );
+
+    // With nobody to pay, funders get their subsidy back
+    client.cancel_market(&admin, &voided, &String::from_str(&env, "duplicate"), &true);
+    assert_eq!(client.refund_subsidy(&creator, &voided), 30);
+    assert_eq!(
+        client.try_refund_subsidy(&creator, &voided),
+        Err(Ok(Error::NothingToClaim))
+    );
+}
+
+#[test]
+fn test_time_weighted_payouts() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let dave = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+
+    let question = String::from_str(&env, "Will it rain tomorrow?");
+    let now = env.ledger().timestamp();
+    let weighted = |early_bonus_bps: u32| {
+        client.try_create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &MarketConfig {
+                early_bonus_bps,
+                ..default_config()
+            },
+            &default_metadata(&env),
+        )
+    };
+    assert_eq!(weighted(10_001), Err(Ok(Error::InvalidAmount)));
+    let market_id = weighted(10_000).unwrap().unwrap();
+
+    for user in [&alice, &bob, &carol] {
+        kale_admin.mint(user, &100);
+    }
+
+    // Weight starts at 2x and decays linearly to 1x at close
+    assert_eq!(client.get_weight_multiplier(&market_id), 20_000);
+    client.bet(&alice, &market_id, &true, &100);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 1800;
+    });
+    client.bet(&bob, &market_id, &false, &100);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 1700;
+    });
+    assert_eq!(client.get_weight_multiplier(&market_id), 10_277);
+    client.bet(&carol, &market_id, &true, &100);
+
+    let stake = client.get_stake(&market_id, &bob);
+    assert_eq!((stake.no, stake.no_weight), (100, 150));
+    let market = client.get_market(&market_id);
+    assert_eq!((market.yes_pool, market.yes_weight), (200, 302));
+
+    // Transferred shares carry their weight with them
+    client.share_transfer(&alice, &dave, &market_id, &true, &50);
+    assert_eq!(client.get_stake(&market_id, &dave).yes_weight, 100);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3800;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+
+    // The pool is split by weight: the early 100 beats the late 100
+    assert_eq!(client.claim(&alice, &market_id), 300 * 100 / 302);
+    assert_eq!(client.claim(&dave, &market_id), 300 * 100 / 302);
+    assert_eq!(client.claim(&carol, &market_id), 300 * 102 / 302);
+}
+
+#[test]
+fn test_commit_reveal_betting() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let dave = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+
+    let question = String::from_str(&env, "Will it rain tomorrow?");
+    let now = env.ledger().timestamp();
+    let sealed = |commit_reveal: CommitRevealMode, bet_limits: BetLimitsChoice| {
+        client.create_market(
+            &creator,
+            &question,
+            &(now + 3600),
+            &(now + 7200),
+            &MarketConfig {
+                commit_reveal,
+                bet_limits,
+                resolver: ResolverChoice::Default,
+                ..default_config()
+            },
+            &default_metadata(&env),
+        )
+    };
+    let market_id = sealed(
+        CommitRevealMode::ForfeitUnrevealed,
+        BetLimitsChoice::Default,
+    );
+    let refunding = sealed(
+        CommitRevealMode::RefundUnrevealed,
+        BetLimitsChoice::Custom(BetLimits {
+            min_bet: 1,
+            max_bet: 0,
+            max_user_stake: 0,
+            max_pool: 30,
+        }),
+    );
+
+    for (user, amount) in [(&alice, 100), (&bob, 50), (&carol, 30), (&dave, 20)] {
+        kale_admin.mint(user, &amount);
+    }
+    assert_eq!(
+        client.try_bet(&alice, &market_id, &true, &100),
+        Err(Ok(Error::WrongBettingMode))
+    );
+
+    // Commitments lock KALE without showing a side
+    let alice_salt = BytesN::from_array(&env, &[1; 32]);
+    let bob_salt = BytesN::from_array(&env, &[2; 32]);
+    let alice_hash = commit_reveal::commitment_hash(&env, market_id, &alice, true, &alice_salt);
+    client.commit_bet(&alice, &market_id, &alice_hash, &100);
+    let (topics, _) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Committed"), market_id, alice.clone()).into_val(&env)
+    );
+    assert_eq!(
+        client.try_commit_bet(&alice, &market_id, &alice_hash, &1),
+        Err(Ok(Error::CommitmentExists))
+    );
+    let bob_hash = commit_reveal::commitment_hash(&env, market_id, &bob, false, &bob_salt);
+    client.commit_bet(&bob, &market_id, &bob_hash, &50);
+    // Copying Alice's commitment doesn't let Carol follow her bet
+    client.commit_bet(&carol, &market_id, &alice_hash, &30);
+    let dave_hash = commit_reveal::commitment_hash(&env, refunding, &dave, true, &bob_salt);
+    client.commit_bet(&dave, &refunding, &dave_hash, &20);
+    assert_eq!(client.get_market(&market_id).yes_pool, 0);
+
+    // Sealed KALE counts towards the pool cap and locks the details
+    assert_eq!(
+        client.try_commit_bet(&carol, &refunding, &dave_hash, &11),
+        Err(Ok(Error::PoolCapExceeded))
+    );
+    assert_eq!(
+        client.try_add_liquidity(&carol, &refunding, &11),
+        Err(Ok(Error::PoolCapExceeded))
+    );
+    assert_eq!(client.get_market(&refunding).committed, 20);
+    assert_eq!(
+        client.try_update_metadata(&refunding, &default_metadata(&env)),
+        Err(Ok(Error::MetadataLocked))
+    );
+
+    // Reveals open once betting closes
+    assert_eq!(
+        client.try_reveal_bet(&alice, &market_id, &true, &alice_salt),
+        Err(Ok(Error::InvalidTime))
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3600;
+    });
+    assert_eq!(
+        client.try_reveal_bet(&bob, &market_id, &true, &bob_salt),
+        Err(Ok(Error::RevealMismatch))
+    );
+    assert_eq!(
+        client.try_reveal_bet(&carol, &market_id, &true, &alice_salt),
+        Err(Ok(Error::RevealMismatch))
+    );
+    client.reveal_bet(&alice, &market_id, &true, &alice_salt);
+    client.reveal_bet(&bob, &market_id, &false, &bob_salt);
+    let market = client.get_market(&market_id);
+    assert_eq!((market.yes_pool, market.no_pool), (100, 50));
+    assert_eq!(
+        client.try_settle_unrevealed(&market_id, &carol),
+        Err(Ok(Error::InvalidTime))
+    );
+
+    // After the window, unrevealed KALE is forfeited or refunded by mode
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 3600;
+    });
+    assert_eq!(
+        client.try_reveal_bet(&carol, &market_id, &true, &alice_salt),
+        Err(Ok(Error::InvalidTime))
+    );
+    assert_eq!(client.settle_unrevealed(&market_id, &carol), 30);
+    assert_eq!(kale.balance(&carol), 0);
+    assert_eq!(kale.balance(&admin), 30);
+    assert_eq!(client.settle_unrevealed(&refunding, &dave), 20);
+    assert_eq!(kale.balance(&dave), 20);
+
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(client.claim(&alice, &market_id), 150);
+}
+
+#[test]
+fn test_reveal_window_ends_before_resolution() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::Client::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+
+    let now = env.ledger().timestamp();
+    let market_id = client.create_market(
+        &creator,
+        &String::from_str(&env, "Will it rain tomorrow?"),
+        &(now + 3600),
+        &(now + 7200),
+        &MarketConfig {
+            commit_reveal: CommitRevealMode::RefundUnrevealed,
+            resolver: ResolverChoice::Default,
+            ..default_config()
+        },
+        &default_metadata(&env),
+    );
+    assert_eq!(
+        commit_reveal::reveal_deadline(&client.get_market(&market_id)),
+        now + 5400
+    );
+
+    kale_admin.mint(&alice, &100);
+    let salt = BytesN::from_array(&env, &[1; 32]);
+    let hash = commit_reveal::commitment_hash(&env, market_id, &alice, true, &salt);
+    client.commit_bet(&alice, &market_id, &hash, &100);
+
+    // Alice waits to see where the outcome is heading before revealing,
+    // but the window has shut well before resolution
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = now + 7199;
+    });
+    assert_eq!(
+        client.try_reveal_bet(&alice, &market_id, &true, &salt),
+        Err(Ok(Error::InvalidTime))
+    );
+    assert_eq!(client.get_market(&market_id).yes_pool, 0);
+    assert_eq!(client.settle_unrevealed(&market_id, &alice), 100);
+    assert_eq!(kale.balance(&alice), 100);
+}
+
+#[test]
+fn test_role_permissions() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let bettor = Address::generate(&env);
+    let outsider = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    for role in [
+        Role::Admin,
+        Role::Moderator,
+        Role::FeeManager,
+        Role::Pauser,
+        Role::OracleManager,
+    ] {
+        assert!(client.has_role(&role, &admin));
+        assert_eq!(client.get_role_count(&role), 1);
+    }
+    assert!(client.has_role(&Role::Resolver, &resolver));
+    assert_eq!(
+        client.get_role_members(&Role::Resolver, &0, &10),
+        Vec::from_array(&env, [resolver.clone()])
+    );
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_resolver(), resolver);
+
+    let market_id = create_test_market(&env, &client, &creator);
+    let reason = String::from_str(&env, "spam");
+    let fees = FeeConfig {
+        fee_bps: 100,
+        bounty_share_bps: 0,
+        lp_share_bps: 0,
+    };
+    let bounty = BountyConfig {
+        settle_tip: 0,
+        invalidate_tip: 0,
+        ttl_tip: 0,
+        invalidate_grace: MIN_INVALIDATE_GRACE,
+    };
+    let missing = Err(Ok(Error::MissingRole));
+
+    // Nobody without a role can use a privileged entrypoint
+    assert_eq!(
+        client.try_resolve(&outsider, &market_id, &Outcome::Yes),
+        missing
+    );
+    assert_eq!(
+        client.try_cancel_market(&outsider, &market_id, &reason, &true),
+        missing
+    );
+    assert_eq!(
+        client.try_set_creator_allowed(&outsider, &creator, &true),
+        missing
+    );
+    assert_eq!(client.try_set_treasury(&outsider, &outsider), missing);
+    assert_eq!(
+        client.try_set_creation_policy(&outsider, &client.get_creation_policy()),
+        missing
+    );
+    assert_eq!(
+        client.try_schedule_action(&outsider, &TimelockAction::SetFeeConfig(fees.clone())),
+        Err(Ok(Error::MissingRole))
+    );
+    assert_eq!(client.try_set_bounty_config(&outsider, &bounty), missing);
+    assert_eq!(
+        client.try_schedule_action(&outsider, &TimelockAction::SetOracle(outsider.clone())),
+        Err(Ok(Error::MissingRole))
+    );
+    assert_eq!(client.try_fund_parlay_house(&outsider, &10), missing);
+    assert_eq!(client.try_withdraw_parlay_house(&outsider, &10), missing);
+    assert_eq!(
+        client.try_create_template(&outsider, &daily_btc_template(&env)),
+        Err(Ok(Error::MissingRole))
+    );
+    assert_eq!(client.try_set_paused(&outsider, &true), missing);
+    assert_eq!(
+        client.try_grant_role(&outsider, &Role::Admin, &outsider),
+        missing
+    );
+
+    // Roles don't imply each other
+    assert_eq!(
+        client.try_resolve(&admin, &market_id, &Outcome::Yes),
+        missing
+    );
+    assert_eq!(
+        client.try_cancel_market(&resolver, &market_id, &reason, &true),
+        missing
+    );
+
+    // Each role unlocks exactly its own powers
+    let moderator = Address::generate(&env);
+    client.grant_role(&admin, &Role::Moderator, &moderator);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (
+            symbol_short!("RoleGrant"),
+            Role::Moderator,
+            moderator.clone()
+        )
+            .into_val(&env)
+    );
+    let ev: events::RoleGranted = data.into_val(&env);
+    assert_eq!((ev.account, ev.sender), (moderator.clone(), admin.clone()));
+    assert_eq!(
+        client.try_schedule_action(&moderator, &TimelockAction::SetFeeConfig(fees.clone())),
+        Err(Ok(Error::MissingRole))
+    );
+    assert_eq!(client.try_set_paused(&moderator, &true), missing);
+    client.set_creator_allowed(&moderator, &creator, &true);
+
+    let fee_manager = Address::generate(&env);
+    client.grant_role(&admin, &Role::FeeManager, &fee_manager);
+    run_timelocked(&client, &fee_manager, TimelockAction::SetFeeConfig(fees));
+    client.set_bounty_config(&fee_manager, &bounty);
+    assert_eq!(
+        client.try_schedule_action(
+            &fee_manager,
+            &TimelockAction::SetOracle(fee_manager.clone())
+        ),
+        Err(Ok(Error::MissingRole))
+    );
+
+    let oracle_manager = Address::generate(&env);
+    client.grant_role(&admin, &Role::OracleManager, &oracle_manager);
+    let oracle = Address::generate(&env);
+    run_timelocked(
+        &client,
+        &oracle_manager,
+        TimelockAction::SetOracle(oracle.clone()),
+    );
+    assert_eq!(client.get_reflector_oracle(), oracle);
+    assert_eq!(client.try_set_treasury(&oracle_manager, &oracle), missing);
+
+    // Pausing stops new money coming in until it is lifted
+    let pauser = Address::generate(&env);
+    client.grant_role(&admin, &Role::Pauser, &pauser);
+    client.set_paused(&pauser, &true);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(topics, (symbol_short!("Paused"),).into_val(&env));
+    let ev: events::PauseUpdated = data.into_val(&env);
+    assert!(ev.paused);
+    assert!(client.is_paused());
+    kale_admin.mint(&bettor, &100);
+    assert_eq!(
+        client.try_bet(&bettor, &market_id, &true, &100),
+        Err(Ok(Error::ContractPaused))
+    );
+    assert_eq!(
+        client.try_create_market(
+            &creator,
+            &String::from_str(&env, "Paused?"),
+            &(env.ledger().timestamp() + 3600),
+            &(env.ledger().timestamp() + 7200),
+            &default_config(),
+            &default_metadata(&env),
+        ),
+        Err(Ok(Error::ContractPaused))
+    );
+    client.set_paused(&pauser, &false);
+    client.bet(&bettor, &market_id, &true, &100);
+
+    // A revoked role stops working at once
+    client.revoke_role(&admin, &Role::Moderator, &moderator);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (
+            symbol_short!("RoleRevok"),
+            Role::Moderator,
+            moderator.clone()
+        )
+            .into_val(&env)
+    );
+    let ev: events::RoleRevoked = data.into_val(&env);
+    assert_eq!(ev.sender, admin);
+    assert!(!client.has_role(&Role::Moderator, &moderator));
+    assert_eq!(
+        client.try_cancel_market(&moderator, &market_id, &reason, &true),
+        missing
+    );
+    assert_eq!(
+        client.try_revoke_role(&admin, &Role::Moderator, &moderator),
+        missing
+    );
+
+    // The last Admin can't be removed, but can hand over
+    assert_eq!(
+        client.try_revoke_role(&admin, &Role::Admin, &admin),
+        Err(Ok(Error::LastAdmin))
+    );
+    let successor = Address::generate(&env);
+    client.grant_role(&admin, &Role::Admin, &successor);
+    client.revoke_role(&successor, &Role::Admin, &admin);
+    assert_eq!(client.get_role_count(&Role::Admin), 1);
+    assert_eq!(
+        client.get_role_members(&Role::Admin, &0, &10),
+        Vec::from_array(&env, [successor.clone()])
+    );
+    assert_eq!(client.get_admin(), successor);
+    assert_eq!(client.try_grant_role(&admin, &Role::Admin, &admin), missing);
+    client.set_treasury(&successor, &successor);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7200;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(client.claim(&bettor, &market_id), 100);
+}
+
+#[test]
+fn test_timelocked_governance() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let outsider = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    assert_eq!(client.get_timelock_delay(), 0);
+
+    // The first delay applies at once, later changes wait for it
+    run_timelocked(&client, &admin, TimelockAction::SetDelay(86_400));
+    assert_eq!(client.get_timelock_delay(), 86_400);
+    assert_eq!(
+        client.try_schedule_action(&admin, &TimelockAction::SetDelay(MAX_TIMELOCK_DELAY + 1)),
+        Err(Ok(Error::InvalidTime))
+    );
+    assert_eq!(
+        client.try_schedule_action(&outsider, &TimelockAction::SetDelay(0)),
+        Err(Ok(Error::MissingRole))
+    );
+
+    let start = env.ledger().timestamp();
+    let oracle = Address::generate(&env);
+    let oracle_change = client.schedule_action(&admin, &TimelockAction::SetOracle(oracle.clone()));
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Scheduled"), oracle_change).into_val(&env)
+    );
+    let ev: events::ActionScheduled = data.into_val(&env);
+    assert_eq!(ev.action, TimelockAction::SetOracle(oracle.clone()));
+    assert_eq!(ev.eta, start + 86_400);
+
+    let fees = FeeConfig {
+        fee_bps: 500,
+        bounty_share_bps: 0,
+        lp_share_bps: 0,
+    };
+    let fee_change = client.schedule_action(&admin, &TimelockAction::SetFeeConfig(fees));
+
+    // Integrators see both changes coming
+    let pending = client.get_pending_actions();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending.get_unchecked(0).id, oracle_change);
+    assert_eq!(pending.get_unchecked(1).id, fee_change);
+    assert_eq!(
+        client.try_execute_action(&oracle_change),
+        Err(Ok(Error::ActionNotReady))
+    );
+    assert_ne!(client.get_reflector_oracle(), oracle);
+
+    // Only the proposer or an Admin can withdraw an action
+    assert_eq!(
+        client.try_cancel_action(&outsider, &fee_change),
+        Err(Ok(Error::MissingRole))
+    );
+    client.cancel_action(&admin, &fee_change);
+    let (topics, _) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Unsched"), fee_change).into_val(&env)
+    );
+    assert_eq!(client.get_pending_action(&fee_change), None);
+    assert_eq!(
+        client.try_execute_action(&fee_change),
+        Err(Ok(Error::ActionNotFound))
+    );
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = start + 86_400;
+    });
+    client.execute_action(&oracle_change);
+    let evs = market_events(&env, &contract_id);
+    let (topics, _) = evs.get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Executed"), oracle_change).into_val(&env)
+    );
+    let (topics, _) = evs.get_unchecked(1);
+    assert_eq!(topics, (symbol_short!("Oracle"),).into_val(&env));
+    assert_eq!(client.get_reflector_oracle(), oracle);
+    assert_eq!(client.get_fee_config().fee_bps, 0);
+    assert_eq!(
+        client.try_execute_action(&oracle_change),
+        Err(Ok(Error::ActionNotFound))
+    );
+
+    // An action nobody runs within the grace period lapses
+    let lapsed =
+        client.schedule_action(&admin, &TimelockAction::SetOracle(Address::generate(&env)));
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 86_400 + TIMELOCK_GRACE_SECS + 1;
+    });
+    assert_eq!(
+        client.try_execute_action(&lapsed),
+        Err(Ok(Error::ActionExpired))
+    );
+    assert_eq!(client.get_reflector_oracle(), oracle);
+    assert!(client.get_pending_actions().is_empty());
+
+    // and is pruned the next time anything is scheduled
+    let next = client.schedule_action(&admin, &TimelockAction::SetDelay(0));
+    assert_eq!(client.get_pending_action(&lapsed), None);
+    let pending = client.get_pending_actions();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending.get_unchecked(0).id, next);
+}
+
+#[test]
+fn test_dispute_voting() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let voter1 = Address::generate(&env);
+    let voter2 = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::TokenClient::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    let config = DisputeConfig {
+        bond: 100,
+        window_secs: 3600,
+        voting_secs: 7200,
+    };
+    assert_eq!(
+        client.try_set_dispute_config(&resolver, &config),
+        Err(Ok(Error::MissingRole))
+    );
+    client.set_dispute_config(&admin, &config);
+    for user in [&alice, &bob, &voter1, &voter2] {
+        kale_admin.mint(user, &1_000);
+    }
+
+    let overturned = create_test_market(&env, &client, &creator);
+    let upheld = create_test_market(&env, &client, &creator);
+    let undisputed = create_test_market(&env, &client, &creator);
+    for market_id in [overturned, upheld, undisputed] {
+        client.bet(&alice, &market_id, &true, &100);
+        client.bet(&bob, &market_id, &false, &100);
+    }
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7200;
+    });
+    for market_id in [overturned, upheld, undisputed] {
+        client.resolve(&resolver, &market_id, &Outcome::Yes);
+    }
+
+    // Nothing pays out while the outcome can still be contested
+    let resolved_at = env.ledger().timestamp();
+    assert_eq!(
+        client.get_dispute_deadline(&overturned),
+        Some(resolved_at + 3600)
+    );
+    assert_eq!(
+        client.try_claim(&alice, &overturned),
+        Err(Ok(Error::DisputePending))
+    );
+    assert_eq!(
+        client.try_dispute_outcome(&bob, &overturned, &Outcome::Yes),
+        Err(Ok(Error::NotDisputable))
+    );
+
+    client.dispute_outcome(&bob, &overturned, &Outcome::No);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Dispute"), overturned, bob.clone()).into_val(&env)
+    );
+    let ev: events::DisputeOpened = data.into_val(&env);
+    assert_eq!((ev.original, ev.proposed), (Outcome::Yes, Outcome::No));
+    assert_eq!(ev.ends_at, resolved_at + 7200);
+    assert_eq!(kale.balance(&bob), 600);
+    assert_eq!(
+        client.try_dispute_outcome(&alice, &overturned, &Outcome::Invalid),
+        Err(Ok(Error::NotDisputable))
+    );
+
+    // KALE locked behind an outcome is its voting weight
+    client.vote(&voter1, &overturned, &Outcome::No, &300);
+    client.vote(&voter2, &overturned, &Outcome::Yes, &200);
+    client.vote(&voter2, &overturned, &Outcome::Yes, &50);
+    let (_, data) = market_events(&env, &contract_id).get_unchecked(0);
+    let ev: events::VoteCast = data.into_val(&env);
+    assert_eq!((ev.amount, ev.yes_votes, ev.no_votes), (250, 250, 300));
+    assert_eq!(
+        client.try_vote(&voter1, &overturned, &Outcome::Yes, &10),
+        Err(Ok(Error::AlreadyVoted))
+    );
+    assert_eq!(
+        client.try_settle_dispute(&overturned),
+        Err(Ok(Error::InvalidTime))
+    );
+
+    client.dispute_outcome(&alice, &upheld, &Outcome::Invalid);
+    client.vote(&voter1, &upheld, &Outcome::Yes, &300);
+    client.vote(&voter2, &upheld, &Outcome::Invalid, &100);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = resolved_at + 7200;
+    });
+    assert_eq!(
+        client.try_vote(&voter1, &overturned, &Outcome::No, &10),
+        Err(Ok(Error::InvalidTime))
+    );
+    assert_eq!(
+        client.try_withdraw_vote(&voter1, &overturned),
+        Err(Ok(Error::DisputePending))
+    );
+
+    // The majority overturns the first market and the disputer is refunded
+    assert_eq!(client.settle_dispute(&overturned), Outcome::No);
+    let evs = market_events(&env, &contract_id);
+    let (topics, _) = evs.get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Resolved"), overturned).into_val(&env)
+    );
+    let (topics, data) = evs.get_unchecked(evs.len() - 1);
+    assert_eq!(
+        topics,
+        (symbol_short!("DispEnd"), overturned).into_val(&env)
+    );
+    let ev: events::DisputeSettled = data.into_val(&env);
+    assert!(ev.overturned);
+    assert_eq!(client.get_market(&overturned).outcome, Outcome::No);
+    assert_eq!(kale.balance(&bob), 700);
+    assert_eq!(
+        client.try_settle_dispute(&overturned),
+        Err(Ok(Error::AlreadyResolved))
+    );
+    assert_eq!(client.claim(&bob, &overturned), 200);
+    assert_eq!(
+        client.try_claim(&alice, &overturned),
+        Err(Ok(Error::NothingToClaim))
+    );
+    assert_eq!(client.withdraw_vote(&voter1, &overturned), 300);
+    assert_eq!(client.withdraw_vote(&voter2, &overturned), 250);
+
+    // An upheld outcome pays the disputer's bond to its backers
+    assert_eq!(client.settle_dispute(&upheld), Outcome::Yes);
+    assert_eq!(client.get_market(&upheld).outcome, Outcome::Yes);
+    assert_eq!(client.withdraw_vote(&voter1, &upheld), 400);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("VoteBack"), upheld, voter1.clone()).into_val(&env)
+    );
+    let ev: events::VoteWithdrawn = data.into_val(&env);
+    assert_eq!((ev.amount, ev.reward), (300, 100));
+    assert_eq!(client.withdraw_vote(&voter2, &upheld), 100);
+    assert_eq!(
+        client.try_withdraw_vote(&voter2, &upheld),
+        Err(Ok(Error::NothingToClaim))
+    );
+    assert_eq!(client.claim(&alice, &upheld), 200);
+
+    // Once the window passes undisputed the outcome is final
+    assert_eq!(
+        client.try_dispute_outcome(&bob, &undisputed, &Outcome::No),
+        Err(Ok(Error::NotDisputable))
+    );
+    assert_eq!(client.claim(&alice, &undisputed), 200);
+    assert_eq!(kale.balance(&voter1), 1_100);
+    assert_eq!(kale.balance(&voter2), 1_000);
+}
+
+#[test]
+fn test_resolver_staking() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let voter = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::TokenClient::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+
+    client.init(&admin, &resolver, &kale_sac);
+    client.set_dispute_config(
+        &admin,
+        &DisputeConfig {
+            bond: 100,
+            window_secs: 3600,
+            voting_secs: 7200,
+        },
+    );
+    let config = ResolverStakeConfig {
+        min_stake: 500,
+        slash_bps: 2_000,
+    };
+    assert_eq!(
+        client.try_set_resolver_stake_config(
+            &admin,
+            &ResolverStakeConfig {
+                min_stake: 500,
+                slash_bps: 10_001,
+            }
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+    client.set_resolver_stake_config(&admin, &config);
+    assert_eq!(client.get_resolver_stake_config(), config);
+    for user in [&resolver, &alice, &bob, &voter] {
+        kale_admin.mint(user, &1_000);
+    }
+
+    let wrong = create_test_market(&env, &client, &creator);
+    let right = create_test_market(&env, &client, &creator);
+    for market_id in [wrong, right] {
+        client.bet(&alice, &market_id, &true, &100);
+        client.bet(&bob, &market_id, &false, &100);
+    }
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 7200;
+    });
+
+    // An unstaked resolver can't settle anything
+    assert_eq!(
+        client.try_resolve(&resolver, &wrong, &Outcome::Yes),
+        Err(Ok(Error::InsufficientResolverStake))
+    );
+    client.stake_resolver(&resolver, &1_000);
+    let (topics, data) = market_events(&env, &contract_id).get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("RStake"), resolver.clone()).into_val(&env)
+    );
+    let ev: events::ResolverStaked = data.into_val(&env);
+    assert_eq!((ev.amount, ev.stake), (1_000, 1_000));
+
+    for market_id in [wrong, right] {
+        client.resolve(&resolver, &market_id, &Outcome::Yes);
+    }
+    let resolved_at = env.ledger().timestamp();
+
+    // The stake stays locked while either outcome can be disputed
+    assert_eq!(
+        client.get_open_resolutions(&resolver),
+        Vec::from_array(&env, [wrong, right])
+    );
+    assert_eq!(
+        client.try_unstake_resolver(&resolver, &100),
+        Err(Ok(Error::DisputePending))
+    );
+
+    client.dispute_outcome(&bob, &wrong, &Outcome::No);
+    client.vote(&voter, &wrong, &Outcome::No, &300);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = resolved_at + 7200;
+    });
+
+    // Overturning the call slashes a fifth of the stake for the bettors
+    assert_eq!(client.settle_dispute(&wrong), Outcome::No);
+    let evs = market_events(&env, &contract_id);
+    let (topics, data) = evs.get_unchecked(1);
+    assert_eq!(
+        topics,
+        (symbol_short!("Slashed"), wrong, resolver.clone()).into_val(&env)
+    );
+    let ev: events::ResolverSlashed = data.into_val(&env);
+    assert_eq!((ev.amount, ev.stake, ev.overturned), (200, 800, 1));
+    assert_eq!(client.get_compensation(&wrong), 200);
+    assert_eq!(
+        client.get_resolver_record(&resolver),
+        ResolverRecord {
+            stake: 800,
+            resolved: 2,
+            overturned: 1,
+            slashed: 200,
+        }
+    );
+    assert_eq!(client.get_resolver_accuracy(&resolver), 5_000);
+    assert_eq!(
+        client.get_resolver_history(&resolver, &0, &10),
+        Vec::from_array(&env, [wrong, right])
+    );
+    assert_eq!(
+        client.get_resolver_history(&resolver, &1, &10),
+        Vec::from_array(&env, [right])
+    );
+
+    // The winners of the corrected outcome take the slash on top of the pool
+    assert_eq!(client.claim(&bob, &wrong), 400);
+    assert_eq!(client.claim(&alice, &right), 200);
+    assert_eq!(kale.balance(&bob), 1_200);
+
+    // With both outcomes final the stake can leave
+    assert_eq!(client.get_open_resolutions(&resolver), Vec::new(&env));
+    assert_eq!(
+        client.try_unstake_resolver(&resolver, &801),
+        Err(Ok(Error::InvalidAmount))
+    );
+    client.unstake_resolver(&resolver, &800);
+    assert_eq!(kale.balance(&resolver), 800);
+    assert_eq!(client.get_resolver_record(&resolver).stake, 0);
+
+    // A resolver a market names keeps min_stake staked until it settles
+    client.stake_resolver(&resolver, &600);
+    let now = env.ledger().timestamp();
+    let assigned = client.create_market(
+        &creator,
+        &String::from_str(&env, "Will it rain tomorrow?"),
+        &(now + 3600),
+        &(now + 7200),
+        &MarketConfig {
+            resolver: ResolverChoice::Account(resolver.clone()),
+            ..default_config()
+        },
+        &default_metadata(&env),
+    );
+    assert_eq!(client.get_open_assignments(&resolver), 1);
+    assert_eq!(
+        client.try_unstake_resolver(&resolver, &101),
+        Err(Ok(Error::InsufficientResolverStake))
+    );
+    client.unstake_resolver(&resolver, &100);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = now + 7200;
+    });
+    client.resolve(&resolver, &assigned, &Outcome::Yes);
+    assert_eq!(client.get_open_assignments(&resolver), 0);
+}
+
+#[test]
+fn test_market_resolver_assignment() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let weather_desk = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let keeper = Address::generate(&env);
+    let (kale_sac, _) = setup_kale_sac(&env);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+
+    let now = env.ledger().timestamp();
+    let create = |resolver: ResolverChoice| {
+        client.create_market(
+            &creator,
+            &String::from_str(&env, "Will it rain tomorrow?"),
+            &(now + 3600),
+            &(now + 7200),
+            &MarketConfig {
+                resolver,
+                ..default_config()
+            },
+            &default_metadata(&env),
+        )
+    };
+    let assigned = create(ResolverChoice::Account(weather_desk.clone()));
+    let abandoned = create(ResolverChoice::Account(weather_desk.clone()));
+    let own_oracle_id = env.register(MockReflector, ());
+    let own_oracle = MockReflectorClient::new(&env, &own_oracle_id);
+    let oracle_settled = create(ResolverChoice::Oracle(own_oracle_id.clone()));
+    let global = create_test_market(&env, &client, &creator);
+    // A named resolver can't be bypassed by an oracle rule keepers settle
+    assert_eq!(
+        client.try_set_price_condition(
+            &assigned,
+            &PriceCondition {
+                asset: Symbol::new(&env, "BTC"),
+                strike: 100_000 * USD,
+            },
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+    client.set_price_condition(
+        &oracle_settled,
+        &PriceCondition {
+            asset: Symbol::new(&env, "BTC"),
+            strike: 100_000 * USD,
+        },
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = now + 7200;
+    });
+
+    // Only the assigned account settles its market, with or without the role
+    assert_eq!(
+        client.try_resolve(&resolver, &assigned, &Outcome::No),
+        Err(Ok(Error::MissingRole))
+    );
+    client.resolve(&weather_desk, &assigned, &Outcome::Yes);
+    assert_eq!(client.get_market(&assigned).outcome, Outcome::Yes);
+    assert_eq!(
+        client.try_resolve(&weather_desk, &global, &Outcome::Yes),
+        Err(Ok(Error::MissingRole))
+    );
+    client.resolve(&resolver, &global, &Outcome::No);
+
+    // An oracle market reads its own feed, not the global one
+    assert_eq!(
+        client.try_resolve(&resolver, &oracle_settled, &Outcome::No),
+        Err(Ok(Error::MissingRole))
+    );
+    own_oracle.set_prices(
+        &ticker(&env, "BTC"),
+        &price_series(&env, &[(101_000 * USD, now + 7200)]),
+    );
+    client.settle_market(&keeper, &oracle_settled);
+    assert_eq!(client.get_market(&oracle_settled).outcome, Outcome::Yes);
+
+    // Global Resolvers take over once the assignee has gone quiet
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = now + 7200 + RESOLVER_FALLBACK_SECS - 1;
+    });
+    assert_eq!(
+        client.try_resolve(&resolver, &abandoned, &Outcome::No),
+        Err(Ok(Error::MissingRole))
+    );
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp += 1;
+    });
+    client.resolve(&resolver, &abandoned, &Outcome::No);
+    assert_eq!(client.get_market(&abandoned).outcome, Outcome::No);
+}
+
+#[test]
+fn test_seasons_and_leaderboards() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let (kale_sac, kale_admin) = setup_kale_sac(&env);
+    let kale = token::TokenClient::new(&env, &kale_sac);
+
+    let contract_id = env.register(FarmersMarket, ());
+    let client = FarmersMarketClient::new(&env, &contract_id);
+
+    env.mock_all_auths();
+    client.init(&admin, &resolver, &kale_sac);
+    for user in [&admin, &alice, &bob, &carol] {
+        kale_admin.mint(user, &1_000);
+    }
+
+    let season = |start_ts: u64, end_ts: u64, metric: RankMetric, prize_bps: &[u32]| SeasonConfig {
+        name: String::from_str(&env, "Harvest"),
+        start_ts,
+        end_ts,
+        metric,
+        prize_bps: Vec::from_slice(&env, prize_bps),
+    };
+    let pnl_season = season(0, 100_000, RankMetric::NetPnl, &[6_000, 3_000, 1_000]);
+    assert_eq!(
+        client.try_create_season(&resolver, &pnl_season, &1_000),
+        Err(Ok(Error::MissingRole))
+    );
+    assert_eq!(
+        client.try_create_season(
+            &admin,
+            &season(0, 100_000, RankMetric::NetPnl, &[6_000, 3_000]),
+            &1_000
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+    let first = client.create_season(&admin, &pnl_season, &1_000);
+    let (topics, data) = market_events(&env, &contract_id).last_unchecked();
+    assert_eq!(topics, (symbol_short!("Season"), first).into_val(&env));
+    let ev: events::SeasonCreated = data.into_val(&env);
+    assert_eq!((ev.metric, ev.prize_pool), (RankMetric::NetPnl, 1_000));
+
+    // Seasons run back to back
+    assert_eq!(
+        client.try_create_season(
+            &admin,
+            &season(50_000, 200_000, RankMetric::Volume, &[10_000]),
+            &0
+        ),
+        Err(Ok(Error::InvalidTime))
+    );
+    let second = client.create_season(
+        &admin,
+        &season(100_000, 200_000, RankMetric::Volume, &[10_000]),
+        &0,
+    );
+    assert_eq!(client.get_season_count(), 2);
+    assert_eq!(client.get_active_season().unwrap().id, first);
+
+    // Bets and claims feed the running season's stats
+    let market_id = create_test_market(&env, &client, &creator);
+    client.bet(&alice, &market_id, &true, &100);
+    client.bet(&bob, &market_id, &false, &300);
+    client.bet(&carol, &market_id, &true, &40);
+    client.bet(&carol, &market_id, &true, &10);
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 7200;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(client.claim(&alice, &market_id), 300);
+    assert_eq!(client.claim(&carol, &market_id), 150);
+
+    assert_eq!(
+        client.get_season_stats(&first, &carol),
+        SeasonStats {
+            volume: 50,
+            markets: 1,
+            wins: 1,
+            pnl: 100,
+        }
+    );
+    assert_eq!(client.get_season_stats(&first, &bob).pnl, -300);
+    let standing = |player: &Address, score: i128| Standing {
+        player: player.clone(),
+        score,
+    };
+    assert_eq!(
+        client.get_leaderboard(&first, &10),
+        Vec::from_array(
+            &env,
+            [
+                standing(&alice, 200),
+                standing(&carol, 100),
+                standing(&bob, -300)
+            ]
+        )
+    );
+    assert_eq!(
+        client.get_leaderboard(&first, &1),
+        Vec::from_array(&env, [standing(&alice, 200)])
+    );
+
+    client.fund_season(&carol, &first, &100);
+    assert_eq!(client.get_season(&first).unwrap().prize_pool, 1_100);
+    assert_eq!(
+        client.try_finalize_season(&first),
+        Err(Ok(Error::InvalidTime))
+    );
+
+    // Only positive scores place; the unplaced third share goes to the treasury
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 100_000;
+    });
+    assert_eq!(client.get_active_season().unwrap().id, second);
+    assert_eq!(client.finalize_season(&first), 990);
+    let evs = market_events(&env, &contract_id);
+    let (topics, data) = evs.get_unchecked(0);
+    assert_eq!(
+        topics,
+        (symbol_short!("Prize"), first, alice.clone()).into_val(&env)
+    );
+    let ev: events::SeasonPrizePaid = data.into_val(&env);
+    assert_eq!((ev.rank, ev.prize), (1, 660));
+    let (topics, _) = evs.last_unchecked();
+    assert_eq!(topics, (symbol_short!("SeasonEnd"), first).into_val(&env));
+    assert_eq!(kale.balance(&alice), 1_000 - 100 + 300 + 660);
+    assert_eq!(kale.balance(&carol), 1_000 - 50 + 150 - 100 + 330);
+    assert_eq!(kale.balance(&admin), 110);
+    assert_eq!(
+        client.try_finalize_season(&first),
+        Err(Ok(Error::AlreadyResolved))
+    );
+    assert_eq!(
+        client.try_fund_season(&carol, &first, &10),
+        Err(Ok(Error::AlreadyResolved))
+    );
+    assert_eq!(
+        client.try_finalize_season(&7),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    // The next season starts from a clean slate
+    let market_id = create_test_market(&env, &client, &creator);
+    client.bet(&bob, &market_id, &false, &25);
+    assert_eq!(client.get_season_stats(&second, &bob).volume, 25);
+    assert_eq!(client.get_season_stats(&first, &bob).volume, 300);
+    assert_eq!(
+        client.get_leaderboard(&second, &10),
+        Vec::from_array(&env, [standing(&bob, 25)])
+    );
+
+    // Betting both sides only counts the net exposure it adds
+    client.bet(&bob, &market_id, &true, &25);
+    assert_eq!(client.get_season_stats(&second, &bob).volume, 25);
+    client.bet(&bob, &market_id, &true, &10);
+    assert_eq!(client.get_season_stats(&second, &bob).volume, 35);
+
+    // Shares carry their cost through transfers and order fills
+    client.bet(&carol, &market_id, &true, &40);
+    client.share_transfer(&carol, &alice, &market_id, &true, &20);
+    assert_eq!(client.get_season_stats(&second, &carol).pnl, -20);
+    assert_eq!(client.get_season_stats(&second, &alice).pnl, -20);
+    client.place_order(&alice, &market_id, &OrderSide::Ask, &5_000, &20);
+    client.place_order(&bob, &market_id, &OrderSide::Bid, &5_000, &20);
+    assert_eq!(client.get_season_stats(&second, &alice).pnl, -10);
+    assert_eq!(client.get_season_stats(&second, &bob).pnl, -70);
+
+    env.ledger().with_mut(|ledger| {
+        ledger.timestamp = 107_200;
+    });
+    client.resolve(&resolver, &market_id, &Outcome::Yes);
+    assert_eq!(client.claim(&bob, &market_id), 73);
+    assert_eq!(client.claim(&carol, &market_id), 26);
+    assert_eq!(client.get_season_stats(&second, &bob).pnl, 3);
+    assert_eq!(client.get_season_stats(&second, &carol).pnl, 6);
+}
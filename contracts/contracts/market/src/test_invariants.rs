@@ -0,0 +1,380 @@
+#![cfg(test)]
+//! Property tests for pool and payout accounting.
+//!
+//! Random sequences of `create_market`, `bet`, `resolve`, `cancel_market` and
+//! `claim` run across many users, under a random protocol fee. After every
+//! step the contract's books are checked against what the harness saw leave
+//! it.
+extern crate std;
+
+use super::*;
+use proptest::prelude::*;
+use soroban_sdk::{
+    testutils::{Address as _, EnvTestConfig, Ledger},
+    token, Address, Env, String, Vec,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use timelock::TimelockAction;
+
+const USERS: usize = 6;
+const MAX_MARKETS: usize = 4;
+const BOND: i128 = 50;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Create {
+        creator: usize,
+        sweep: bool,
+        early_bonus_bps: u32,
+    },
+    Bet {
+        user: usize,
+        market: usize,
+        side_yes: bool,
+        amount: i128,
+    },
+    Advance {
+        secs: u64,
+    },
+    Resolve {
+        market: usize,
+        outcome: Outcome,
+    },
+    Cancel {
+        market: usize,
+    },
+    Claim {
+        user: usize,
+        market: usize,
+    },
+}
+
+fn outcome() -> impl Strategy<Value = Outcome> {
+    prop_oneof![
+        Just(Outcome::Yes),
+        Just(Outcome::No),
+        Just(Outcome::Invalid)
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        1 => (0..USERS, any::<bool>(), prop_oneof![Just(0u32), 1u32..=10_000]).prop_map(
+            |(creator, sweep, early_bonus_bps)| Op::Create { creator, sweep, early_bonus_bps }
+        ),
+        6 => (0..USERS, 0..MAX_MARKETS, any::<bool>(), 1i128..=1_000).prop_map(
+            |(user, market, side_yes, amount)| Op::Bet { user, market, side_yes, amount }
+        ),
+        2 => (0u64..=4_000).prop_map(|secs| Op::Advance { secs }),
+        2 => (0..MAX_MARKETS, outcome())
+            .prop_map(|(market, outcome)| Op::Resolve { market, outcome }),
+        1 => (0..MAX_MARKETS).prop_map(|market| Op::Cancel { market }),
+        3 => (0..USERS, 0..MAX_MARKETS).prop_map(|(user, market)| Op::Claim { user, market }),
+    ]
+}
+
+struct Harness<'a> {
+    env: Env,
+    contract_id: Address,
+    client: FarmersMarketClient<'a>,
+    kale: token::StellarAssetClient<'a>,
+    token: token::TokenClient<'a>,
+    admin: Address, // also the treasury
+    resolver: Address,
+    users: std::vec::Vec<Address>,
+    markets: std::vec::Vec<u32>,
+    // KALE that left each market's pool through claims, fees or a treasury
+    // sweep
+    released: BTreeMap<u32, i128>,
+    claimed: BTreeSet<(u32, usize)>,
+}
+
+impl Harness<'_> {
+    fn new(fee_bps: u32) -> Self {
+        // Hundreds of cases; skip writing a ledger snapshot for each one
+        let env = Env::new_with_config(EnvTestConfig {
+            capture_snapshot_at_drop: false,
+        });
+        env.mock_all_auths();
+
+        let kale_sac = env.register_stellar_asset_contract_v2(Address::generate(&env));
+        let kale = token::StellarAssetClient::new(&env, &kale_sac.address());
+        let token = token::TokenClient::new(&env, &kale_sac.address());
+        let contract_id = env.register(FarmersMarket, ());
+        let client = FarmersMarketClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let resolver = Address::generate(&env);
+        client.init(&admin, &resolver, &kale_sac.address());
+        client.set_creation_policy(
+            &admin,
+            &CreationPolicy {
+                bond: BOND,
+                allowlist_enabled: false,
+                max_per_window: 0,
+                window_secs: 0,
+            },
+        );
+        // Half the fee stays in the contract as keeper bounties
+        let action_id = client.schedule_action(
+            &admin,
+            &TimelockAction::SetFeeConfig(FeeConfig {
+                fee_bps,
+                bounty_share_bps: 5_000,
+                lp_share_bps: 0,
+            }),
+        );
+        client.execute_action(&action_id);
+
+        let users = (0..USERS).map(|_| Address::generate(&env)).collect();
+        Harness {
+            env,
+            contract_id,
+            client,
+            kale,
+            token,
+            admin,
+            resolver,
+            users,
+            markets: std::vec::Vec::new(),
+            released: BTreeMap::new(),
+            claimed: BTreeSet::new(),
+        }
+    }
+
+    fn market(&self, index: usize) -> Option<u32> {
+        if self.markets.is_empty() {
+            None
+        } else {
+            Some(self.markets[index % self.markets.len()])
+        }
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match *op {
+            Op::Create {
+                creator,
+                sweep,
+                early_bonus_bps,
+            } => {
+                if self.markets.len() == MAX_MARKETS {
+                    return;
+                }
+                let creator = &self.users[creator];
+                self.kale.mint(creator, &BOND);
+                let now = self.env.ledger().timestamp();
+                let config = MarketConfig {
+                    no_winner_policy: if sweep {
+                        NoWinnerPolicy::Treasury
+                    } else {
+                        NoWinnerPolicy::Refund
+                    },
+                    bet_limits: BetLimitsChoice::Default,
+                    subsidy: 0,
+                    early_bonus_bps,
+                    commit_reveal: CommitRevealMode::Off,
+                    resolver: ResolverChoice::Default,
+                };
+                let metadata = MarketMetadata {
+                    description: String::from_str(&self.env, ""),
+                    category: Category::Other,
+                    tags: Vec::new(&self.env),
+                    resolution_source: String::from_str(&self.env, "https://example.com"),
+                    content_hash: None,
+                };
+                let id = self.client.create_market(
+                    creator,
+                    &String::from_str(&self.env, "Will it happen?"),
+                    &(now + 3600),
+                    &(now + 7200),
+                    &config,
+                    &metadata,
+                );
+                self.markets.push(id);
+            }
+            Op::Bet {
+                user,
+                market,
+                side_yes,
+                amount,
+            } => {
+                let Some(id) = self.market(market) else {
+                    return;
+                };
+                let user = &self.users[user];
+                let before = self.client.get_market(&id);
+                self.kale.mint(user, &amount);
+                let res = self.client.try_bet(user, &id, &side_yes, &amount);
+                let after = self.client.get_market(&id);
+
+                let (dy, dn) = match res {
+                    Ok(_) if side_yes => (amount, 0),
+                    Ok(_) => (0, amount),
+                    Err(Ok(Error::MarketClosed | Error::BetsClosed)) => (0, 0),
+                    Err(e) => panic!("bet rejected unexpectedly: {:?}", e),
+                };
+                assert_eq!(after.yes_pool, before.yes_pool + dy);
+                assert_eq!(after.no_pool, before.no_pool + dn);
+            }
+            Op::Advance { secs } => {
+                self.env.ledger().with_mut(|ledger| {
+                    ledger.timestamp += secs;
+                });
+            }
+            Op::Resolve {
+                market,
+                ref outcome,
+            } => {
+                let Some(id) = self.market(market) else {
+                    return;
+                };
+                self.resolve(id, outcome);
+            }
+            Op::Cancel { market } => {
+                let Some(id) = self.market(market) else {
+                    return;
+                };
+                let res = self.client.try_cancel_market(
+                    &self.admin,
+                    &id,
+                    &String::from_str(&self.env, "void"),
+                    &true,
+                );
+                assert!(matches!(res, Ok(_) | Err(Ok(Error::AlreadyResolved))));
+            }
+            Op::Claim { user, market } => {
+                let Some(id) = self.market(market) else {
+                    return;
+                };
+                self.claim(id, user);
+            }
+        }
+    }
+
+    fn resolve(&mut self, id: u32, outcome: &Outcome) {
+        let treasury_before = self.token.balance(&self.admin);
+        match self.client.try_resolve(&self.resolver, &id, outcome) {
+            Ok(_) => {
+                let swept = self.token.balance(&self.admin) - treasury_before;
+                *self.released.entry(id).or_default() += swept;
+            }
+            Err(Ok(Error::AlreadyResolved | Error::InvalidTime)) => {}
+            Err(e) => panic!("resolve failed: {:?}", e),
+        }
+    }
+
+    fn claim(&mut self, id: u32, user: usize) {
+        let addr = &self.users[user];
+        let balance_before = self.token.balance(addr);
+        let treasury_before = self.token.balance(&self.admin);
+        let bounties_before = self.client.get_bounty_pool();
+        match self.client.try_claim(addr, &id) {
+            Ok(Ok(payout)) => {
+                assert!(payout > 0);
+                assert!(
+                    self.claimed.insert((id, user)),
+                    "user {} claimed market {} twice",
+                    user,
+                    id
+                );
+                assert!(self.client.get_stake(&id, addr).claimed);
+                assert_eq!(self.token.balance(addr) - balance_before, payout);
+                let fee = self.token.balance(&self.admin) - treasury_before
+                    + self.client.get_bounty_pool()
+                    - bounties_before;
+                assert!(fee >= 0);
+                *self.released.entry(id).or_default() += payout + fee;
+            }
+            Err(Ok(Error::NotResolved | Error::NothingToClaim)) => {
+                assert_eq!(self.token.balance(addr), balance_before);
+            }
+            // A transfer the contract can't cover surfaces as a host error
+            res => panic!("claim failed: {:?}", res),
+        }
+    }
+
+    fn check_invariants(&self) {
+        let mut owed = 0;
+        for &id in &self.markets {
+            let market = self.client.get_market(&id);
+            let pool = market.yes_pool + market.no_pool;
+            let released = self.released.get(&id).copied().unwrap_or(0);
+            assert!(
+                released <= pool,
+                "market {} paid out {} from a pool of {}",
+                id,
+                released,
+                pool
+            );
+
+            let odds = self.client.get_odds(&id);
+            assert!(odds <= 10_000, "odds {} out of range", odds);
+
+            owed += pool - released + market.bond;
+        }
+        // Every stroop the contract holds is a stake, dust, a bond or a
+        // keeper bounty
+        assert_eq!(
+            self.token.balance(&self.contract_id),
+            owed + self.client.get_bounty_pool()
+        );
+    }
+
+    /// Settle whatever is still open and let everyone claim everything
+    fn settle_all(&mut self) {
+        self.env.ledger().with_mut(|ledger| {
+            ledger.timestamp += 7200;
+        });
+        for id in self.markets.clone() {
+            self.resolve(id, &Outcome::Yes);
+        }
+        for id in self.markets.clone() {
+            for user in 0..USERS {
+                self.claim(id, user);
+            }
+        }
+        self.check_invariants();
+
+        for &id in &self.markets {
+            let market = self.client.get_market(&id);
+            let pool = market.yes_pool + market.no_pool;
+            let released = self.released.get(&id).copied().unwrap_or(0);
+            let winners = (0..USERS)
+                .filter(|user| self.claimed.contains(&(id, *user)))
+                .count() as i128;
+            // Pro-rata rounding loses less than one stroop per winner
+            assert!(
+                pool - released < winners.max(1),
+                "market {} left {} undistributed across {} winners",
+                id,
+                pool - released,
+                winners
+            );
+
+            // Claimed stakes can't be claimed again
+            for user in 0..USERS {
+                assert_eq!(
+                    self.client.try_claim(&self.users[user], &id),
+                    Err(Ok(Error::NothingToClaim))
+                );
+            }
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_pools_and_payouts_stay_consistent(
+        fee_bps in prop_oneof![Just(0u32), 1u32..=2_000],
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        let mut h = Harness::new(fee_bps);
+        for op in &ops {
+            h.apply(op);
+            h.check_invariants();
+        }
+        h.settle_all();
+    }
+}
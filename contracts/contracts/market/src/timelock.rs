@@ -0,0 +1,244 @@
+//! Timelocked governance.
+//!
+//! Changes that could catch users out (the oracle, the fee split, a
+//! contract upgrade and the delay itself) don't apply directly. A
+//! role holder schedules a `TimelockAction`, and it becomes executable once
+//! the timelock delay has passed. Until then anyone watching
+//! `get_pending_actions` can see it coming and exit. The scheduler or an
+//! Admin can cancel it before it runs. Once due, anyone may execute it
+//! within `TIMELOCK_GRACE_SECS`; after that it expires and must be
+//! scheduled again. The delay starts at zero so a new deployment can be
+//! configured, and should be raised with a `SetDelay` action before users
+//! arrive. The KALE token is fixed at `init`: every stake, order, pool and
+//! bond is escrowed in it, so it can't be swapped out from under them.
+use soroban_sdk::{contractimpl, contracttype, Address, BytesN, Env, Vec};
+
+use crate::roles::{holds_role, require_role, Role};
+use crate::{
+    events, now, require_initialized, write_address, DataKey, Error, FarmersMarket,
+    FarmersMarketArgs, FarmersMarketClient, FeeConfig,
+};
+
+// Longest delay an Admin may configure
+pub const MAX_TIMELOCK_DELAY: u64 = 30 * 86_400;
+// How long a due action stays executable
+pub const TIMELOCK_GRACE_SECS: u64 = 14 * 86_400;
+// Cap on queued actions, so the pending list stays cheap to read
+pub const MAX_PENDING_ACTIONS: u32 = 20;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum TimelockAction {
+    SetOracle(Address),      // OracleManager
+    SetFeeConfig(FeeConfig), // FeeManager
+    Upgrade(BytesN<32>),     // Admin; hash of already uploaded wasm
+    SetDelay(u64),           // Admin
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PendingAction {
+    pub id: u32,
+    pub action: TimelockAction,
+    pub proposer: Address,
+    pub scheduled_at: u64,
+    pub eta: u64, // earliest execution time
+}
+
+/// Role that may schedule an action
+fn action_role(action: &TimelockAction) -> Role {
+    match action {
+        TimelockAction::SetOracle(_) => Role::OracleManager,
+        TimelockAction::SetFeeConfig(_) => Role::FeeManager,
+        TimelockAction::Upgrade(_) | TimelockAction::SetDelay(_) => Role::Admin,
+    }
+}
+
+fn validate_action(action: &TimelockAction) -> Result<(), Error> {
+    match action {
+        TimelockAction::SetFeeConfig(fees)
+            if fees.fee_bps > 10_000
+                || fees.bounty_share_bps > 10_000
+                || fees.lp_share_bps > 10_000 =>
+        {
+            Err(Error::InvalidAmount)
+        }
+        TimelockAction::SetDelay(delay) if *delay > MAX_TIMELOCK_DELAY => Err(Error::InvalidTime),
+        _ => Ok(()),
+    }
+}
+
+fn read_delay(e: &Env) -> u64 {
+    e.storage()
+        .instance()
+        .get(&DataKey::TimelockDelay)
+        .unwrap_or(0)
+}
+
+fn read_pending_ids(e: &Env) -> Vec<u32> {
+    e.storage()
+        .instance()
+        .get(&DataKey::PendingActions)
+        .unwrap_or(Vec::new(e))
+}
+
+fn is_expired(pending: &PendingAction, t: u64) -> bool {
+    t > pending.eta + TIMELOCK_GRACE_SECS
+}
+
+/// Drop actions past their grace period, returning the ids still queued
+fn prune_expired(e: &Env) -> Vec<u32> {
+    let t = now(e);
+    let mut live = Vec::new(e);
+    for id in read_pending_ids(e).iter() {
+        match read_action(e, id) {
+            Ok(pending) if !is_expired(&pending, t) => live.push_back(id),
+            _ => e
+                .storage()
+                .persistent()
+                .remove(&DataKey::TimelockAction(id)),
+        }
+    }
+    e.storage().instance().set(&DataKey::PendingActions, &live);
+    live
+}
+
+fn read_action(e: &Env, id: u32) -> Result<PendingAction, Error> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::TimelockAction(id))
+        .ok_or(Error::ActionNotFound)
+}
+
+/// Drop an action from the queue, executed or not
+fn remove_action(e: &Env, id: u32) {
+    e.storage()
+        .persistent()
+        .remove(&DataKey::TimelockAction(id));
+    let mut ids = read_pending_ids(e);
+    if let Some(i) = ids.first_index_of(id) {
+        ids.remove(i);
+    }
+    e.storage().instance().set(&DataKey::PendingActions, &ids);
+}
+
+fn apply_action(e: &Env, action: &TimelockAction) {
+    match action {
+        TimelockAction::SetOracle(oracle) => {
+            write_address(e, DataKey::ReflectorOracle, oracle.clone());
+            events::oracle_updated(e, oracle.clone());
+        }
+        TimelockAction::SetFeeConfig(fees) => {
+            e.storage().instance().set(&DataKey::FeeConfig, fees);
+            events::fee_config_updated(e, fees.clone());
+        }
+        TimelockAction::Upgrade(wasm_hash) => {
+            e.deployer().update_current_contract_wasm(wasm_hash.clone());
+        }
+        TimelockAction::SetDelay(delay) => {
+            e.storage().instance().set(&DataKey::TimelockDelay, delay);
+        }
+    }
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Queue a governance action. It can run once the current delay has
+    /// passed.
+    pub fn schedule_action(
+        e: Env,
+        proposer: Address,
+        action: TimelockAction,
+    ) -> Result<u32, Error> {
+        require_initialized(&e)?;
+        require_role(&e, &proposer, action_role(&action))?;
+        validate_action(&action)?;
+
+        let mut ids = prune_expired(&e);
+        if ids.len() >= MAX_PENDING_ACTIONS {
+            return Err(Error::TooManyPendingActions);
+        }
+        let id: u32 = e
+            .storage()
+            .instance()
+            .get(&DataKey::NextActionId)
+            .unwrap_or(0);
+        e.storage()
+            .instance()
+            .set(&DataKey::NextActionId, &(id + 1));
+
+        let t = now(&e);
+        let pending = PendingAction {
+            id,
+            action,
+            proposer,
+            scheduled_at: t,
+            eta: t + read_delay(&e),
+        };
+        e.storage()
+            .persistent()
+            .set(&DataKey::TimelockAction(id), &pending);
+        ids.push_back(id);
+        e.storage().instance().set(&DataKey::PendingActions, &ids);
+
+        events::action_scheduled(&e, &pending);
+        Ok(id)
+    }
+
+    /// Withdraw a queued action (its proposer or an Admin)
+    pub fn cancel_action(e: Env, caller: Address, action_id: u32) -> Result<(), Error> {
+        require_initialized(&e)?;
+        caller.require_auth();
+
+        let pending = read_action(&e, action_id)?;
+        if caller != pending.proposer && !holds_role(&e, Role::Admin, &caller) {
+            return Err(Error::MissingRole);
+        }
+        remove_action(&e, action_id);
+
+        events::action_cancelled(&e, action_id, caller);
+        Ok(())
+    }
+
+    /// Run a due action. Anyone can call this between its `eta` and the end
+    /// of the grace period.
+    pub fn execute_action(e: Env, action_id: u32) -> Result<(), Error> {
+        require_initialized(&e)?;
+
+        let pending = read_action(&e, action_id)?;
+        let t = now(&e);
+        if t < pending.eta {
+            return Err(Error::ActionNotReady);
+        }
+        if is_expired(&pending, t) {
+            return Err(Error::ActionExpired);
+        }
+        remove_action(&e, action_id);
+
+        events::action_executed(&e, &pending);
+        apply_action(&e, &pending.action);
+        Ok(())
+    }
+
+    /// Actions still queued and not yet expired, oldest first
+    pub fn get_pending_actions(e: Env) -> Vec<PendingAction> {
+        let t = now(&e);
+        let mut out = Vec::new(&e);
+        for id in read_pending_ids(&e).iter() {
+            if let Ok(pending) = read_action(&e, id) {
+                if !is_expired(&pending, t) {
+                    out.push_back(pending);
+                }
+            }
+        }
+        out
+    }
+
+    pub fn get_pending_action(e: Env, action_id: u32) -> Option<PendingAction> {
+        read_action(&e, action_id).ok()
+    }
+
+    pub fn get_timelock_delay(e: Env) -> u64 {
+        read_delay(&e)
+    }
+}
@@ -0,0 +1,58 @@
+//! Time-weighted stakes.
+//!
+//! With `MarketConfig.early_bonus_bps` set, KALE staked when a market opens
+//! counts for `1x + bonus` and the multiplier decays linearly to 1x at
+//! `close_ts`, so a bet placed just before close can't earn what an early
+//! one does. `Stake` and `Market` keep these weighted amounts next to the
+//! raw ones and `claim` splits the pool by weight. Markets without a bonus
+//! weigh every stroop at 1x and pay exactly as plain parimutuel.
+use soroban_sdk::{contractimpl, Env};
+
+use crate::{
+    now, read_market, Error, FarmersMarket, FarmersMarketArgs, FarmersMarketClient, Market, Stake,
+};
+
+// Multipliers are in basis points, 10_000 = 1x
+pub const WEIGHT_SCALE: i128 = 10_000;
+// At most a 2x weight for the earliest stake
+pub const MAX_EARLY_BONUS_BPS: u32 = 10_000;
+
+/// Weight multiplier for KALE staked at time `t`
+pub(crate) fn weight_multiplier(m: &Market, t: u64) -> i128 {
+    let bonus = m.config.early_bonus_bps as i128;
+    if bonus == 0 || t >= m.close_ts {
+        return WEIGHT_SCALE;
+    }
+    let window = m.close_ts.saturating_sub(m.created_ts).max(1) as i128;
+    let left = (m.close_ts - t.max(m.created_ts)) as i128;
+    WEIGHT_SCALE + bonus * left / window
+}
+
+/// Weight of `amount` KALE staked now
+pub(crate) fn weigh(e: &Env, m: &Market, amount: i128) -> i128 {
+    amount * weight_multiplier(m, now(e)) / WEIGHT_SCALE
+}
+
+/// Weight carried by `amount` of a holder's shares on one side. Moving
+/// every share moves all the weight, so nothing is stranded by rounding.
+pub(crate) fn share_weight(stake: &Stake, side_yes: bool, amount: i128) -> i128 {
+    let (shares, weight) = if side_yes {
+        (stake.yes, stake.yes_weight)
+    } else {
+        (stake.no, stake.no_weight)
+    };
+    if amount >= shares {
+        weight
+    } else {
+        weight * amount / shares
+    }
+}
+
+#[contractimpl]
+impl FarmersMarket {
+    /// Multiplier a stake placed now would get, in basis points
+    pub fn get_weight_multiplier(e: Env, market_id: u32) -> Result<u32, Error> {
+        let market = read_market(&e, market_id)?;
+        Ok(weight_multiplier(&market, now(&e)) as u32)
+    }
+}